@@ -0,0 +1,828 @@
+//! Campaign pipeline: CSV import, cost preview, checkpointed sending, and status collection.
+//!
+//! A [`Campaign`] is built from a CSV file with one recipient per row and a [`MessageTemplate`]
+//! whose placeholders name CSV columns. Numbers are normalized to E.164 and deduplicated; rows
+//! that cannot be sent are kept in [`Campaign::rejected`].
+//!
+//! [`Campaign::run`] sends the recipients in fixed chunks and rewrites a JSON checkpoint file
+//! before and after every chunk. Running again with the same checkpoint skips finished chunks;
+//! the checkpoint records each chunk's recipients and a fingerprint of its rendered texts and
+//! options, so resuming with a changed CSV, template, or options fails instead of mixing runs.
+//! A chunk that was started but never recorded (the process died while SMS.RU was answering)
+//! is *not* sent again: it is reported as [`CampaignReport::uncertain`]. Sending is therefore
+//! at-most-once, unlike the [`crate::outbox`].
+
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error as StdError;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use phonenumber::country;
+use serde::{Deserialize, Serialize};
+
+use crate::client::{SmsRuClient, SmsRuError};
+use crate::csv;
+use crate::domain::{
+    CHECK_STATUS_MAX_SMS_IDS, CheckCost, CheckStatus, MessageTemplate, MessageText, RawPhoneNumber,
+    SEND_SMS_MAX_RECIPIENTS, SendOptions, SendSms, SendSmsResponse, SmsId, SmsStatusResult, Status,
+    StatusCode, TemplateError, ValidationError, canonicalize_phone,
+};
+use crate::transport::send_sms_fingerprint;
+
+const DEFAULT_PHONE_COLUMN: &str = "phone";
+const CHECKPOINT_VERSION: u32 = 2;
+
+#[derive(Debug, Clone)]
+/// How [`Campaign::from_csv_with`] reads a CSV file.
+pub struct CsvImport {
+    phone_column: String,
+    default_region: Option<country::Id>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A recipient that will receive a campaign message.
+pub struct CampaignRecipient {
+    /// CSV line the row starts on (the header is line 1).
+    pub line: usize,
+    /// Number as sent to SMS.RU: E.164 digits without `+`.
+    pub phone: RawPhoneNumber,
+    /// Rendered message text.
+    pub text: MessageText,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A CSV row that was not turned into a recipient.
+pub struct RejectedRow {
+    /// CSV line the row starts on.
+    pub line: usize,
+    /// Phone column as written in the file.
+    pub phone: String,
+    /// Why the row was rejected.
+    pub reason: RejectReason,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Reason a CSV row was rejected.
+pub enum RejectReason {
+    /// The phone number could not be parsed.
+    InvalidPhone(ValidationError),
+    /// The number (after normalization) already appeared on `first_line`.
+    Duplicate { first_line: usize },
+    /// The template could not be rendered for this row.
+    Template(TemplateError),
+}
+
+#[derive(Debug, Clone)]
+/// Recipients and rendered messages of a campaign, split into send chunks.
+pub struct Campaign {
+    recipients: Vec<CampaignRecipient>,
+    rejected: Vec<RejectedRow>,
+    options: SendOptions,
+    chunk_size: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Result of [`Campaign::preview_cost`].
+pub struct CostPreview {
+    /// Sum of `total_cost` over all chunks.
+    pub total_cost: f64,
+    /// Sum of `total_sms` (segments) over all chunks.
+    pub total_sms: u32,
+    /// Recipients SMS.RU would not send to, with the per-recipient status code.
+    pub rejected: Vec<(RawPhoneNumber, StatusCode)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A message accepted by SMS.RU, see [`CampaignReport::messages`].
+pub struct CampaignMessage {
+    /// CSV line of the recipient.
+    pub line: usize,
+    /// Recipient number.
+    pub phone: RawPhoneNumber,
+    /// Latest delivery status, filled by [`CampaignReport::collect_statuses`].
+    pub status: Option<SmsStatusResult>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A recipient that was not accepted by SMS.RU, or whose outcome is unknown.
+pub struct CampaignFailure {
+    /// CSV line of the recipient.
+    pub line: usize,
+    /// Recipient number.
+    pub phone: RawPhoneNumber,
+    /// Per-recipient status code (`None` for [`CampaignReport::uncertain`] entries).
+    pub status_code: Option<StatusCode>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Outcome of a campaign, built from its checkpoint.
+pub struct CampaignReport {
+    /// Accepted messages keyed by the SMS id assigned by SMS.RU.
+    pub messages: BTreeMap<SmsId, CampaignMessage>,
+    /// Recipients SMS.RU rejected.
+    pub failed: Vec<CampaignFailure>,
+    /// Recipients of chunks that were interrupted mid-send and are not sent again.
+    pub uncertain: Vec<CampaignFailure>,
+    /// Recipients of chunks that have not been sent yet.
+    pub pending: Vec<CampaignRecipient>,
+    /// CSV rows rejected during import.
+    pub rejected: Vec<RejectedRow>,
+}
+
+#[derive(Debug, thiserror::Error)]
+/// Errors produced by the campaign pipeline.
+pub enum CampaignError {
+    /// Reading the CSV or checkpoint file failed.
+    #[error("campaign I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    /// The CSV input is malformed.
+    #[error("invalid CSV at line {line}: {reason}")]
+    Csv { line: usize, reason: &'static str },
+
+    /// A column required by the phone column or the template is missing from the header.
+    #[error("CSV header has no `{0}` column")]
+    MissingColumn(String),
+
+    /// The checkpoint file could not be decoded.
+    #[error("corrupt campaign checkpoint {path:?}: {source}")]
+    Checkpoint {
+        path: PathBuf,
+        #[source]
+        source: Box<dyn StdError + Send + Sync>,
+    },
+
+    /// The checkpoint was written for different recipients, texts, options, or chunking.
+    #[error("campaign checkpoint {0:?} belongs to a different campaign")]
+    CheckpointMismatch(PathBuf),
+
+    /// Sending chunk `chunk` failed; the run stopped and can be resumed.
+    #[error("sending campaign chunk {chunk} failed: {source}")]
+    Send {
+        chunk: usize,
+        #[source]
+        source: SmsRuError,
+    },
+}
+
+impl Default for CsvImport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CsvImport {
+    /// Read phone numbers from the `phone` column, defaulting to Russian numbering.
+    pub fn new() -> Self {
+        Self {
+            phone_column: DEFAULT_PHONE_COLUMN.to_owned(),
+            default_region: Some(country::RU),
+        }
+    }
+
+    /// Column holding the phone number.
+    pub fn phone_column(mut self, column: impl Into<String>) -> Self {
+        self.phone_column = column.into();
+        self
+    }
+
+    /// Region assumed for numbers without a country prefix (`None` requires one).
+    pub fn default_region(mut self, region: Option<country::Id>) -> Self {
+        self.default_region = region;
+        self
+    }
+}
+
+impl Campaign {
+    /// Import a campaign from CSV with [`CsvImport::new`] settings.
+    pub fn from_csv(
+        reader: impl Read,
+        template: &MessageTemplate,
+        options: SendOptions,
+    ) -> Result<Self, CampaignError> {
+        Self::from_csv_with(reader, template, options, &CsvImport::new())
+    }
+
+    /// Import a campaign from CSV.
+    ///
+    /// The header must contain the phone column and one column per template placeholder;
+    /// other columns are ignored.
+    pub fn from_csv_with(
+        mut reader: impl Read,
+        template: &MessageTemplate,
+        options: SendOptions,
+        import: &CsvImport,
+    ) -> Result<Self, CampaignError> {
+        let mut input = String::new();
+        reader.read_to_string(&mut input)?;
+        let mut records = csv::parse(&input)
+            .map_err(|err| CampaignError::Csv {
+                line: err.line,
+                reason: err.reason,
+            })?
+            .into_iter();
+        let Some(header) = records.next() else {
+            return Err(CampaignError::MissingColumn(import.phone_column.clone()));
+        };
+        let column = |name: &str| {
+            header
+                .fields
+                .iter()
+                .position(|field| field.trim() == name)
+                .ok_or_else(|| CampaignError::MissingColumn(name.to_owned()))
+        };
+        let phone_column = column(&import.phone_column)?;
+        let variable_columns = template
+            .placeholders()
+            .iter()
+            .map(|name| Ok((name.clone(), column(name)?)))
+            .collect::<Result<Vec<_>, CampaignError>>()?;
+
+        let mut recipients = Vec::new();
+        let mut rejected = Vec::new();
+        let mut first_lines = HashMap::new();
+        for record in records {
+            if record.fields.len() != header.fields.len() {
+                return Err(CampaignError::Csv {
+                    line: record.line,
+                    reason: "row has a different number of fields than the header",
+                });
+            }
+            let raw_phone = record.fields[phone_column].clone();
+            let reject = |reason| RejectedRow {
+                line: record.line,
+                phone: raw_phone.clone(),
+                reason,
+            };
+
+            // The same canonical form `SendSms::normalize` uses.
+            let phone = match canonicalize_phone(import.default_region, raw_phone.as_str()) {
+                Ok(phone) => phone,
+                Err(err) => {
+                    rejected.push(reject(RejectReason::InvalidPhone(err)));
+                    continue;
+                }
+            };
+            if let Some(&first_line) = first_lines.get(&phone) {
+                rejected.push(reject(RejectReason::Duplicate { first_line }));
+                continue;
+            }
+
+            let variables = variable_columns
+                .iter()
+                .map(|(name, index)| (name.clone(), record.fields[*index].clone()))
+                .collect();
+            let text = match template.render(&variables) {
+                Ok(text) => text,
+                Err(err) => {
+                    rejected.push(reject(RejectReason::Template(err)));
+                    continue;
+                }
+            };
+
+            first_lines.insert(phone.clone(), record.line);
+            recipients.push(CampaignRecipient {
+                line: record.line,
+                phone,
+                text,
+            });
+        }
+
+        Ok(Self {
+            recipients,
+            rejected,
+            options,
+            chunk_size: SEND_SMS_MAX_RECIPIENTS,
+        })
+    }
+
+    /// Recipients per `sms/send` request, clamped to `1..=SEND_SMS_MAX_RECIPIENTS`.
+    ///
+    /// Changing the chunk size invalidates existing checkpoints.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.clamp(1, SEND_SMS_MAX_RECIPIENTS);
+        self
+    }
+
+    /// Recipients in CSV order.
+    pub fn recipients(&self) -> &[CampaignRecipient] {
+        &self.recipients
+    }
+
+    /// Rows rejected during import.
+    pub fn rejected(&self) -> &[RejectedRow] {
+        &self.rejected
+    }
+
+    /// Ask `sms/cost` what the campaign would cost, one request per chunk.
+    pub async fn preview_cost(&self, client: &SmsRuClient) -> Result<CostPreview, SmsRuError> {
+        let mut preview = CostPreview {
+            total_cost: 0.0,
+            total_sms: 0,
+            rejected: Vec::new(),
+        };
+        for chunk in self.chunks() {
+            let response = client
+                .check_cost(CheckCost::from(&self.request(chunk)))
+                .await?;
+            if let Some(cost) = response.total_cost.as_deref() {
+                preview.total_cost += cost.trim().parse::<f64>().map_err(|err| {
+                    SmsRuError::Parse(format!("invalid total_cost {cost:?}: {err}").into())
+                })?;
+            }
+            preview.total_sms += response.total_sms.unwrap_or(0);
+            preview.rejected.extend(
+                response
+                    .sms
+                    .into_iter()
+                    .filter(|(_, result)| result.status != Status::Ok)
+                    .map(|(phone, result)| (phone, result.status_code)),
+            );
+        }
+        Ok(preview)
+    }
+
+    /// Send every chunk that the checkpoint at `checkpoint` does not record yet.
+    ///
+    /// The checkpoint is created if missing and rewritten before and after each chunk. On a
+    /// send error the run stops with [`CampaignError::Send`]; errors that prove the chunk was
+    /// not accepted (API, validation, and guard errors) leave it pending for the next run,
+    /// others (transport, unexpected HTTP status) mark it uncertain.
+    pub async fn run(
+        &self,
+        client: &SmsRuClient,
+        checkpoint: impl AsRef<Path>,
+    ) -> Result<CampaignReport, CampaignError> {
+        let path = checkpoint.as_ref();
+        let mut stored = self.load_checkpoint(path)?;
+        write_checkpoint(path, &stored)?;
+
+        for (index, chunk) in self.chunks().enumerate() {
+            if stored.chunks[index].state != ChunkState::Pending {
+                continue;
+            }
+            stored.chunks[index].state = ChunkState::Started;
+            write_checkpoint(path, &stored)?;
+
+            let response = match client.send_sms(self.request(chunk)).await {
+                Ok(response) => response,
+                Err(SmsRuError::RecipientsFailed(response)) => *response,
+                Err(error) => {
+                    if !error.may_have_been_accepted() {
+                        stored.chunks[index].state = ChunkState::Pending;
+                        write_checkpoint(path, &stored)?;
+                    }
+                    return Err(CampaignError::Send {
+                        chunk: index,
+                        source: error,
+                    });
+                }
+            };
+            stored.chunks[index].record(&response);
+            write_checkpoint(path, &stored)?;
+        }
+        Ok(self.report_from(&stored))
+    }
+
+    /// Build the report from an existing checkpoint without sending anything.
+    pub fn report(&self, checkpoint: impl AsRef<Path>) -> Result<CampaignReport, CampaignError> {
+        let stored = self.load_checkpoint(checkpoint.as_ref())?;
+        Ok(self.report_from(&stored))
+    }
+
+    fn chunks(&self) -> std::slice::Chunks<'_, CampaignRecipient> {
+        self.recipients.chunks(self.chunk_size)
+    }
+
+    fn request(&self, chunk: &[CampaignRecipient]) -> SendSms {
+        SendSms::per_recipient(
+            chunk
+                .iter()
+                .map(|recipient| (recipient.phone.clone(), recipient.text.clone()))
+                .collect(),
+            self.options.clone(),
+        )
+        .expect("chunks hold 1..=SEND_SMS_MAX_RECIPIENTS distinct recipients")
+    }
+
+    fn load_checkpoint(&self, path: &Path) -> Result<StoredCheckpoint, CampaignError> {
+        let plan = self
+            .chunks()
+            .map(|chunk| {
+                let recipients = chunk
+                    .iter()
+                    .map(|recipient| recipient.phone.raw().to_owned())
+                    .collect::<Vec<_>>();
+                (recipients, send_sms_fingerprint(&self.request(chunk)))
+            })
+            .collect::<Vec<_>>();
+
+        let stored = match fs::read(path) {
+            Ok(bytes) => serde_json::from_slice::<StoredCheckpoint>(&bytes).map_err(|err| {
+                CampaignError::Checkpoint {
+                    path: path.to_path_buf(),
+                    source: Box::new(err),
+                }
+            })?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Ok(StoredCheckpoint {
+                    version: CHECKPOINT_VERSION,
+                    chunks: plan
+                        .into_iter()
+                        .map(|(recipients, fingerprint)| StoredChunk {
+                            recipients,
+                            fingerprint,
+                            state: ChunkState::Pending,
+                            results: BTreeMap::new(),
+                        })
+                        .collect(),
+                });
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let matches = stored.version == CHECKPOINT_VERSION
+            && stored.chunks.len() == plan.len()
+            && stored
+                .chunks
+                .iter()
+                .zip(&plan)
+                .all(|(chunk, (recipients, fingerprint))| {
+                    &chunk.recipients == recipients && &chunk.fingerprint == fingerprint
+                });
+        if !matches {
+            return Err(CampaignError::CheckpointMismatch(path.to_path_buf()));
+        }
+        Ok(stored)
+    }
+
+    fn report_from(&self, stored: &StoredCheckpoint) -> CampaignReport {
+        let mut report = CampaignReport {
+            messages: BTreeMap::new(),
+            failed: Vec::new(),
+            uncertain: Vec::new(),
+            pending: Vec::new(),
+            rejected: self.rejected.clone(),
+        };
+        for (chunk, recipients) in stored.chunks.iter().zip(self.chunks()) {
+            for recipient in recipients {
+                let failure = |status_code| CampaignFailure {
+                    line: recipient.line,
+                    phone: recipient.phone.clone(),
+                    status_code,
+                };
+                match chunk.state {
+                    ChunkState::Pending => report.pending.push(recipient.clone()),
+                    ChunkState::Started => report.uncertain.push(failure(None)),
+                    ChunkState::Done => {
+                        let result = chunk.results.get(recipient.phone.raw());
+                        let sms_id = result
+                            .and_then(|result| result.sms_id.as_deref())
+                            .and_then(|id| SmsId::new(id).ok());
+                        match sms_id {
+                            Some(sms_id) => {
+                                report.messages.insert(
+                                    sms_id,
+                                    CampaignMessage {
+                                        line: recipient.line,
+                                        phone: recipient.phone.clone(),
+                                        status: None,
+                                    },
+                                );
+                            }
+                            None => report.failed.push(failure(
+                                result.map(|result| StatusCode::new(result.status_code)),
+                            )),
+                        }
+                    }
+                }
+            }
+        }
+        report
+    }
+}
+
+impl CampaignReport {
+    /// Fetch the latest `sms/status` for every accepted message.
+    pub async fn collect_statuses(&mut self, client: &SmsRuClient) -> Result<(), SmsRuError> {
+        let ids = self.messages.keys().cloned().collect::<Vec<_>>();
+        for ids in ids.chunks(CHECK_STATUS_MAX_SMS_IDS) {
+            let response = client.check_status(CheckStatus::new(ids.to_vec())?).await?;
+            for (id, status) in response.sms {
+                if let Some(message) = self.messages.get_mut(&id) {
+                    message.status = Some(status);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredCheckpoint {
+    version: u32,
+    chunks: Vec<StoredChunk>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredChunk {
+    recipients: Vec<String>,
+    /// [`send_sms_fingerprint`] of the chunk's request.
+    fingerprint: String,
+    state: ChunkState,
+    #[serde(default)]
+    results: BTreeMap<String, StoredResult>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ChunkState {
+    Pending,
+    Started,
+    Done,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredResult {
+    status_code: i32,
+    sms_id: Option<String>,
+}
+
+impl StoredChunk {
+    fn record(&mut self, response: &SendSmsResponse) {
+        self.state = ChunkState::Done;
+        self.results = response
+            .sms
+            .iter()
+            .map(|(phone, result)| {
+                (
+                    phone.raw().to_owned(),
+                    StoredResult {
+                        status_code: result.status_code.as_i32(),
+                        sms_id: result
+                            .is_success()
+                            .then(|| result.sms_id.as_ref().map(|id| id.as_str().to_owned()))
+                            .flatten(),
+                    },
+                )
+            })
+            .collect();
+    }
+}
+
+fn write_checkpoint(path: &Path, checkpoint: &StoredCheckpoint) -> Result<(), CampaignError> {
+    let bytes = serde_json::to_vec_pretty(checkpoint).map_err(|err| CampaignError::Checkpoint {
+        path: path.to_path_buf(),
+        source: Box::new(err),
+    })?;
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    fs::write(&tmp, bytes)?;
+    fs::File::open(&tmp)?.sync_all()?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Auth;
+    use crate::client::test_support::{FakeTransport, make_client};
+    use crate::domain::KnownStatusCode;
+    use std::process;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static NEXT_TEMP: AtomicU64 = AtomicU64::new(0);
+
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new() -> Self {
+            let sequence = NEXT_TEMP.fetch_add(1, Ordering::Relaxed);
+            Self(std::env::temp_dir().join(format!(
+                "smsru-campaign-test-{}-{sequence}.json",
+                process::id()
+            )))
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    const CSV: &str = "phone,name,extra\n\
+        +7 925 000-00-01,Ann,x\n\
+        89250000002,Bob,y\n\
+        not a phone,Eve,z\n\
+        79250000001,Ann again,w\n\
+        79250000003,Cy,v\n";
+
+    fn campaign() -> Campaign {
+        let template = MessageTemplate::parse("Hi {name}").unwrap();
+        Campaign::from_csv(CSV.as_bytes(), &template, SendOptions::default())
+            .unwrap()
+            .chunk_size(2)
+    }
+
+    #[test]
+    fn import_normalizes_deduplicates_and_renders() {
+        let campaign = campaign();
+        let recipients = campaign
+            .recipients()
+            .iter()
+            .map(|r| (r.line, r.phone.raw(), r.text.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            recipients,
+            vec![
+                (2, "79250000001", "Hi Ann"),
+                (3, "79250000002", "Hi Bob"),
+                (6, "79250000003", "Hi Cy"),
+            ]
+        );
+
+        let rejected = campaign.rejected();
+        assert_eq!(rejected.len(), 2);
+        assert!(matches!(rejected[0].reason, RejectReason::InvalidPhone(_)));
+        assert_eq!(rejected[1].line, 5);
+        assert_eq!(
+            rejected[1].reason,
+            RejectReason::Duplicate { first_line: 2 }
+        );
+    }
+
+    #[test]
+    fn import_requires_template_columns() {
+        let template = MessageTemplate::parse("Code {code}").unwrap();
+        let err =
+            Campaign::from_csv(CSV.as_bytes(), &template, SendOptions::default()).unwrap_err();
+        assert!(matches!(err, CampaignError::MissingColumn(name) if name == "code"));
+
+        let err = Campaign::from_csv_with(
+            "msisdn\n79250000001\n".as_bytes(),
+            &MessageTemplate::parse("hi").unwrap(),
+            SendOptions::default(),
+            &CsvImport::new().phone_column("tel"),
+        )
+        .unwrap_err();
+        assert!(matches!(err, CampaignError::MissingColumn(name) if name == "tel"));
+    }
+
+    #[tokio::test]
+    async fn preview_cost_sums_chunks() {
+        let transport = FakeTransport::sequence(vec![
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"total_cost":1.5,"total_sms":2,"sms":{"79250000001":{"status":"OK","status_code":100},"79250000002":{"status":"ERROR","status_code":207}}}"#,
+            ),
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"total_cost":"0.75","total_sms":1,"sms":{"79250000003":{"status":"OK","status_code":100}}}"#,
+            ),
+        ]);
+        let client = make_client(Auth::api_id("key").unwrap(), transport);
+        let preview = campaign().preview_cost(&client).await.unwrap();
+
+        assert_eq!(preview.total_cost, 2.25);
+        assert_eq!(preview.total_sms, 3);
+        assert_eq!(preview.rejected.len(), 1);
+        assert_eq!(preview.rejected[0].0.raw(), "79250000002");
+    }
+
+    #[tokio::test]
+    async fn run_checkpoints_and_resumes_without_resending() {
+        let checkpoint = TempFile::new();
+        let campaign = campaign();
+
+        let transport = FakeTransport::sequence(vec![
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"sms":{"79250000001":{"status":"OK","status_code":100,"sms_id":"000-1"},"79250000002":{"status":"ERROR","status_code":207}}}"#,
+            ),
+            (503, ""),
+        ]);
+        let client = make_client(Auth::api_id("key").unwrap(), transport);
+        let err = campaign.run(&client, &checkpoint.0).await.unwrap_err();
+        assert!(matches!(err, CampaignError::Send { chunk: 1, .. }));
+
+        // The second chunk may have reached SMS.RU, so it is never sent again.
+        let transport = FakeTransport::new(200, "{}");
+        let client = make_client(Auth::api_id("key").unwrap(), transport.clone());
+        let report = campaign.run(&client, &checkpoint.0).await.unwrap();
+        assert!(transport.requested_urls().is_empty());
+
+        let id = SmsId::new("000-1").unwrap();
+        assert_eq!(report.messages[&id].line, 2);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(
+            report.failed[0]
+                .status_code
+                .and_then(|code| code.known_kind()),
+            Some(KnownStatusCode::NoDeliveryRoute)
+        );
+        assert_eq!(report.uncertain.len(), 1);
+        assert_eq!(report.uncertain[0].phone.raw(), "79250000003");
+        assert!(report.pending.is_empty());
+    }
+
+    #[tokio::test]
+    async fn api_errors_leave_the_chunk_pending() {
+        let checkpoint = TempFile::new();
+        let campaign = campaign().chunk_size(100);
+
+        let transport = FakeTransport::new(200, r#"{"status":"ERROR","status_code":201}"#);
+        let client = make_client(Auth::api_id("key").unwrap(), transport);
+        campaign.run(&client, &checkpoint.0).await.unwrap_err();
+        assert_eq!(campaign.report(&checkpoint.0).unwrap().pending.len(), 3);
+
+        let err = campaign
+            .clone()
+            .chunk_size(1)
+            .report(&checkpoint.0)
+            .unwrap_err();
+        assert!(matches!(err, CampaignError::CheckpointMismatch(_)));
+    }
+
+    #[tokio::test]
+    async fn changed_texts_or_options_do_not_resume_a_checkpoint() {
+        let checkpoint = TempFile::new();
+        let campaign = campaign().chunk_size(100);
+        let transport = FakeTransport::new(200, r#"{"status":"ERROR","status_code":201}"#);
+        let client = make_client(Auth::api_id("key").unwrap(), transport);
+        campaign.run(&client, &checkpoint.0).await.unwrap_err();
+        assert!(campaign.report(&checkpoint.0).is_ok());
+
+        let template = MessageTemplate::parse("Hello {name}").unwrap();
+        let retemplated = Campaign::from_csv(CSV.as_bytes(), &template, SendOptions::default())
+            .unwrap()
+            .chunk_size(100);
+        assert!(matches!(
+            retemplated.report(&checkpoint.0),
+            Err(CampaignError::CheckpointMismatch(_))
+        ));
+
+        let template = MessageTemplate::parse("Hi {name}").unwrap();
+        let options = SendOptions {
+            translit: true,
+            ..SendOptions::default()
+        };
+        let reconfigured = Campaign::from_csv(CSV.as_bytes(), &template, options)
+            .unwrap()
+            .chunk_size(100);
+        assert!(matches!(
+            reconfigured.report(&checkpoint.0),
+            Err(CampaignError::CheckpointMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn import_keeps_digits_only_international_numbers() {
+        let template = MessageTemplate::parse("hi").unwrap();
+        let campaign = Campaign::from_csv(
+            "phone\n4915123456789\n380501234567\n".as_bytes(),
+            &template,
+            SendOptions::default(),
+        )
+        .unwrap();
+        let phones = campaign
+            .recipients()
+            .iter()
+            .map(|r| r.phone.raw())
+            .collect::<Vec<_>>();
+        assert_eq!(phones, vec!["4915123456789", "380501234567"]);
+        assert!(campaign.rejected().is_empty());
+    }
+
+    #[tokio::test]
+    async fn collect_statuses_fills_report_by_sms_id() {
+        let checkpoint = TempFile::new();
+        let campaign = campaign().chunk_size(100);
+        let transport = FakeTransport::sequence(vec![
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"sms":{"79250000001":{"status":"OK","status_code":100,"sms_id":"000-1"},"79250000002":{"status":"OK","status_code":100,"sms_id":"000-2"},"79250000003":{"status":"OK","status_code":100,"sms_id":"000-3"}}}"#,
+            ),
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"sms":{"000-1":{"status":"OK","status_code":103},"000-2":{"status":"OK","status_code":102}}}"#,
+            ),
+        ]);
+        let client = make_client(Auth::api_id("key").unwrap(), transport);
+        let mut report = campaign.run(&client, &checkpoint.0).await.unwrap();
+        report.collect_statuses(&client).await.unwrap();
+
+        let status = |id: &str| {
+            report.messages[&SmsId::new(id).unwrap()]
+                .status
+                .as_ref()
+                .map(|status| status.status_code.as_i32())
+        };
+        assert_eq!(status("000-1"), Some(103));
+        assert_eq!(status("000-2"), Some(102));
+        assert_eq!(status("000-3"), None);
+    }
+}
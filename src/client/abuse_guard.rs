@@ -0,0 +1,710 @@
+//! Optional velocity limits and risk checks against SMS pumping on OTP endpoints.
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::hash::Hash;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use phonenumber::country;
+
+use crate::domain::resolve_phone;
+use crate::domain::{RawPhoneNumber, SendSms};
+
+/// Digits of the E.164 number (without `+`) that make up a prefix by default.
+const DEFAULT_PREFIX_LEN: usize = 8;
+
+type AuditHook = Arc<dyn Fn(&AbuseDecision) + Send + Sync>;
+
+#[derive(Clone)]
+/// Local abuse protection for `send_sms` and `start_call_auth`.
+///
+/// When configured through [`super::SmsRuClientBuilder::abuse_guard`], every recipient is
+/// checked before anything is sent to SMS.RU:
+/// - velocity limits per number prefix and per end-user IP (`SendOptions.ip`),
+/// - a per-country risk score, blocking countries above [`AbuseGuard::max_country_risk`],
+/// - burst detection: too many *different* numbers from one prefix in a short window, the
+///   usual shape of a pumping attack walking a premium-rate range.
+///
+/// A request is refused with [`super::SmsRuError::Abuse`] when any recipient is blocked; only
+/// requests that pass are counted. `send_sms` runs this check after the client's other local
+/// checks, so requests they refuse are not counted either. Every decision, allowed or blocked,
+/// is passed to the [`AbuseGuard::on_decision`] hook for auditing.
+///
+/// Clones share the same counters, so one guard can protect several clients.
+pub struct AbuseGuard {
+    default_region: Option<country::Id>,
+    prefix_len: usize,
+    prefix_velocity: Option<Limit>,
+    ip_velocity: Option<Limit>,
+    prefix_burst: Option<Limit>,
+    country_risk: HashMap<country::Id, u8>,
+    default_risk: u8,
+    max_risk: Option<u8>,
+    audit: Option<AuditHook>,
+    state: Arc<Mutex<AbuseState>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Limit {
+    max: u32,
+    window: Duration,
+}
+
+#[derive(Debug, Default)]
+struct AbuseState {
+    prefix_sends: HashMap<String, VecDeque<Instant>>,
+    ip_sends: HashMap<IpAddr, VecDeque<Instant>>,
+    prefix_numbers: HashMap<String, VecDeque<(Instant, String)>>,
+}
+
+impl Default for AbuseGuard {
+    fn default() -> Self {
+        Self {
+            default_region: None,
+            prefix_len: DEFAULT_PREFIX_LEN,
+            prefix_velocity: None,
+            ip_velocity: None,
+            prefix_burst: None,
+            country_risk: HashMap::new(),
+            default_risk: 0,
+            max_risk: None,
+            audit: None,
+            state: Arc::new(Mutex::new(AbuseState::default())),
+        }
+    }
+}
+
+impl fmt::Debug for AbuseGuard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AbuseGuard")
+            .field("default_region", &self.default_region)
+            .field("prefix_len", &self.prefix_len)
+            .field("prefix_velocity", &self.prefix_velocity)
+            .field("ip_velocity", &self.ip_velocity)
+            .field("prefix_burst", &self.prefix_burst)
+            .field("country_risk", &self.country_risk)
+            .field("default_risk", &self.default_risk)
+            .field("max_risk", &self.max_risk)
+            .field("audit", &self.audit.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl AbuseGuard {
+    /// Create a guard with no limits; it only reports decisions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Region used for numbers written without a country code.
+    pub fn default_region(mut self, region: country::Id) -> Self {
+        self.default_region = Some(region);
+        self
+    }
+
+    /// Number of leading E.164 digits (without `+`) that identify a prefix (default 8).
+    ///
+    /// Values below 1 are treated as 1.
+    pub fn prefix_len(mut self, digits: usize) -> Self {
+        self.prefix_len = digits.max(1);
+        self
+    }
+
+    /// Allow at most `max` recipients per prefix within `window`.
+    pub fn prefix_velocity(mut self, max: u32, window: Duration) -> Self {
+        self.prefix_velocity = Some(Limit { max, window });
+        self
+    }
+
+    /// Allow at most `max` recipients per end-user IP within `window`.
+    ///
+    /// Only requests that carry an IP (`SendOptions.ip`) are counted.
+    pub fn ip_velocity(mut self, max: u32, window: Duration) -> Self {
+        self.ip_velocity = Some(Limit { max, window });
+        self
+    }
+
+    /// Allow at most `max` different numbers per prefix within `window`.
+    pub fn prefix_burst(mut self, max: u32, window: Duration) -> Self {
+        self.prefix_burst = Some(Limit { max, window });
+        self
+    }
+
+    /// Risk score (`0..=100`, higher is riskier) of `country`; larger values are clamped.
+    pub fn country_risk(mut self, country: country::Id, score: u8) -> Self {
+        self.country_risk.insert(country, score.min(100));
+        self
+    }
+
+    /// Risk score of countries without an explicit score (default 0).
+    pub fn default_country_risk(mut self, score: u8) -> Self {
+        self.default_risk = score.min(100);
+        self
+    }
+
+    /// Block recipients whose country risk score is above `score`.
+    pub fn max_country_risk(mut self, score: u8) -> Self {
+        self.max_risk = Some(score);
+        self
+    }
+
+    /// Call `hook` with every decision the guard makes.
+    ///
+    /// The hook runs on the sending task after the decision is made, so it should be quick (e.g.
+    /// log or push to a channel).
+    pub fn on_decision(mut self, hook: impl Fn(&AbuseDecision) + Send + Sync + 'static) -> Self {
+        self.audit = Some(Arc::new(hook));
+        self
+    }
+
+    pub(crate) fn check_send(&self, request: &SendSms) -> Result<(), AbuseGuardViolation> {
+        let (phones, ip) = match request {
+            SendSms::ToMany(to_many) => (
+                to_many.recipients().iter().collect::<Vec<_>>(),
+                to_many.options().ip,
+            ),
+            SendSms::PerRecipient(per_recipient) => (
+                per_recipient.messages().keys().collect(),
+                per_recipient.options().ip,
+            ),
+        };
+        self.check(AbuseOperation::SendSms, &phones, ip)
+    }
+
+    pub(crate) fn check_call_auth(
+        &self,
+        phone: &RawPhoneNumber,
+    ) -> Result<(), AbuseGuardViolation> {
+        self.check(AbuseOperation::StartCallAuth, &[phone], None)
+    }
+
+    fn check(
+        &self,
+        operation: AbuseOperation,
+        phones: &[&RawPhoneNumber],
+        ip: Option<IpAddr>,
+    ) -> Result<(), AbuseGuardViolation> {
+        self.check_at(Instant::now(), SystemTime::now(), operation, phones, ip)
+    }
+
+    fn check_at(
+        &self,
+        now: Instant,
+        at: SystemTime,
+        operation: AbuseOperation,
+        phones: &[&RawPhoneNumber],
+        ip: Option<IpAddr>,
+    ) -> Result<(), AbuseGuardViolation> {
+        let (decisions, blocked) = {
+            let mut state = self.state.lock().unwrap();
+            state.prune(now, self);
+
+            // Count recipients tentatively so later recipients of the same request see earlier
+            // ones; roll back if anything is blocked.
+            let mut recorded = Vec::new();
+            let decisions = phones
+                .iter()
+                .map(|&phone| {
+                    let decision = AbuseDecision {
+                        at,
+                        operation,
+                        phone: phone.clone(),
+                        ip,
+                        country: None,
+                        prefix: None,
+                        risk: self.default_risk,
+                        blocked: None,
+                    };
+                    self.decide(&mut state, now, decision, &mut recorded)
+                })
+                .collect::<Vec<_>>();
+
+            let blocked = decisions
+                .iter()
+                .filter_map(|d| d.blocked.clone().map(|reason| (d.phone.clone(), reason)))
+                .collect::<BTreeMap<_, _>>();
+            if !blocked.is_empty() {
+                state.rollback(recorded);
+            }
+            (decisions, blocked)
+        };
+
+        // Outside the lock, so a slow or panicking hook cannot stall or poison the counters.
+        if let Some(audit) = self.audit.as_ref() {
+            for decision in &decisions {
+                audit(decision);
+            }
+        }
+
+        if blocked.is_empty() {
+            Ok(())
+        } else {
+            Err(AbuseGuardViolation { blocked })
+        }
+    }
+
+    fn decide(
+        &self,
+        state: &mut AbuseState,
+        now: Instant,
+        mut decision: AbuseDecision,
+        recorded: &mut Vec<Recorded>,
+    ) -> AbuseDecision {
+        let Some(parsed) = resolve_phone(self.default_region, decision.phone.raw()) else {
+            decision.blocked = Some(AbuseReason::InvalidNumber);
+            return decision;
+        };
+        let digits = parsed.e164().trim_start_matches('+').to_owned();
+        let prefix = digits.chars().take(self.prefix_len).collect::<String>();
+        decision.country = parsed.parsed().country().id();
+        decision.prefix = Some(prefix.clone());
+        if let Some(country) = decision.country {
+            if let Some(&score) = self.country_risk.get(&country) {
+                decision.risk = score;
+            }
+        }
+
+        decision.blocked = self.blocked(state, now, &decision, &prefix, &digits);
+        if decision.blocked.is_none() {
+            state.record(now, &prefix, &digits, decision.ip, self, recorded);
+        }
+        decision
+    }
+
+    fn blocked(
+        &self,
+        state: &AbuseState,
+        now: Instant,
+        decision: &AbuseDecision,
+        prefix: &str,
+        digits: &str,
+    ) -> Option<AbuseReason> {
+        if let Some(max) = self.max_risk {
+            if decision.risk > max {
+                return Some(AbuseReason::CountryRisk {
+                    country: decision.country,
+                    score: decision.risk,
+                    max,
+                });
+            }
+        }
+
+        if let Some(limit) = self.prefix_velocity {
+            if let Some(sends) = state.prefix_sends.get(prefix) {
+                if sends.len() >= limit.max as usize {
+                    return Some(AbuseReason::PrefixVelocity {
+                        prefix: prefix.to_owned(),
+                        max: limit.max,
+                        window: limit.window,
+                        retry_after: retry_after(now, sends.front().copied(), limit.window),
+                    });
+                }
+            }
+        }
+
+        if let (Some(limit), Some(ip)) = (self.ip_velocity, decision.ip) {
+            if let Some(sends) = state.ip_sends.get(&ip) {
+                if sends.len() >= limit.max as usize {
+                    return Some(AbuseReason::IpVelocity {
+                        ip,
+                        max: limit.max,
+                        window: limit.window,
+                        retry_after: retry_after(now, sends.front().copied(), limit.window),
+                    });
+                }
+            }
+        }
+
+        if let Some(limit) = self.prefix_burst {
+            if let Some(numbers) = state.prefix_numbers.get(prefix) {
+                let distinct = numbers
+                    .iter()
+                    .map(|(_, number)| number.as_str())
+                    .collect::<HashSet<_>>();
+                if !distinct.contains(digits) && distinct.len() >= limit.max as usize {
+                    return Some(AbuseReason::PrefixBurst {
+                        prefix: prefix.to_owned(),
+                        numbers: distinct.len() as u32,
+                        max: limit.max,
+                        window: limit.window,
+                        retry_after: retry_after(
+                            now,
+                            numbers.front().map(|(at, _)| *at),
+                            limit.window,
+                        ),
+                    });
+                }
+            }
+        }
+
+        None
+    }
+}
+
+fn retry_after(now: Instant, oldest: Option<Instant>, window: Duration) -> Duration {
+    oldest.map_or(Duration::ZERO, |oldest| {
+        (oldest + window).saturating_duration_since(now)
+    })
+}
+
+enum Recorded {
+    Prefix(String),
+    Ip(IpAddr),
+    Burst(String),
+}
+
+impl AbuseState {
+    fn prune(&mut self, now: Instant, guard: &AbuseGuard) {
+        if let Some(limit) = guard.prefix_velocity {
+            prune_map(&mut self.prefix_sends, now, limit.window, |at| *at);
+        }
+        if let Some(limit) = guard.ip_velocity {
+            prune_map(&mut self.ip_sends, now, limit.window, |at| *at);
+        }
+        if let Some(limit) = guard.prefix_burst {
+            prune_map(&mut self.prefix_numbers, now, limit.window, |(at, _)| *at);
+        }
+    }
+
+    fn record(
+        &mut self,
+        now: Instant,
+        prefix: &str,
+        digits: &str,
+        ip: Option<IpAddr>,
+        guard: &AbuseGuard,
+        recorded: &mut Vec<Recorded>,
+    ) {
+        if guard.prefix_velocity.is_some() {
+            self.prefix_sends
+                .entry(prefix.to_owned())
+                .or_default()
+                .push_back(now);
+            recorded.push(Recorded::Prefix(prefix.to_owned()));
+        }
+        if let (Some(_), Some(ip)) = (guard.ip_velocity, ip) {
+            self.ip_sends.entry(ip).or_default().push_back(now);
+            recorded.push(Recorded::Ip(ip));
+        }
+        if guard.prefix_burst.is_some() {
+            self.prefix_numbers
+                .entry(prefix.to_owned())
+                .or_default()
+                .push_back((now, digits.to_owned()));
+            recorded.push(Recorded::Burst(prefix.to_owned()));
+        }
+    }
+
+    fn rollback(&mut self, recorded: Vec<Recorded>) {
+        for entry in recorded.into_iter().rev() {
+            match entry {
+                Recorded::Prefix(prefix) => pop_back(&mut self.prefix_sends, &prefix),
+                Recorded::Ip(ip) => pop_back(&mut self.ip_sends, &ip),
+                Recorded::Burst(prefix) => pop_back(&mut self.prefix_numbers, &prefix),
+            }
+        }
+    }
+}
+
+fn prune_map<K, T>(
+    map: &mut HashMap<K, VecDeque<T>>,
+    now: Instant,
+    window: Duration,
+    at: impl Fn(&T) -> Instant,
+) {
+    map.retain(|_, entries| {
+        while entries
+            .front()
+            .is_some_and(|entry| now.saturating_duration_since(at(entry)) >= window)
+        {
+            entries.pop_front();
+        }
+        !entries.is_empty()
+    });
+}
+
+fn pop_back<K: Eq + Hash, T>(map: &mut HashMap<K, VecDeque<T>>, key: &K) {
+    if let Some(entries) = map.get_mut(key) {
+        entries.pop_back();
+        if entries.is_empty() {
+            map.remove(key);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Client method an [`AbuseDecision`] was made for.
+pub enum AbuseOperation {
+    /// [`super::SmsRuClient::send_sms`] and the methods built on it.
+    SendSms,
+    /// [`super::SmsRuClient::start_call_auth`].
+    StartCallAuth,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// One recipient checked by [`AbuseGuard`], for auditing.
+///
+/// A request is refused when any of its recipients is blocked, so an allowed decision does not
+/// by itself mean the message was sent.
+pub struct AbuseDecision {
+    /// When the decision was made.
+    pub at: SystemTime,
+    pub operation: AbuseOperation,
+    /// The number as given in the request.
+    pub phone: RawPhoneNumber,
+    /// End-user IP from the request options, if any.
+    pub ip: Option<IpAddr>,
+    /// Country of the number, if it could be resolved.
+    pub country: Option<country::Id>,
+    /// Leading E.164 digits used for prefix limits.
+    pub prefix: Option<String>,
+    /// Country risk score applied to the number.
+    pub risk: u8,
+    /// Why the recipient was blocked, or `None` if it was allowed.
+    pub blocked: Option<AbuseReason>,
+}
+
+impl AbuseDecision {
+    /// Whether the recipient passed the guard.
+    pub fn is_allowed(&self) -> bool {
+        self.blocked.is_none()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Why [`AbuseGuard`] blocked a recipient.
+pub enum AbuseReason {
+    /// The number could not be parsed or is not a valid number.
+    InvalidNumber,
+    /// The country's risk score is above the configured maximum.
+    CountryRisk {
+        country: Option<country::Id>,
+        score: u8,
+        max: u8,
+    },
+    /// Too many recipients in this prefix within the window.
+    PrefixVelocity {
+        prefix: String,
+        max: u32,
+        window: Duration,
+        retry_after: Duration,
+    },
+    /// Too many recipients for this end-user IP within the window.
+    IpVelocity {
+        ip: IpAddr,
+        max: u32,
+        window: Duration,
+        retry_after: Duration,
+    },
+    /// Too many different numbers in this prefix within the window.
+    PrefixBurst {
+        prefix: String,
+        numbers: u32,
+        max: u32,
+        window: Duration,
+        retry_after: Duration,
+    },
+}
+
+impl AbuseReason {
+    /// Time until the limit that blocked the recipient frees up; `None` for permanent reasons.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::InvalidNumber | Self::CountryRisk { .. } => None,
+            Self::PrefixVelocity { retry_after, .. }
+            | Self::IpVelocity { retry_after, .. }
+            | Self::PrefixBurst { retry_after, .. } => Some(*retry_after),
+        }
+    }
+}
+
+impl fmt::Display for AbuseReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidNumber => f.write_str("invalid number"),
+            Self::CountryRisk { score, max, .. } => {
+                write!(f, "country risk {score} is above {max}")
+            }
+            Self::PrefixVelocity {
+                prefix,
+                max,
+                window,
+                ..
+            } => write!(f, "more than {max} sends to prefix {prefix} in {window:?}"),
+            Self::IpVelocity {
+                ip, max, window, ..
+            } => write!(f, "more than {max} sends for IP {ip} in {window:?}"),
+            Self::PrefixBurst {
+                prefix,
+                max,
+                window,
+                ..
+            } => write!(
+                f,
+                "more than {max} different numbers in prefix {prefix} in {window:?}"
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("{} recipient(s) blocked by abuse guard", .blocked.len())]
+/// Recipients blocked by [`AbuseGuard`], keyed by the number as given in the request.
+pub struct AbuseGuardViolation {
+    pub blocked: BTreeMap<RawPhoneNumber, AbuseReason>,
+}
+
+impl AbuseGuardViolation {
+    /// Whether every block is temporary (velocity or burst limits).
+    pub fn is_temporary(&self) -> bool {
+        self.blocked
+            .values()
+            .all(|reason| reason.retry_after().is_some())
+    }
+
+    /// Longest wait until every blocking limit frees up, if all blocks are temporary.
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.blocked
+            .values()
+            .map(AbuseReason::retry_after)
+            .try_fold(Duration::ZERO, |longest, wait| Some(longest.max(wait?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phone(raw: &str) -> RawPhoneNumber {
+        RawPhoneNumber::new(raw).unwrap()
+    }
+
+    fn check(
+        guard: &AbuseGuard,
+        now: Instant,
+        phones: &[&str],
+        ip: Option<IpAddr>,
+    ) -> Result<(), AbuseGuardViolation> {
+        let phones = phones.iter().map(|raw| phone(raw)).collect::<Vec<_>>();
+        let phones = phones.iter().collect::<Vec<_>>();
+        guard.check_at(
+            now,
+            SystemTime::UNIX_EPOCH,
+            AbuseOperation::SendSms,
+            &phones,
+            ip,
+        )
+    }
+
+    #[test]
+    fn prefix_velocity_blocks_until_window_passes() {
+        let guard = AbuseGuard::new().prefix_velocity(2, Duration::from_secs(60));
+        let start = Instant::now();
+
+        check(&guard, start, &["+79251234501", "+79251234502"], None).unwrap();
+        let err = check(&guard, start, &["+79251234503"], None).unwrap_err();
+        assert!(matches!(
+            err.blocked[&phone("+79251234503")],
+            AbuseReason::PrefixVelocity { ref prefix, .. } if prefix == "79251234"
+        ));
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(60)));
+
+        // Another prefix is unaffected, and the window frees up.
+        check(&guard, start, &["+79261234501"], None).unwrap();
+        check(
+            &guard,
+            start + Duration::from_secs(60),
+            &["+79251234503"],
+            None,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn ip_velocity_counts_only_requests_with_an_ip() {
+        let guard = AbuseGuard::new().ip_velocity(1, Duration::from_secs(60));
+        let now = Instant::now();
+        let ip = IpAddr::from([203, 0, 113, 7]);
+
+        check(&guard, now, &["+79251234501"], Some(ip)).unwrap();
+        check(&guard, now, &["+79251234502"], None).unwrap();
+        let err = check(&guard, now, &["+79251234503"], Some(ip)).unwrap_err();
+        assert!(matches!(
+            err.blocked[&phone("+79251234503")],
+            AbuseReason::IpVelocity { .. }
+        ));
+    }
+
+    #[test]
+    fn prefix_burst_counts_different_numbers() {
+        let guard = AbuseGuard::new().prefix_burst(2, Duration::from_secs(60));
+        let now = Instant::now();
+
+        check(&guard, now, &["+79251234501", "+79251234502"], None).unwrap();
+        // Repeats of known numbers are not a burst.
+        check(&guard, now, &["+79251234501"], None).unwrap();
+        let err = check(&guard, now, &["+79251234503"], None).unwrap_err();
+        assert!(matches!(
+            err.blocked[&phone("+79251234503")],
+            AbuseReason::PrefixBurst { numbers: 2, .. }
+        ));
+    }
+
+    #[test]
+    fn blocked_request_is_not_counted_and_is_audited() {
+        let decisions = Arc::new(Mutex::new(Vec::new()));
+        let sink = decisions.clone();
+        let guard = AbuseGuard::new()
+            .prefix_velocity(1, Duration::from_secs(60))
+            .country_risk(country::DE, 90)
+            .max_country_risk(50)
+            .on_decision(move |decision| sink.lock().unwrap().push(decision.clone()));
+        let now = Instant::now();
+
+        let err = check(&guard, now, &["+79251234501", "+4915123456789"], None).unwrap_err();
+        assert!(!err.is_temporary());
+        assert_eq!(
+            err.blocked[&phone("+4915123456789")],
+            AbuseReason::CountryRisk {
+                country: Some(country::DE),
+                score: 90,
+                max: 50
+            }
+        );
+
+        // The Russian recipient was rolled back, so it still fits the limit.
+        check(&guard, now, &["+79251234501"], None).unwrap();
+
+        let decisions = decisions.lock().unwrap();
+        assert_eq!(decisions.len(), 3);
+        assert!(decisions[0].is_allowed());
+        assert_eq!(decisions[0].country, Some(country::RU));
+        assert_eq!(decisions[1].risk, 90);
+        assert!(!decisions[1].is_allowed());
+        assert!(decisions[2].is_allowed());
+    }
+
+    #[test]
+    fn panicking_hook_does_not_break_the_guard() {
+        let guard = AbuseGuard::new()
+            .prefix_velocity(1, Duration::from_secs(60))
+            .on_decision(|decision| {
+                if decision.phone.raw() == "+79251234501" {
+                    panic!("audit sink failed");
+                }
+            });
+        let now = Instant::now();
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            check(&guard, now, &["+79251234501"], None)
+        }));
+        assert!(result.is_err());
+        let err = check(&guard, now, &["+79251234502"], None).unwrap_err();
+        assert!(matches!(
+            err.blocked[&phone("+79251234502")],
+            AbuseReason::PrefixVelocity { .. }
+        ));
+    }
+}
@@ -0,0 +1,582 @@
+//! Concurrent, rate-limited bulk sending of per-recipient messages.
+
+use std::collections::BTreeMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_core::Stream;
+use tokio::sync::{Semaphore, mpsc, watch};
+use tokio::task::{JoinHandle, JoinSet};
+use tokio::time::Instant;
+
+use super::{SmsRuClient, SmsRuError};
+use crate::domain::{
+    MessageText, RawPhoneNumber, SEND_SMS_MAX_RECIPIENTS, SendOptions, SendSms, SendSmsResponse,
+};
+
+const DEFAULT_CONCURRENCY: usize = 4;
+
+#[derive(Clone)]
+/// Sends a stream of `(phone, text)` pairs as [`SendSms::per_recipient`] chunks.
+///
+/// Chunks are sent on the Tokio runtime with bounded concurrency and an optional target rate.
+/// A phone number that repeats inside one chunk closes the chunk early, so every pair is sent.
+pub struct BulkSender {
+    client: SmsRuClient,
+    options: SendOptions,
+    chunk_size: usize,
+    concurrency: usize,
+    max_messages_per_second: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// Running totals reported by [`BulkSend`].
+pub struct BulkProgress {
+    /// Recipients accepted by SMS.RU.
+    pub sent: usize,
+    /// Recipients rejected by SMS.RU or part of a chunk that SMS.RU refused.
+    pub failed: usize,
+    /// Recipients of chunks whose request failed in a way SMS.RU may still have accepted
+    /// (transport errors, unexpected HTTP status, unreadable responses); check their delivery
+    /// before sending them again.
+    pub uncertain: usize,
+    /// Money spent so far, measured as the drop of the account balance since the start.
+    ///
+    /// `None` when the balance could not be read. Other sends on the same account during the
+    /// run are included.
+    pub cost: Option<f64>,
+    /// Chunks answered (successfully or not).
+    pub chunks_done: usize,
+    /// Chunks sent and not answered yet.
+    pub chunks_in_flight: usize,
+}
+
+#[derive(Debug)]
+/// Outcome of one chunk.
+pub struct BulkChunk {
+    /// Position of the chunk in send order, starting at 0.
+    pub index: usize,
+    /// The request that was sent.
+    pub request: SendSms,
+    /// SMS.RU response, with per-recipient failures left in the response.
+    pub result: Result<SendSmsResponse, SmsRuError>,
+}
+
+#[derive(Debug)]
+/// Final result of a [`BulkSend`].
+pub struct BulkReport {
+    /// Totals after every chunk has been answered.
+    pub progress: BulkProgress,
+    /// Every chunk that was sent, ordered by [`BulkChunk::index`].
+    pub chunks: Vec<BulkChunk>,
+    /// Pairs taken from the input but not sent because the run was cancelled.
+    pub unsent: Vec<(RawPhoneNumber, MessageText)>,
+    /// Number of pairs taken from the input, including those in [`BulkReport::unsent`].
+    ///
+    /// To resume a cancelled run, send `unsent` and then the input after this many pairs.
+    pub consumed: usize,
+    /// Whether [`BulkSend::cancel`] stopped the run before the input was exhausted.
+    pub cancelled: bool,
+}
+
+/// A running bulk send; a [`Stream`] of [`BulkProgress`] updates.
+///
+/// The stream ends once every chunk has been answered; [`BulkSend::join`] then returns the
+/// [`BulkReport`]. Dropping the handle does not stop the run.
+pub struct BulkSend {
+    progress: mpsc::UnboundedReceiver<BulkProgress>,
+    cancel: BulkCancel,
+    task: JoinHandle<BulkReport>,
+}
+
+#[derive(Debug, Clone)]
+/// Cancels a [`BulkSend`] from another task.
+pub struct BulkCancel(Arc<watch::Sender<bool>>);
+
+impl BulkSender {
+    /// Create a sender with chunks of `SEND_SMS_MAX_RECIPIENTS`, 4 chunks in flight, and no
+    /// rate limit.
+    pub fn new(client: SmsRuClient) -> Self {
+        Self {
+            client,
+            options: SendOptions::default(),
+            chunk_size: SEND_SMS_MAX_RECIPIENTS,
+            concurrency: DEFAULT_CONCURRENCY,
+            max_messages_per_second: None,
+        }
+    }
+
+    /// Options applied to every chunk (sender, scheduling, test mode, ...).
+    pub fn options(mut self, options: SendOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Recipients per `sms/send` request, clamped to `1..=SEND_SMS_MAX_RECIPIENTS`.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.clamp(1, SEND_SMS_MAX_RECIPIENTS);
+        self
+    }
+
+    /// Maximum number of chunks in flight at once (at least 1).
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Start chunks no faster than `rate` messages per second on average; `0` disables pacing.
+    pub fn max_messages_per_second(mut self, rate: u32) -> Self {
+        self.max_messages_per_second = (rate > 0).then_some(rate);
+        self
+    }
+
+    /// Start sending `messages` in the background.
+    ///
+    /// Must be called within a Tokio runtime.
+    pub fn start<S>(self, messages: S) -> BulkSend
+    where
+        S: Stream<Item = (RawPhoneNumber, MessageText)> + Send + 'static,
+    {
+        let (progress_tx, progress) = mpsc::unbounded_channel();
+        let (cancel_tx, cancel_rx) = watch::channel(false);
+        let task = tokio::spawn(run(self, Box::pin(messages), cancel_rx, progress_tx));
+        BulkSend {
+            progress,
+            cancel: BulkCancel(Arc::new(cancel_tx)),
+            task,
+        }
+    }
+
+    /// Like [`BulkSender::start`], for messages that are already in memory.
+    pub fn start_iter<I>(self, messages: I) -> BulkSend
+    where
+        I: IntoIterator<Item = (RawPhoneNumber, MessageText)>,
+        I::IntoIter: Send + 'static,
+    {
+        self.start(IterStream(messages.into_iter()))
+    }
+}
+
+impl BulkSend {
+    /// Stop taking pairs from the input; chunks already in flight are still awaited.
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// A handle that cancels this run when [`BulkCancel::cancel`] is called.
+    pub fn cancel_handle(&self) -> BulkCancel {
+        self.cancel.clone()
+    }
+
+    /// Wait for the run to finish and return its report.
+    pub async fn join(self) -> BulkReport {
+        match self.task.await {
+            Ok(report) => report,
+            Err(err) => std::panic::resume_unwind(err.into_panic()),
+        }
+    }
+}
+
+impl Stream for BulkSend {
+    type Item = BulkProgress;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<BulkProgress>> {
+        self.progress.poll_recv(cx)
+    }
+}
+
+impl BulkCancel {
+    /// Stop taking pairs from the input; chunks already in flight are still awaited.
+    pub fn cancel(&self) {
+        self.0.send_replace(true);
+    }
+}
+
+struct IterStream<I>(I);
+
+// The iterator is never pinned in place, so any iterator works.
+impl<I> Unpin for IterStream<I> {}
+
+impl<I: Iterator> Stream for IterStream<I> {
+    type Item = I::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<I::Item>> {
+        Poll::Ready(self.0.next())
+    }
+}
+
+#[derive(Default)]
+struct Totals {
+    progress: BulkProgress,
+    chunks: Vec<BulkChunk>,
+    baseline_balance: Option<f64>,
+    lowest_balance: Option<f64>,
+}
+
+impl Totals {
+    fn record(&mut self, chunk: BulkChunk) {
+        self.progress.chunks_in_flight -= 1;
+        self.progress.chunks_done += 1;
+        match &chunk.result {
+            Ok(response) => {
+                self.progress.sent += response.successes().count();
+                self.progress.failed += response.failures().count();
+                if let Some(balance) = parse_balance(response.balance.as_deref()) {
+                    let lowest = self.lowest_balance.map_or(balance, |low| low.min(balance));
+                    self.lowest_balance = Some(lowest);
+                    if let Some(baseline) = self.baseline_balance {
+                        self.progress.cost = Some((baseline - lowest).max(0.0));
+                    }
+                }
+            }
+            Err(err) if err.may_have_been_accepted() => {
+                self.progress.uncertain += chunk.request.recipient_count();
+            }
+            Err(_) => self.progress.failed += chunk.request.recipient_count(),
+        }
+        self.chunks.push(chunk);
+    }
+}
+
+struct Driver {
+    sender: BulkSender,
+    totals: Arc<Mutex<Totals>>,
+    progress_tx: mpsc::UnboundedSender<BulkProgress>,
+    cancel: watch::Receiver<bool>,
+    permits: Arc<Semaphore>,
+    tasks: JoinSet<()>,
+    next_index: usize,
+    next_start: Instant,
+}
+
+async fn run(
+    sender: BulkSender,
+    mut messages: Pin<Box<dyn Stream<Item = (RawPhoneNumber, MessageText)> + Send>>,
+    cancel: watch::Receiver<bool>,
+    progress_tx: mpsc::UnboundedSender<BulkProgress>,
+) -> BulkReport {
+    let baseline_balance = match sender.client.get_balance().await {
+        Ok(response) => parse_balance(response.balance.as_deref()),
+        Err(_) => None,
+    };
+    let totals = Totals {
+        progress: BulkProgress {
+            cost: baseline_balance.map(|_| 0.0),
+            ..BulkProgress::default()
+        },
+        baseline_balance,
+        ..Totals::default()
+    };
+    let mut driver = Driver {
+        permits: Arc::new(Semaphore::new(sender.concurrency)),
+        sender,
+        totals: Arc::new(Mutex::new(totals)),
+        progress_tx,
+        cancel,
+        tasks: JoinSet::new(),
+        next_index: 0,
+        next_start: Instant::now(),
+    };
+
+    let mut buffer = BTreeMap::new();
+    let mut unsent = Vec::new();
+    let mut consumed = 0;
+    let mut cancelled = false;
+    loop {
+        let next = tokio::select! {
+            biased;
+            _ = cancelled_signal(&mut driver.cancel) => None,
+            next = std::future::poll_fn(|cx| messages.as_mut().poll_next(cx)) => Some(next),
+        };
+        let Some(next) = next else {
+            cancelled = true;
+            break;
+        };
+        let Some((phone, text)) = next else {
+            break;
+        };
+        consumed += 1;
+
+        if buffer.contains_key(&phone) {
+            if let Err(chunk) = driver.dispatch(std::mem::take(&mut buffer)).await {
+                unsent.extend(chunk);
+                unsent.push((phone, text));
+                cancelled = true;
+                break;
+            }
+        }
+        buffer.insert(phone, text);
+        if buffer.len() == driver.sender.chunk_size {
+            if let Err(chunk) = driver.dispatch(std::mem::take(&mut buffer)).await {
+                unsent.extend(chunk);
+                cancelled = true;
+                break;
+            }
+        }
+    }
+    if cancelled {
+        unsent.extend(buffer);
+    } else if !buffer.is_empty() {
+        if let Err(chunk) = driver.dispatch(buffer).await {
+            unsent.extend(chunk);
+            cancelled = true;
+        }
+    }
+
+    while let Some(joined) = driver.tasks.join_next().await {
+        if let Err(err) = joined {
+            std::panic::resume_unwind(err.into_panic());
+        }
+    }
+    let totals = std::mem::take(&mut *driver.totals.lock().unwrap());
+    let mut chunks = totals.chunks;
+    chunks.sort_by_key(|chunk| chunk.index);
+    BulkReport {
+        progress: totals.progress,
+        chunks,
+        unsent,
+        consumed,
+        cancelled,
+    }
+}
+
+impl Driver {
+    /// Send `chunk` once a concurrency slot and the rate allow it; gives it back on cancel.
+    async fn dispatch(
+        &mut self,
+        chunk: BTreeMap<RawPhoneNumber, MessageText>,
+    ) -> Result<(), BTreeMap<RawPhoneNumber, MessageText>> {
+        let permit = tokio::select! {
+            biased;
+            _ = cancelled_signal(&mut self.cancel) => return Err(chunk),
+            permit = self.permits.clone().acquire_owned() => {
+                permit.expect("the semaphore is never closed")
+            }
+        };
+        if let Some(rate) = self.sender.max_messages_per_second {
+            tokio::select! {
+                biased;
+                _ = cancelled_signal(&mut self.cancel) => return Err(chunk),
+                _ = tokio::time::sleep_until(self.next_start) => {}
+            }
+            let interval = Duration::from_secs_f64(chunk.len() as f64 / f64::from(rate));
+            self.next_start = self.next_start.max(Instant::now()) + interval;
+        }
+
+        let request = SendSms::per_recipient(chunk, self.sender.options.clone())
+            .expect("chunks hold 1..=SEND_SMS_MAX_RECIPIENTS recipients");
+        let index = self.next_index;
+        self.next_index += 1;
+        {
+            let mut totals = self.totals.lock().unwrap();
+            totals.progress.chunks_in_flight += 1;
+            let _ = self.progress_tx.send(totals.progress);
+        }
+
+        let client = self.sender.client.clone();
+        let totals = Arc::clone(&self.totals);
+        let progress_tx = self.progress_tx.clone();
+        self.tasks.spawn(async move {
+            let _permit = permit;
+            let result = client.send_sms_lenient(request.clone()).await;
+            let mut totals = totals.lock().unwrap();
+            totals.record(BulkChunk {
+                index,
+                request,
+                result,
+            });
+            let _ = progress_tx.send(totals.progress);
+        });
+        Ok(())
+    }
+}
+
+/// Resolves once cancellation is requested; never resolves if the handle was dropped.
+async fn cancelled_signal(cancel: &mut watch::Receiver<bool>) {
+    if cancel.wait_for(|cancelled| *cancelled).await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
+
+fn parse_balance(balance: Option<&str>) -> Option<f64> {
+    balance?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Auth;
+    use crate::client::test_support::{FakeTransport, make_client};
+
+    fn pair(phone: &str, text: &str) -> (RawPhoneNumber, MessageText) {
+        (
+            RawPhoneNumber::new(phone).unwrap(),
+            MessageText::new(text).unwrap(),
+        )
+    }
+
+    async fn drain(send: &mut BulkSend) -> Vec<BulkProgress> {
+        let mut updates = Vec::new();
+        while let Some(update) = std::future::poll_fn(|cx| Pin::new(&mut *send).poll_next(cx)).await
+        {
+            updates.push(update);
+        }
+        updates
+    }
+
+    #[tokio::test]
+    async fn packs_pairs_into_chunks_and_reports_progress() {
+        let transport = FakeTransport::sequence(vec![
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"balance":"100.00"}"#,
+            ),
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"balance":"98.50","sms":{"79250000001":{"status":"OK","status_code":100,"sms_id":"1"},"79250000002":{"status":"ERROR","status_code":207}}}"#,
+            ),
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"balance":"97.00","sms":{"79250000003":{"status":"OK","status_code":100,"sms_id":"3"}}}"#,
+            ),
+        ]);
+        let client = make_client(Auth::api_id("key").unwrap(), transport.clone());
+        let mut send = BulkSender::new(client)
+            .chunk_size(2)
+            .concurrency(1)
+            .start_iter(vec![
+                pair("79250000001", "hi 1"),
+                pair("79250000002", "hi 2"),
+                pair("79250000003", "hi 3"),
+            ]);
+
+        let updates = drain(&mut send).await;
+        let report = send.join().await;
+
+        assert_eq!(updates.last().copied(), Some(report.progress));
+        assert_eq!(report.progress.sent, 2);
+        assert_eq!(report.progress.failed, 1);
+        assert_eq!(report.progress.uncertain, 0);
+        assert_eq!(report.progress.cost, Some(3.0));
+        assert_eq!(report.progress.chunks_done, 2);
+        assert_eq!(report.progress.chunks_in_flight, 0);
+        assert_eq!(report.chunks.len(), 2);
+        assert_eq!(report.chunks[0].request.recipient_count(), 2);
+        assert_eq!(report.consumed, 3);
+        assert!(!report.cancelled && report.unsent.is_empty());
+        assert_eq!(
+            transport.requested_urls(),
+            vec![
+                "https://example.invalid/my/balance",
+                "https://example.invalid/sms/send",
+                "https://example.invalid/sms/send",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn repeated_phone_starts_a_new_chunk_and_errors_are_failed_or_uncertain() {
+        let transport = FakeTransport::sequence(vec![
+            (503, ""),
+            (200, r#"{"status":"ERROR","status_code":220}"#),
+            (503, ""),
+        ]);
+        let client = make_client(Auth::api_id("key").unwrap(), transport);
+        let report = BulkSender::new(client)
+            .concurrency(1)
+            .start_iter(vec![
+                pair("79250000001", "first"),
+                pair("79250000001", "second"),
+            ])
+            .join()
+            .await;
+
+        assert_eq!(report.chunks.len(), 2);
+        assert!(matches!(
+            report.chunks[0].result,
+            Err(SmsRuError::Api { .. })
+        ));
+        assert!(matches!(
+            report.chunks[1].result,
+            Err(SmsRuError::HttpStatus { status: 503, .. })
+        ));
+        // SMS.RU refused the first chunk but may have accepted the second.
+        assert_eq!(report.progress.failed, 1);
+        assert_eq!(report.progress.uncertain, 1);
+        assert_eq!(report.progress.sent, 0);
+        assert_eq!(report.progress.cost, None);
+    }
+
+    #[tokio::test]
+    async fn cancel_before_start_sends_nothing() {
+        let transport = FakeTransport::sequence(vec![(
+            200,
+            r#"{"status":"OK","status_code":100,"balance":"100.00"}"#,
+        )]);
+        let client = make_client(Auth::api_id("key").unwrap(), transport.clone());
+        let send = BulkSender::new(client).start_iter(vec![pair("79250000001", "a")]);
+        // The run has not been polled yet on the current-thread test runtime.
+        send.cancel();
+        let report = send.join().await;
+
+        assert!(report.cancelled);
+        assert!(report.chunks.is_empty());
+        assert_eq!(report.consumed, 0);
+        assert!(report.unsent.is_empty());
+        assert_eq!(
+            transport.requested_urls(),
+            vec!["https://example.invalid/my/balance"]
+        );
+    }
+
+    #[tokio::test]
+    async fn cancel_between_chunks_keeps_sent_chunks_and_returns_unsent_pairs() {
+        let transport = FakeTransport::sequence(vec![
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"balance":"100.00"}"#,
+            ),
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"sms":{"79250000001":{"status":"OK","status_code":100,"sms_id":"1"}}}"#,
+            ),
+        ]);
+        let client = make_client(Auth::api_id("key").unwrap(), transport.clone());
+
+        // Cancel from inside the input when the second pair is taken, after the first chunk
+        // was dispatched.
+        let handle = Arc::new(Mutex::new(None::<BulkCancel>));
+        let trigger = Arc::clone(&handle);
+        let input = vec![
+            pair("79250000001", "a"),
+            pair("79250000002", "b"),
+            pair("79250000003", "c"),
+        ]
+        .into_iter()
+        .inspect(move |(phone, _)| {
+            if phone.raw() == "79250000002" {
+                let handle = trigger.lock().unwrap();
+                handle
+                    .as_ref()
+                    .expect("handle is set before the run")
+                    .cancel();
+            }
+        });
+        let send = BulkSender::new(client)
+            .chunk_size(1)
+            .concurrency(1)
+            .start_iter(input);
+        *handle.lock().unwrap() = Some(send.cancel_handle());
+        let report = send.join().await;
+
+        assert!(report.cancelled);
+        assert_eq!(report.chunks.len(), 1);
+        assert_eq!(report.progress.sent, 1);
+        assert_eq!(report.progress.chunks_done, 1);
+        assert_eq!(report.consumed, 2);
+        assert_eq!(report.unsent, vec![pair("79250000002", "b")]);
+        assert_eq!(transport.requested_urls().len(), 2);
+    }
+}
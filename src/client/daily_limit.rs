@@ -0,0 +1,286 @@
+//! Optional local tracking of the `my/limit` daily allowance.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::domain::{LimitUsageResponse, SendSms, SendSmsResponse};
+
+/// SMS.RU resets daily counters at midnight Moscow time (UTC+3).
+const SMSRU_UTC_OFFSET_SECS: u64 = 3 * 60 * 60;
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone)]
+/// Local counter of recipients sent today, seeded from `my/limit`.
+///
+/// When configured through [`super::SmsRuClientBuilder::daily_limit_guard`], `send_sms` reserves
+/// one unit per recipient before sending and fails fast with [`super::SmsRuError::DailyLimit`]
+/// instead of hitting `DailyLimitExceeded` (206) on the server. The counter is re-seeded from
+/// `my/limit` every `refresh_interval` and whenever the SMS.RU day (Moscow time) changes.
+/// Reservations of sends that are still running are kept apart from the server's count, so a
+/// refresh during concurrent sends never forgets them.
+///
+/// Clones share the same counter, so one guard can be reused by several clients of the same
+/// account.
+pub struct DailyLimitGuard {
+    refresh_interval: Duration,
+    state: Arc<Mutex<DailyLimitState>>,
+}
+
+#[derive(Debug, Default)]
+struct DailyLimitState {
+    total_limit: Option<u32>,
+    /// `used_today` from the last `my/limit` read.
+    server_used: u32,
+    /// Recipients accepted by SMS.RU since the last `my/limit` read.
+    accepted: u32,
+    /// Recipients reserved by sends that have not finished yet.
+    in_flight: u32,
+    refreshed_at: Option<Instant>,
+    day: u64,
+}
+
+impl DailyLimitGuard {
+    /// Create a guard that re-reads `my/limit` at least every `refresh_interval`.
+    pub fn new(refresh_interval: Duration) -> Self {
+        Self {
+            refresh_interval,
+            state: Arc::new(Mutex::new(DailyLimitState::default())),
+        }
+    }
+
+    /// Recipients that can still be sent today, or `None` if the limit is unknown or unset.
+    pub fn remaining(&self) -> Option<u32> {
+        self.state.lock().unwrap().remaining()
+    }
+
+    pub(crate) fn needs_refresh(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        match state.refreshed_at {
+            None => true,
+            Some(at) => at.elapsed() >= self.refresh_interval || state.day != smsru_day(),
+        }
+    }
+
+    pub(crate) fn seed(&self, usage: &LimitUsageResponse) {
+        let mut state = self.state.lock().unwrap();
+        state.total_limit = usage.total_limit;
+        state.server_used = usage.used_today.unwrap_or(0);
+        state.accepted = 0;
+        state.refreshed_at = Some(Instant::now());
+        state.day = smsru_day();
+    }
+
+    /// Reserve `requested` recipients, or report how many are left.
+    pub(crate) fn reserve(&self, requested: u32) -> Result<(), DailyLimitExceeded> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(remaining) = state.remaining() {
+            if requested > remaining {
+                return Err(DailyLimitExceeded {
+                    requested,
+                    remaining,
+                });
+            }
+        }
+        state.in_flight = state.in_flight.saturating_add(requested);
+        Ok(())
+    }
+
+    /// Reserve as many of `requested` recipients as fit and return that number.
+    pub(crate) fn reserve_up_to(&self, requested: u32) -> u32 {
+        let mut state = self.state.lock().unwrap();
+        let granted = state
+            .remaining()
+            .map_or(requested, |remaining| requested.min(remaining));
+        state.in_flight = state.in_flight.saturating_add(granted);
+        granted
+    }
+
+    /// Drop `reserved` and mark today's allowance as used up (SMS.RU reported
+    /// `DailyLimitExceeded`).
+    pub(crate) fn exhaust(&self, reserved: u32) {
+        let mut state = self.state.lock().unwrap();
+        state.in_flight = state.in_flight.saturating_sub(reserved);
+        if let Some(total) = state.total_limit {
+            state.server_used = state.server_used.max(total);
+        }
+    }
+
+    /// Give back reservations that did not turn into sent messages.
+    pub(crate) fn release(&self, count: u32) {
+        let mut state = self.state.lock().unwrap();
+        state.in_flight = state.in_flight.saturating_sub(count);
+    }
+
+    /// Turn `reserved` into the recipients SMS.RU accepted and give back the rest.
+    pub(crate) fn settle(&self, reserved: u32, response: &SendSmsResponse) {
+        let accepted = response
+            .sms
+            .values()
+            .filter(|result| result.status == crate::domain::Status::Ok)
+            .count();
+        let accepted = u32::try_from(accepted).unwrap_or(u32::MAX).min(reserved);
+        let mut state = self.state.lock().unwrap();
+        state.in_flight = state.in_flight.saturating_sub(reserved);
+        state.accepted = state.accepted.saturating_add(accepted);
+    }
+}
+
+impl DailyLimitState {
+    fn remaining(&self) -> Option<u32> {
+        self.total_limit.map(|total| {
+            total
+                .saturating_sub(self.server_used)
+                .saturating_sub(self.accepted)
+                .saturating_sub(self.in_flight)
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("daily limit would be exceeded: {requested} recipient(s) requested, {remaining} remaining")]
+/// A send was refused locally because it would exceed the daily limit.
+pub struct DailyLimitExceeded {
+    /// Number of recipients in the refused request.
+    pub requested: u32,
+    /// Recipients still allowed today according to the local counter.
+    pub remaining: u32,
+}
+
+#[derive(Debug, Clone)]
+/// Outcome of [`super::SmsRuClient::send_sms_within_daily_limit`].
+pub struct DailyLimitedSend {
+    /// Response for the part of the request that fit into today's allowance.
+    pub response: Option<SendSmsResponse>,
+    /// Recipients that did not fit and were not sent.
+    pub deferred: Option<SendSms>,
+}
+
+pub(crate) fn recipient_count(request: &SendSms) -> u32 {
+    u32::try_from(request.recipient_count()).unwrap_or(u32::MAX)
+}
+
+/// Time left until the SMS.RU day (Moscow time) rolls over.
+pub(crate) fn until_next_smsru_day() -> Duration {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let elapsed_today = (now + SMSRU_UTC_OFFSET_SECS) % SECS_PER_DAY;
+    Duration::from_secs(SECS_PER_DAY - elapsed_today)
+}
+
+fn smsru_day() -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    (now + SMSRU_UTC_OFFSET_SECS) / SECS_PER_DAY
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Status, StatusCode};
+
+    fn usage(total_limit: Option<u32>, used_today: Option<u32>) -> LimitUsageResponse {
+        LimitUsageResponse {
+            status: Status::Ok,
+            status_code: StatusCode::new(100),
+            status_text: None,
+            total_limit,
+            used_today,
+        }
+    }
+
+    #[test]
+    fn reserve_fails_fast_when_allowance_is_exhausted() {
+        let guard = DailyLimitGuard::new(Duration::from_secs(60));
+        assert!(guard.needs_refresh());
+
+        guard.seed(&usage(Some(10), Some(7)));
+        assert!(!guard.needs_refresh());
+        assert_eq!(guard.remaining(), Some(3));
+
+        assert!(guard.reserve(2).is_ok());
+        assert_eq!(
+            guard.reserve(2),
+            Err(DailyLimitExceeded {
+                requested: 2,
+                remaining: 1
+            })
+        );
+
+        guard.release(2);
+        assert_eq!(guard.remaining(), Some(3));
+        assert_eq!(guard.reserve_up_to(5), 3);
+        assert_eq!(guard.remaining(), Some(0));
+
+        guard.exhaust(3);
+        assert_eq!(guard.remaining(), Some(0));
+    }
+
+    #[test]
+    fn refresh_keeps_reservations_of_running_sends() {
+        let guard = DailyLimitGuard::new(Duration::from_secs(60));
+        guard.seed(&usage(Some(10), Some(2)));
+        guard.reserve(3).unwrap();
+
+        // `my/limit` does not count the running send yet.
+        guard.seed(&usage(Some(10), Some(2)));
+        assert_eq!(guard.remaining(), Some(5));
+        guard.release(3);
+        assert_eq!(guard.remaining(), Some(8));
+
+        let response = SendSmsResponse {
+            status: Status::Ok,
+            status_code: StatusCode::new(100),
+            status_text: None,
+            balance: None,
+            sms: Default::default(),
+        };
+        guard.reserve(2).unwrap();
+        guard.settle(2, &response);
+        assert_eq!(guard.remaining(), Some(8));
+    }
+
+    #[test]
+    fn concurrent_reserve_seed_and_release_never_overcommit() {
+        let guard = DailyLimitGuard::new(Duration::from_secs(60));
+        guard.seed(&usage(Some(10), Some(2)));
+
+        let workers = (0..8)
+            .map(|_| {
+                let guard = guard.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..200 {
+                        if guard.reserve(1).is_ok() {
+                            guard.seed(&usage(Some(10), Some(2)));
+                            assert!(guard.remaining().unwrap() < 8);
+                            guard.release(1);
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        assert_eq!(guard.remaining(), Some(8));
+    }
+
+    #[test]
+    fn unknown_limit_is_unbounded() {
+        let guard = DailyLimitGuard::new(Duration::from_secs(60));
+        guard.seed(&usage(None, Some(5)));
+        assert_eq!(guard.remaining(), None);
+        assert!(guard.reserve(1_000).is_ok());
+        assert_eq!(guard.reserve_up_to(7), 7);
+    }
+
+    #[test]
+    fn zero_refresh_interval_always_refreshes() {
+        let guard = DailyLimitGuard::new(Duration::ZERO);
+        guard.seed(&usage(Some(10), Some(0)));
+        assert!(guard.needs_refresh());
+    }
+}
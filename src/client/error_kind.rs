@@ -0,0 +1,226 @@
+//! Coarse classification of [`SmsRuError`] for retry/alert/drop decisions.
+
+use std::time::Duration;
+
+use super::{SmsRuError, SpendGuardViolation, daily_limit};
+use crate::domain::{KnownStatusCode, StatusCode};
+
+/// Suggested wait after SMS.RU's "identical message per minute" and repeat-send limits.
+const PER_MINUTE_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// What kind of failure an [`SmsRuError`] represents.
+pub enum SmsRuErrorKind {
+    /// Transient failure (network, 5xx, SMS.RU temporarily unavailable); retry with backoff.
+    Retryable,
+    /// Credentials are invalid or the account is not confirmed.
+    Auth,
+    /// The account balance is too low.
+    InsufficientFunds,
+    /// A rate or daily limit was hit; retry later (see [`SmsRuError::retry_after`]).
+    Throttled,
+    /// The request itself is invalid and will fail again unchanged.
+    InvalidRequest,
+    /// One or more recipients were rejected (invalid number, stoplist, no route).
+    RecipientRejected,
+    /// Other failures that will not succeed on retry.
+    Permanent,
+    /// The outcome could not be classified (unknown status code or unreadable response).
+    Unknown,
+}
+
+impl SmsRuErrorKind {
+    /// Classify an SMS.RU status code returned with `status = ERROR`.
+    pub fn from_status_code(code: StatusCode) -> Self {
+        let Some(known) = code.known_kind() else {
+            return Self::Unknown;
+        };
+        if known.is_auth_error() {
+            return Self::Auth;
+        }
+        match known {
+            KnownStatusCode::InsufficientFunds => Self::InsufficientFunds,
+            KnownStatusCode::ServiceTemporarilyUnavailable | KnownStatusCode::ServerError => {
+                Self::Retryable
+            }
+            KnownStatusCode::DailyLimitExceeded
+            | KnownStatusCode::ExceededDailyLimitToNumber
+            | KnownStatusCode::ExceededIdenticalPerMinute
+            | KnownStatusCode::ExceededIdenticalPerDay
+            | KnownStatusCode::ExceededRepeatSendLimit
+            | KnownStatusCode::TooManyConfirmationCodes
+            | KnownStatusCode::TooManyWrongAttempts
+            | KnownStatusCode::LimitIpCountryMismatchCategory1
+            | KnownStatusCode::LimitIpCountryMismatchCategory2
+            | KnownStatusCode::LimitTooManyToCountry
+            | KnownStatusCode::LimitTooManyForeignAuth
+            | KnownStatusCode::LimitTooManyFromIp
+            | KnownStatusCode::LimitHostingProviderIp
+            | KnownStatusCode::LimitTooManyCalls => Self::Throttled,
+            KnownStatusCode::InvalidRecipientOrNoRoute
+            | KnownStatusCode::NotDeliveredNoRoute
+            | KnownStatusCode::NoDeliveryRoute
+            | KnownStatusCode::RecipientInStopList
+            | KnownStatusCode::RecipientAbroadBlocked
+            | KnownStatusCode::RecipientInGlobalStopList
+            | KnownStatusCode::CountryBlocked => Self::RecipientRejected,
+            KnownStatusCode::EmptyMessageText
+            | KnownStatusCode::SenderNotEnabled
+            | KnownStatusCode::MessageTooLong
+            | KnownStatusCode::InvalidTime
+            | KnownStatusCode::UsedGetInsteadOfPost
+            | KnownStatusCode::MethodNotFound
+            | KnownStatusCode::MessageNotUtf8
+            | KnownStatusCode::TooManyNumbers
+            | KnownStatusCode::ForbiddenWordInText
+            | KnownStatusCode::MissingDisclaimerPhrase
+            | KnownStatusCode::SenderMustMatchBrand
+            | KnownStatusCode::InvalidEndUserIp
+            | KnownStatusCode::CallbackUrlInvalid
+            | KnownStatusCode::CallbackHandlerNotFound => Self::InvalidRequest,
+            _ => Self::Permanent,
+        }
+    }
+
+    /// Whether retrying the same request later can succeed.
+    pub fn is_retryable(self) -> bool {
+        matches!(self, Self::Retryable | Self::Throttled)
+    }
+}
+
+impl SmsRuError {
+    /// Classify this error; see [`SmsRuErrorKind`].
+    pub fn kind(&self) -> SmsRuErrorKind {
+        match self {
+            Self::Transport(_) | Self::IdempotencyKeyInFlight(_) => SmsRuErrorKind::Retryable,
+            Self::HttpStatus { status, .. } => match status {
+                429 => SmsRuErrorKind::Throttled,
+                408 | 500..=599 => SmsRuErrorKind::Retryable,
+                401 | 403 => SmsRuErrorKind::Auth,
+                400..=499 => SmsRuErrorKind::InvalidRequest,
+                _ => SmsRuErrorKind::Unknown,
+            },
+            Self::Api { status_code, .. } => SmsRuErrorKind::from_status_code(*status_code),
+            Self::Parse(_) | Self::IdempotencyOutcomeUnknown(_) => SmsRuErrorKind::Unknown,
+            Self::UnsupportedResponseFormat(_)
+            | Self::Validation(_)
+            | Self::SenderNotApproved(_)
+            | Self::IdempotencyKeyReused(_) => SmsRuErrorKind::InvalidRequest,
+            Self::SpendGuard(SpendGuardViolation::RecipientsRejected { .. })
+            | Self::RecipientPolicy(_)
+            | Self::RecipientsFailed(_) => SmsRuErrorKind::RecipientRejected,
+            Self::Abuse(violation) if violation.is_temporary() => SmsRuErrorKind::Throttled,
+            Self::Abuse(_) => SmsRuErrorKind::RecipientRejected,
+            Self::SpendGuard(_) => SmsRuErrorKind::Permanent,
+            Self::DailyLimit(_) => SmsRuErrorKind::Throttled,
+        }
+    }
+
+    /// Whether SMS.RU may have accepted (and billed) the request that ended with this error.
+    ///
+    /// False for local refusals and for errors SMS.RU returned instead of sending.
+    pub(crate) fn may_have_been_accepted(&self) -> bool {
+        !matches!(
+            self,
+            Self::Api { .. }
+                | Self::Validation(_)
+                | Self::UnsupportedResponseFormat(_)
+                | Self::SpendGuard(_)
+                | Self::RecipientPolicy(_)
+                | Self::Abuse(_)
+                | Self::SenderNotApproved(_)
+                | Self::DailyLimit(_)
+                | Self::IdempotencyKeyInFlight(_)
+                | Self::IdempotencyKeyReused(_)
+        )
+    }
+
+    /// Suggested wait before retrying a throttled request, when SMS.RU's limit window is known.
+    ///
+    /// Per-minute limits (`231`, `233`) suggest one minute; daily limits (`206`, `230`, `232`,
+    /// and [`SmsRuError::DailyLimit`]) suggest waiting until the SMS.RU day (Moscow time) rolls
+    /// over. [`SmsRuError::Abuse`] suggests the time until the local velocity limits free up.
+    pub fn retry_after(&self) -> Option<Duration> {
+        let known = match self {
+            Self::DailyLimit(_) => return Some(daily_limit::until_next_smsru_day()),
+            Self::Abuse(violation) => return violation.retry_after(),
+            Self::Api { status_code, .. } => status_code.known_kind()?,
+            _ => return None,
+        };
+        match known {
+            KnownStatusCode::ExceededIdenticalPerMinute
+            | KnownStatusCode::ExceededRepeatSendLimit => Some(PER_MINUTE_RETRY_AFTER),
+            KnownStatusCode::DailyLimitExceeded
+            | KnownStatusCode::ExceededDailyLimitToNumber
+            | KnownStatusCode::ExceededIdenticalPerDay => Some(daily_limit::until_next_smsru_day()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::DailyLimitExceeded;
+    use crate::domain::ValidationError;
+
+    fn api(code: i32) -> SmsRuError {
+        SmsRuError::Api {
+            status_code: StatusCode::new(code),
+            status_text: None,
+        }
+    }
+
+    #[test]
+    fn api_errors_are_classified_by_status_code() {
+        assert_eq!(api(200).kind(), SmsRuErrorKind::Auth);
+        assert_eq!(api(201).kind(), SmsRuErrorKind::InsufficientFunds);
+        assert_eq!(api(220).kind(), SmsRuErrorKind::Retryable);
+        assert_eq!(api(231).kind(), SmsRuErrorKind::Throttled);
+        assert_eq!(api(209).kind(), SmsRuErrorKind::RecipientRejected);
+        assert_eq!(api(204).kind(), SmsRuErrorKind::InvalidRequest);
+        assert_eq!(api(402).kind(), SmsRuErrorKind::Permanent);
+        assert_eq!(api(999).kind(), SmsRuErrorKind::Unknown);
+    }
+
+    #[test]
+    fn client_side_errors_are_classified() {
+        let http = |status| SmsRuError::HttpStatus { status, body: None };
+        assert_eq!(http(429).kind(), SmsRuErrorKind::Throttled);
+        assert_eq!(http(503).kind(), SmsRuErrorKind::Retryable);
+        assert_eq!(http(403).kind(), SmsRuErrorKind::Auth);
+        assert_eq!(http(404).kind(), SmsRuErrorKind::InvalidRequest);
+        assert_eq!(
+            SmsRuError::Transport("reset".into()).kind(),
+            SmsRuErrorKind::Retryable
+        );
+        assert_eq!(
+            SmsRuError::Validation(ValidationError::Empty { field: "msg" }).kind(),
+            SmsRuErrorKind::InvalidRequest
+        );
+        assert_eq!(
+            SmsRuError::SpendGuard(SpendGuardViolation::CostExceedsCap {
+                total_cost: 2.0,
+                cap: 1.0
+            })
+            .kind(),
+            SmsRuErrorKind::Permanent
+        );
+        assert!(SmsRuErrorKind::Throttled.is_retryable());
+        assert!(!SmsRuErrorKind::Auth.is_retryable());
+    }
+
+    #[test]
+    fn retry_after_covers_per_minute_and_daily_limits() {
+        assert_eq!(api(231).retry_after(), Some(Duration::from_secs(60)));
+        let daily = api(232).retry_after().unwrap();
+        assert!(daily > Duration::ZERO && daily <= Duration::from_secs(24 * 60 * 60));
+        let local = SmsRuError::DailyLimit(DailyLimitExceeded {
+            requested: 1,
+            remaining: 0,
+        });
+        assert!(local.retry_after().is_some());
+        assert_eq!(api(220).retry_after(), None);
+        assert_eq!(api(202).retry_after(), None);
+    }
+}
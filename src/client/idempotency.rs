@@ -0,0 +1,256 @@
+//! Idempotency keys for `send_sms`: remember responses so retries do not send twice.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::domain::{IdempotencyKey, SendSmsResponse};
+
+/// How long responses are remembered unless configured otherwise.
+///
+/// One day covers SMS.RU's "identical message per day" limit (232).
+pub(crate) const DEFAULT_IDEMPOTENCY_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, PartialEq)]
+/// What an [`IdempotencyStore`] remembers for a key.
+///
+/// Both states carry the fingerprint of the request sent with the key, so reusing the key for
+/// a different request is detected.
+pub enum IdempotencyEntry {
+    /// A send was started but its outcome is not recorded: it is still running, or it failed in
+    /// a way that SMS.RU may still have accepted (e.g. a timeout).
+    Started { fingerprint: String },
+    /// SMS.RU answered the send with this response.
+    Completed {
+        fingerprint: String,
+        response: SendSmsResponse,
+    },
+}
+
+impl IdempotencyEntry {
+    /// Fingerprint of the request sent with the key.
+    pub fn fingerprint(&self) -> &str {
+        match self {
+            Self::Started { fingerprint } | Self::Completed { fingerprint, .. } => fingerprint,
+        }
+    }
+}
+
+/// Storage for [`super::SmsRuClient::send_sms_idempotent`].
+///
+/// Implement this to share results between processes (e.g. Redis or a database); the default
+/// is an [`InMemoryIdempotencyStore`] owned by the client.
+pub trait IdempotencyStore: Send + Sync + fmt::Debug {
+    /// Return the entry stored for `key`, unless it has expired.
+    fn get(&self, key: &IdempotencyKey) -> Option<IdempotencyEntry>;
+
+    /// Atomically store [`IdempotencyEntry::Started`] with `fingerprint` for `key` if it has no
+    /// live entry, or return the live entry unchanged.
+    ///
+    /// This must be a single insert-if-absent operation (e.g. Redis `SET NX`, or an insert
+    /// guarded by a unique key), so that two processes sharing the store never both start a
+    /// send with the same key.
+    fn try_start(
+        &self,
+        key: IdempotencyKey,
+        fingerprint: String,
+        ttl: Duration,
+    ) -> Result<(), IdempotencyEntry>;
+
+    /// Remember `entry` for `key` for at least `ttl`, replacing any previous entry.
+    fn insert(&self, key: IdempotencyKey, entry: IdempotencyEntry, ttl: Duration);
+
+    /// Forget `key`, e.g. after confirming that a [`IdempotencyEntry::Started`] send never
+    /// reached SMS.RU.
+    fn remove(&self, key: &IdempotencyKey);
+}
+
+#[derive(Debug, Default)]
+/// Process-local [`IdempotencyStore`] that drops entries after their TTL.
+pub struct InMemoryIdempotencyStore {
+    entries: Mutex<HashMap<IdempotencyKey, (Instant, IdempotencyEntry)>>,
+}
+
+impl InMemoryIdempotencyStore {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of entries that have not expired yet.
+    pub fn len(&self) -> usize {
+        let now = Instant::now();
+        self.entries
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|(expires_at, _)| *expires_at > now)
+            .count()
+    }
+
+    /// Whether the store holds no live entries.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl IdempotencyStore for InMemoryIdempotencyStore {
+    fn get(&self, key: &IdempotencyKey) -> Option<IdempotencyEntry> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(key)
+            .filter(|(expires_at, _)| *expires_at > Instant::now())
+            .map(|(_, entry)| entry.clone())
+    }
+
+    fn try_start(
+        &self,
+        key: IdempotencyKey,
+        fingerprint: String,
+        ttl: Duration,
+    ) -> Result<(), IdempotencyEntry> {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (expires_at, _)| *expires_at > now);
+        if let Some((_, entry)) = entries.get(&key) {
+            return Err(entry.clone());
+        }
+        entries.insert(key, (now + ttl, IdempotencyEntry::Started { fingerprint }));
+        Ok(())
+    }
+
+    fn insert(&self, key: IdempotencyKey, entry: IdempotencyEntry, ttl: Duration) {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (expires_at, _)| *expires_at > now);
+        entries.insert(key, (now + ttl, entry));
+    }
+
+    fn remove(&self, key: &IdempotencyKey) {
+        self.entries.lock().unwrap().remove(key);
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Store, TTL, and the keys currently being sent by clients sharing this configuration.
+pub(crate) struct Idempotency {
+    pub(crate) store: Arc<dyn IdempotencyStore>,
+    pub(crate) ttl: Duration,
+    in_flight: Arc<Mutex<HashSet<IdempotencyKey>>>,
+}
+
+impl Idempotency {
+    pub(crate) fn new(store: Arc<dyn IdempotencyStore>, ttl: Duration) -> Self {
+        Self {
+            store,
+            ttl,
+            in_flight: Arc::default(),
+        }
+    }
+
+    /// Mark `key` as being sent, or return `None` if another send with it is in flight.
+    pub(crate) fn begin(&self, key: &IdempotencyKey) -> Option<InFlight> {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if !in_flight.insert(key.clone()) {
+            return None;
+        }
+        Some(InFlight {
+            key: key.clone(),
+            in_flight: Arc::clone(&self.in_flight),
+        })
+    }
+}
+
+impl Default for Idempotency {
+    fn default() -> Self {
+        Self::new(
+            Arc::new(InMemoryIdempotencyStore::new()),
+            DEFAULT_IDEMPOTENCY_TTL,
+        )
+    }
+}
+
+/// Releases the in-flight mark when the send finishes or is cancelled.
+pub(crate) struct InFlight {
+    key: IdempotencyKey,
+    in_flight: Arc<Mutex<HashSet<IdempotencyKey>>>,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.in_flight.lock().unwrap().remove(&self.key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::domain::{Status, StatusCode};
+
+    fn response() -> SendSmsResponse {
+        SendSmsResponse {
+            status: Status::Ok,
+            status_code: StatusCode::new(100),
+            status_text: None,
+            balance: None,
+            sms: BTreeMap::new(),
+        }
+    }
+
+    fn started(fingerprint: &str) -> IdempotencyEntry {
+        IdempotencyEntry::Started {
+            fingerprint: fingerprint.to_owned(),
+        }
+    }
+
+    #[test]
+    fn in_memory_store_expires_entries() {
+        let store = InMemoryIdempotencyStore::new();
+        let key = IdempotencyKey::new("otp-1").unwrap();
+        let completed = IdempotencyEntry::Completed {
+            fingerprint: "f1".to_owned(),
+            response: response(),
+        };
+        store.insert(key.clone(), completed.clone(), Duration::from_secs(60));
+        assert_eq!(store.get(&key), Some(completed));
+        assert_eq!(store.len(), 1);
+
+        store.insert(key.clone(), started("f1"), Duration::ZERO);
+        assert_eq!(store.get(&key), None);
+        assert!(store.is_empty());
+
+        store.insert(key.clone(), started("f1"), Duration::from_secs(60));
+        store.remove(&key);
+        assert_eq!(store.get(&key), None);
+    }
+
+    #[test]
+    fn try_start_only_starts_keys_without_a_live_entry() {
+        let store = InMemoryIdempotencyStore::new();
+        let key = IdempotencyKey::new("otp-1").unwrap();
+        let ttl = Duration::from_secs(60);
+
+        assert_eq!(store.try_start(key.clone(), "f1".to_owned(), ttl), Ok(()));
+        assert_eq!(
+            store.try_start(key.clone(), "f2".to_owned(), ttl),
+            Err(started("f1"))
+        );
+
+        store.insert(key.clone(), started("f1"), Duration::ZERO);
+        assert_eq!(store.try_start(key.clone(), "f2".to_owned(), ttl), Ok(()));
+        assert_eq!(store.get(&key).unwrap().fingerprint(), "f2");
+    }
+
+    #[test]
+    fn in_flight_keys_are_exclusive_until_dropped() {
+        let idempotency = Idempotency::default();
+        let key = IdempotencyKey::new("otp-1").unwrap();
+        let guard = idempotency.begin(&key).unwrap();
+        assert!(idempotency.clone().begin(&key).is_none());
+        drop(guard);
+        assert!(idempotency.begin(&key).is_some());
+    }
+}
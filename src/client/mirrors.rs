@@ -0,0 +1,208 @@
+//! Fallback base URLs used after connection-level failures.
+
+use std::error::Error as StdError;
+use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::{HttpResponse, HttpTransport, SmsRuError};
+
+#[derive(Debug, Clone)]
+/// Primary base URL plus ordered fallbacks; clones share the active mirror.
+pub(crate) struct Mirrors {
+    bases: Vec<String>,
+    active: Arc<AtomicUsize>,
+}
+
+impl Mirrors {
+    pub(crate) fn new(primary: String, fallbacks: Vec<String>) -> Self {
+        let mut bases = vec![primary];
+        bases.extend(fallbacks);
+        Self {
+            bases,
+            active: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// POST to `endpoint`, moving on to the next mirror when the connection cannot be made.
+    ///
+    /// Only endpoints under the primary base URL are rewritten; per-method overrides pointing
+    /// elsewhere are used as-is. The mirror that answered stays active for later requests.
+    pub(crate) async fn post_form(
+        &self,
+        http: &dyn HttpTransport,
+        endpoint: &str,
+        params: Vec<(String, String)>,
+    ) -> Result<HttpResponse, SmsRuError> {
+        // Require a path boundary so `https://sms.ru` does not match `https://sms.ru.evil/...`.
+        let path = endpoint
+            .strip_prefix(self.bases[0].as_str())
+            .filter(|path| path.is_empty() || path.starts_with(['/', '?']));
+        let Some(path) = path else {
+            return http
+                .post_form(endpoint, params)
+                .await
+                .map_err(SmsRuError::Transport);
+        };
+
+        let start = self.active.load(Ordering::Relaxed);
+        let mut last_error = None;
+        for offset in 0..self.bases.len() {
+            let idx = (start + offset) % self.bases.len();
+            let url = format!("{}{path}", self.bases[idx]);
+            match http.post_form(&url, params.clone()).await {
+                Ok(response) => {
+                    self.active.store(idx, Ordering::Relaxed);
+                    return Ok(response);
+                }
+                Err(err) if is_connect_error(err.as_ref()) => last_error = Some(err),
+                Err(err) => return Err(SmsRuError::Transport(err)),
+            }
+        }
+        Err(SmsRuError::Transport(
+            last_error.expect("at least one base URL is configured"),
+        ))
+    }
+}
+
+/// Whether `err` means the request never reached the server (so retrying elsewhere is safe).
+pub(crate) fn is_connect_error(err: &(dyn StdError + 'static)) -> bool {
+    let mut current = Some(err);
+    while let Some(err) = current {
+        if let Some(err) = err.downcast_ref::<reqwest::Error>() {
+            if err.is_connect() {
+                return true;
+            }
+        }
+        if let Some(err) = err.downcast_ref::<io::Error>() {
+            if matches!(
+                err.kind(),
+                io::ErrorKind::ConnectionRefused
+                    | io::ErrorKind::NotConnected
+                    | io::ErrorKind::AddrNotAvailable
+                    | io::ErrorKind::HostUnreachable
+                    | io::ErrorKind::NetworkUnreachable
+            ) {
+                return true;
+            }
+        }
+        current = err.source();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::test_support::FakeTransport;
+
+    fn mirrors() -> Mirrors {
+        Mirrors::new(
+            "https://primary.invalid".to_owned(),
+            vec![
+                "https://mirror-a.invalid".to_owned(),
+                "https://mirror-b.invalid".to_owned(),
+            ],
+        )
+    }
+
+    #[tokio::test]
+    async fn switches_to_next_mirror_on_connect_error_and_stays_there() {
+        let transport = FakeTransport::new(200, "{}");
+        transport.refuse("https://primary.invalid");
+        let mirrors = mirrors();
+
+        mirrors
+            .post_form(&transport, "https://primary.invalid/sms/send", Vec::new())
+            .await
+            .unwrap();
+        mirrors
+            .post_form(&transport, "https://primary.invalid/my/balance", Vec::new())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            transport.requested_urls(),
+            vec![
+                "https://primary.invalid/sms/send",
+                "https://mirror-a.invalid/sms/send",
+                "https://mirror-a.invalid/my/balance",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn endpoints_only_sharing_a_host_prefix_are_not_rewritten() {
+        let transport = FakeTransport::new(200, "{}");
+        transport.refuse("https://primary.invalid");
+        let mirrors = mirrors();
+
+        let err = mirrors
+            .post_form(
+                &transport,
+                "https://primary.invalid.evil/sms/send",
+                Vec::new(),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(err, SmsRuError::Transport(_)));
+        assert_eq!(
+            transport.requested_urls(),
+            vec!["https://primary.invalid.evil/sms/send"]
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_last_connect_error_when_every_mirror_is_down() {
+        let transport = FakeTransport::new(200, "{}");
+        transport.refuse("https://");
+        let err = mirrors()
+            .post_form(&transport, "https://primary.invalid/sms/send", Vec::new())
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, SmsRuError::Transport(_)));
+        assert_eq!(transport.requested_urls().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn leaves_endpoints_outside_the_base_url_alone() {
+        let transport = FakeTransport::new(200, "{}");
+        transport.refuse("https://other.invalid");
+        let err = mirrors()
+            .post_form(&transport, "https://other.invalid/sms/send", Vec::new())
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, SmsRuError::Transport(_)));
+        assert_eq!(
+            transport.requested_urls(),
+            vec!["https://other.invalid/sms/send"]
+        );
+    }
+
+    #[derive(Debug)]
+    struct Wrapped(io::Error);
+
+    impl std::fmt::Display for Wrapped {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("request failed")
+        }
+    }
+
+    impl StdError for Wrapped {
+        fn source(&self) -> Option<&(dyn StdError + 'static)> {
+            Some(&self.0)
+        }
+    }
+
+    #[test]
+    fn detects_connect_errors_in_the_source_chain() {
+        let refused = io::Error::new(io::ErrorKind::ConnectionRefused, "refused");
+        assert!(is_connect_error(&refused));
+        let wrapped = Wrapped(refused);
+        assert!(is_connect_error(&wrapped));
+        let timeout = io::Error::new(io::ErrorKind::TimedOut, "timed out");
+        assert!(!is_connect_error(&timeout));
+    }
+}
@@ -1,20 +1,57 @@
 //! Client layer: orchestrates transport calls and maps transport ↔ domain.
 
+use std::collections::{BTreeMap, BTreeSet};
 use std::error::Error as StdError;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
+
+use phonenumber::country;
 
 use crate::domain::{
-    AddCallback, AddStoplistEntry, ApiId, BalanceResponse, CallbacksResponse, CheckCallAuthStatus,
-    CheckCallAuthStatusResponse, CheckCost, CheckCostOptions, CheckCostResponse, CheckStatus,
-    CheckStatusResponse, FreeUsageResponse, LimitUsageResponse, Login, Password, RemoveCallback,
-    RemoveStoplistEntry, SendOptions, SendSms, SendSmsResponse, SendersResponse, StartCallAuth,
-    StartCallAuthResponse, Status, StatusCode, StatusOnlyResponse, StoplistResponse,
-    ValidationError,
+    AddCallback, AddStoplistEntry, ApiId, BalanceResponse, CallbackSyncPolicy, CallbackSyncReport,
+    CallbackUrl, CallbacksResponse, CheckCallAuthStatus, CheckCallAuthStatusResponse, CheckCost,
+    CheckCostOptions, CheckCostResponse, CheckStatus, CheckStatusResponse, FreeUsageResponse,
+    IdempotencyKey, KnownStatusCode, LimitUsageResponse, Login, MessageLimits, MessageText,
+    NormalizedSendSms, Password, RemoveCallback, RemoveStoplistEntry, SendOptions, SendSms,
+    SendSmsResponse, SenderId, SendersResponse, StartCallAuth, StartCallAuthResponse, Status,
+    StatusCode, StatusOnlyResponse, StoplistResponse, ValidationError,
+};
+
+mod abuse_guard;
+mod bulk;
+mod daily_limit;
+mod error_kind;
+mod idempotency;
+mod mirrors;
+mod pool;
+mod recipient_policy;
+mod sender_router;
+mod senders;
+#[cfg(feature = "tower")]
+mod service;
+mod spend_guard;
+#[cfg(test)]
+pub(crate) mod test_support;
+
+pub use abuse_guard::{
+    AbuseDecision, AbuseGuard, AbuseGuardViolation, AbuseOperation, AbuseReason,
+};
+pub use bulk::{BulkCancel, BulkChunk, BulkProgress, BulkReport, BulkSend, BulkSender};
+pub use daily_limit::{DailyLimitExceeded, DailyLimitGuard, DailyLimitedSend};
+pub use error_kind::SmsRuErrorKind;
+pub use idempotency::{IdempotencyEntry, IdempotencyStore, InMemoryIdempotencyStore};
+pub use pool::{
+    PoolPolicy, PooledSend, SmsRuPool, SmsRuPoolBuilder, SmsRuPoolError, is_failover_error,
 };
+pub use recipient_policy::{RecipientPolicy, RecipientPolicyViolation, RecipientRejection};
+pub use sender_router::{RoutedSend, SenderRouter};
+#[cfg(feature = "tower")]
+pub use service::{SmsRuFuture, SmsRuRequest, SmsRuResponse};
+pub use spend_guard::{SpendGuard, SpendGuardViolation};
 
+const DEFAULT_BASE_URL: &str = "https://sms.ru";
 const DEFAULT_SEND_ENDPOINT: &str = "https://sms.ru/sms/send";
 const DEFAULT_COST_ENDPOINT: &str = "https://sms.ru/sms/cost";
 const DEFAULT_STATUS_ENDPOINT: &str = "https://sms.ru/sms/status";
@@ -34,6 +71,11 @@ const DEFAULT_CALLBACK_GET_ENDPOINT: &str = "https://sms.ru/callback/get";
 
 type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
 
+#[derive(Debug, Clone, Copy)]
+struct Normalization {
+    default_region: Option<country::Id>,
+}
+
 #[derive(Debug, Clone)]
 struct HttpResponse {
     status: u16,
@@ -144,12 +186,53 @@ pub enum SmsRuError {
     /// One of the domain constructors rejected an invalid value.
     #[error("validation error: {0}")]
     Validation(#[from] ValidationError),
+
+    /// The configured [`SpendGuard`] refused to send the request.
+    #[error("spend guard: {0}")]
+    SpendGuard(#[source] SpendGuardViolation),
+
+    /// The configured [`RecipientPolicy`] refused one or more recipients; nothing was sent.
+    #[error("{0}")]
+    RecipientPolicy(#[source] RecipientPolicyViolation),
+
+    /// The configured [`AbuseGuard`] blocked one or more recipients; nothing was sent.
+    #[error("{0}")]
+    Abuse(#[source] AbuseGuardViolation),
+
+    /// `SendOptions.from` is not in the account's approved senders (only with
+    /// [`SmsRuClientBuilder::approved_senders_ttl`]); nothing was sent.
+    #[error("sender {:?} is not approved for this account", .0.as_str())]
+    SenderNotApproved(SenderId),
+
+    /// The configured [`DailyLimitGuard`] refused to send the request.
+    #[error("{0}")]
+    DailyLimit(#[source] DailyLimitExceeded),
+
+    /// SMS.RU rejected some recipients (only with
+    /// [`SmsRuClientBuilder::fail_on_recipient_errors`]).
+    ///
+    /// Other recipients in the response may have been accepted and will be delivered.
+    #[error("{} recipient(s) rejected by SMS.RU", .0.failures().count())]
+    RecipientsFailed(Box<SendSmsResponse>),
+
+    /// A send with the same idempotency key is already in progress.
+    #[error("send with idempotency key {:?} is already in progress", .0.as_str())]
+    IdempotencyKeyInFlight(IdempotencyKey),
+
+    /// An earlier send with the same idempotency key failed in a way SMS.RU may still have
+    /// accepted (e.g. a timeout), so it is not sent again.
+    #[error("outcome of earlier send with idempotency key {:?} is unknown", .0.as_str())]
+    IdempotencyOutcomeUnknown(IdempotencyKey),
+
+    /// The idempotency key was already used for a different request.
+    #[error("idempotency key {:?} was already used for a different request", .0.as_str())]
+    IdempotencyKeyReused(IdempotencyKey),
 }
 
 #[derive(Debug, Clone)]
 /// Builder for [`SmsRuClient`].
 ///
-/// Use this when you need to customize the endpoint, timeout, or user-agent.
+/// Use this when you need to customize endpoints, timeouts, the user-agent, proxy, or TLS roots.
 pub struct SmsRuClientBuilder {
     auth: Auth,
     send_endpoint: String,
@@ -168,8 +251,25 @@ pub struct SmsRuClientBuilder {
     callback_add_endpoint: String,
     callback_del_endpoint: String,
     callback_get_endpoint: String,
+    base_url: String,
+    fallback_base_urls: Vec<String>,
     timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
     user_agent: Option<String>,
+    proxy: Option<String>,
+    proxy_basic_auth: Option<(String, String)>,
+    root_certificates_pem: Vec<Vec<u8>>,
+    only_root_certificates_pem: Vec<Vec<u8>>,
+    spend_guard: Option<SpendGuard>,
+    recipient_policy: Option<RecipientPolicy>,
+    abuse_guard: Option<AbuseGuard>,
+    approved_senders: Option<senders::ApprovedSenders>,
+    message_limits: Option<MessageLimits>,
+    daily_limit: Option<DailyLimitGuard>,
+    idempotency: idempotency::Idempotency,
+    fail_on_recipient_errors: bool,
+    normalization: Option<Normalization>,
 }
 
 impl SmsRuClientBuilder {
@@ -193,15 +293,75 @@ impl SmsRuClientBuilder {
             callback_add_endpoint: DEFAULT_CALLBACK_ADD_ENDPOINT.to_owned(),
             callback_del_endpoint: DEFAULT_CALLBACK_DEL_ENDPOINT.to_owned(),
             callback_get_endpoint: DEFAULT_CALLBACK_GET_ENDPOINT.to_owned(),
+            base_url: DEFAULT_BASE_URL.to_owned(),
+            fallback_base_urls: Vec::new(),
             timeout: None,
+            connect_timeout: None,
+            read_timeout: None,
             user_agent: None,
-        }
+            proxy: None,
+            proxy_basic_auth: None,
+            root_certificates_pem: Vec::new(),
+            only_root_certificates_pem: Vec::new(),
+            spend_guard: None,
+            recipient_policy: None,
+            abuse_guard: None,
+            approved_senders: None,
+            message_limits: None,
+            daily_limit: None,
+            idempotency: idempotency::Idempotency::default(),
+            fail_on_recipient_errors: false,
+            normalization: None,
+        }
+    }
+
+    /// Derive every method endpoint from one root, e.g. `https://proxy.example/smsru`.
+    ///
+    /// Each endpoint becomes `<base_url>/<method path>` (`/sms/send`, `/my/balance`, ...); a
+    /// trailing `/` is ignored. Call this before any per-method override, which it replaces.
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        let base = base_url.into().trim_end_matches('/').to_owned();
+        self.send_endpoint = format!("{base}/sms/send");
+        self.cost_endpoint = format!("{base}/sms/cost");
+        self.status_endpoint = format!("{base}/sms/status");
+        self.callcheck_add_endpoint = format!("{base}/callcheck/add");
+        self.callcheck_status_endpoint = format!("{base}/callcheck/status");
+        self.auth_check_endpoint = format!("{base}/auth/check");
+        self.my_balance_endpoint = format!("{base}/my/balance");
+        self.my_free_endpoint = format!("{base}/my/free");
+        self.my_limit_endpoint = format!("{base}/my/limit");
+        self.my_senders_endpoint = format!("{base}/my/senders");
+        self.stoplist_add_endpoint = format!("{base}/stoplist/add");
+        self.stoplist_del_endpoint = format!("{base}/stoplist/del");
+        self.stoplist_get_endpoint = format!("{base}/stoplist/get");
+        self.callback_add_endpoint = format!("{base}/callback/add");
+        self.callback_del_endpoint = format!("{base}/callback/del");
+        self.callback_get_endpoint = format!("{base}/callback/get");
+        self.base_url = base;
+        self
+    }
+
+    /// Base URLs to switch to, in order, when the current one cannot be connected to.
+    ///
+    /// Only connection-level failures (refused, unreachable, DNS) trigger a switch, because the
+    /// request cannot have reached SMS.RU; the mirror that answers stays active. Endpoints
+    /// overridden per method to a URL outside [`SmsRuClientBuilder::base_url`] never fail over.
+    pub fn fallback_base_urls<I, S>(mut self, base_urls: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.fallback_base_urls = base_urls
+            .into_iter()
+            .map(|url| url.into().trim_end_matches('/').to_owned())
+            .collect();
+        self
     }
 
     /// Override all SMS.RU endpoint URLs (`sms/send`, `sms/cost`, and `sms/status`) at once.
     ///
     /// This is kept for backwards compatibility with older code that configured a
-    /// single endpoint value.
+    /// single endpoint value. Prefer [`SmsRuClientBuilder::base_url`].
     pub fn endpoint(mut self, endpoint: impl Into<String>) -> Self {
         let endpoint = endpoint.into();
         self.send_endpoint = endpoint.clone();
@@ -325,25 +485,188 @@ impl SmsRuClientBuilder {
         self
     }
 
+    /// Set a timeout for establishing the TCP/TLS connection only.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Set a timeout for each read from the connection (reset after every successful read).
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
     /// Override the HTTP `User-Agent` header.
     pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
         self.user_agent = Some(user_agent.into());
         self
     }
 
+    /// Send all requests through an HTTP(S) proxy, e.g. `http://proxy.internal:3128`.
+    ///
+    /// Replaces proxies picked up from the environment (`HTTPS_PROXY` and friends).
+    pub fn proxy(mut self, url: impl Into<String>) -> Self {
+        self.proxy = Some(url.into());
+        self
+    }
+
+    /// Authenticate to the [`SmsRuClientBuilder::proxy`] with `Proxy-Authorization: Basic`.
+    ///
+    /// Has no effect when no proxy is configured.
+    pub fn proxy_basic_auth(
+        mut self,
+        username: impl Into<String>,
+        password: impl Into<String>,
+    ) -> Self {
+        self.proxy_basic_auth = Some((username.into(), password.into()));
+        self
+    }
+
+    /// Trust the certificates in a PEM bundle in addition to the system roots.
+    ///
+    /// Can be called several times; the bundle is parsed by [`SmsRuClientBuilder::build`].
+    pub fn root_certificates_pem(mut self, pem: impl Into<Vec<u8>>) -> Self {
+        self.root_certificates_pem.push(pem.into());
+        self
+    }
+
+    /// Trust only the CA certificates in a PEM bundle, ignoring system and extra roots.
+    ///
+    /// Connections to servers whose chain does not end at one of these roots are refused. This
+    /// restricts the trust anchors only: any certificate those CAs issue for the host is
+    /// accepted, so it is not leaf-certificate or public-key (SPKI) pinning. Can be called
+    /// several times to trust a set.
+    pub fn only_root_certificates_pem(mut self, pem: impl Into<Vec<u8>>) -> Self {
+        self.only_root_certificates_pem.push(pem.into());
+        self
+    }
+
+    /// Check cost (and balance, if configured) before every `send_sms` call.
+    ///
+    /// See [`SpendGuard`] for the available limits.
+    pub fn spend_guard(mut self, guard: SpendGuard) -> Self {
+        self.spend_guard = Some(guard);
+        self
+    }
+
+    /// Refuse sends and cost checks to recipients outside the given countries or number types.
+    ///
+    /// See [`RecipientPolicy`] for how recipients are resolved.
+    pub fn recipient_policy(mut self, policy: RecipientPolicy) -> Self {
+        self.recipient_policy = Some(policy);
+        self
+    }
+
+    /// Check `send_sms` and `start_call_auth` recipients against velocity limits and country
+    /// risk before sending.
+    ///
+    /// See [`AbuseGuard`] for the available checks.
+    pub fn abuse_guard(mut self, guard: AbuseGuard) -> Self {
+        self.abuse_guard = Some(guard);
+        self
+    }
+
+    /// Check every message text of `send_sms` and `check_cost` against `limits` before sending.
+    ///
+    /// Texts over a limit fail with [`SmsRuError::Validation`] instead of SMS.RU's
+    /// `MessageTooLong` (205).
+    pub fn message_limits(mut self, limits: MessageLimits) -> Self {
+        self.message_limits = Some(limits);
+        self
+    }
+
+    /// Check `SendOptions.from` against the approved senders from `my/senders` before sending.
+    ///
+    /// The list is fetched on first use and cached for `ttl`; it is dropped early when SMS.RU
+    /// still answers `SenderNotEnabled` (204) or `SenderMustMatchBrand` (221). A sender that was
+    /// approved after the last fetch is refused until the cache expires.
+    pub fn approved_senders_ttl(mut self, ttl: Duration) -> Self {
+        self.approved_senders = Some(senders::ApprovedSenders::new(ttl));
+        self
+    }
+
+    /// Track today's `my/limit` allowance locally and fail fast before exceeding it.
+    ///
+    /// See [`DailyLimitGuard`] for how the counter is seeded and refreshed.
+    pub fn daily_limit_guard(mut self, guard: DailyLimitGuard) -> Self {
+        self.daily_limit = Some(guard);
+        self
+    }
+
+    /// Store responses of [`SmsRuClient::send_sms_idempotent`] in `store`.
+    ///
+    /// Defaults to a fresh [`InMemoryIdempotencyStore`] per built client.
+    pub fn idempotency_store(mut self, store: Arc<dyn IdempotencyStore>) -> Self {
+        self.idempotency = idempotency::Idempotency::new(store, self.idempotency.ttl);
+        self
+    }
+
+    /// How long idempotent send results are remembered (defaults to 24 hours).
+    pub fn idempotency_ttl(mut self, ttl: Duration) -> Self {
+        self.idempotency.ttl = ttl;
+        self
+    }
+
+    /// Make `send_sms` return [`SmsRuError::RecipientsFailed`] when any recipient is rejected.
+    ///
+    /// By default a top-level `OK` response is returned as-is, even if some recipients failed.
+    pub fn fail_on_recipient_errors(mut self, enabled: bool) -> Self {
+        self.fail_on_recipient_errors = enabled;
+        self
+    }
+
+    /// Normalize `send_sms` recipients to E.164 and drop duplicates before sending.
+    ///
+    /// Numbers without a country code are parsed in `default_region`; digits-only numbers that
+    /// are not valid there are read as international, the way SMS.RU reads them. An invalid
+    /// input fails the call with [`SmsRuError::Validation`]. Responses are keyed by the
+    /// original inputs, see [`NormalizedSendSms::restore_keys`](crate::NormalizedSendSms::restore_keys).
+    pub fn normalize_recipients(mut self, default_region: Option<country::Id>) -> Self {
+        self.normalization = Some(Normalization { default_region });
+        self
+    }
+
     /// Build a [`SmsRuClient`].
     pub fn build(self) -> Result<SmsRuClient, SmsRuError> {
         let mut builder = reqwest::Client::builder();
         if let Some(timeout) = self.timeout {
             builder = builder.timeout(timeout);
         }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.read_timeout {
+            builder = builder.read_timeout(timeout);
+        }
         if let Some(user_agent) = self.user_agent {
             builder = builder.user_agent(user_agent);
         }
+        if let Some(url) = self.proxy {
+            let mut proxy =
+                reqwest::Proxy::all(url).map_err(|err| SmsRuError::Transport(Box::new(err)))?;
+            if let Some((username, password)) = &self.proxy_basic_auth {
+                proxy = proxy.basic_auth(username, password);
+            }
+            builder = builder.proxy(proxy);
+        }
+        if self.only_root_certificates_pem.is_empty() {
+            builder = builder.tls_certs_merge(parse_pem_bundles(&self.root_certificates_pem)?);
+        } else {
+            let roots = parse_pem_bundles(&self.only_root_certificates_pem)?;
+            if roots.is_empty() {
+                return Err(SmsRuError::Transport(
+                    "no certificates found in the only-roots PEM bundle".into(),
+                ));
+            }
+            builder = builder.tls_certs_only(roots);
+        }
 
         let client = builder
             .build()
             .map_err(|err| SmsRuError::Transport(Box::new(err)))?;
+        let mirrors = (!self.fallback_base_urls.is_empty())
+            .then(|| mirrors::Mirrors::new(self.base_url, self.fallback_base_urls));
 
         Ok(SmsRuClient {
             auth: self.auth,
@@ -363,11 +686,32 @@ impl SmsRuClientBuilder {
             callback_add_endpoint: self.callback_add_endpoint,
             callback_del_endpoint: self.callback_del_endpoint,
             callback_get_endpoint: self.callback_get_endpoint,
+            spend_guard: self.spend_guard,
+            recipient_policy: self.recipient_policy,
+            abuse_guard: self.abuse_guard,
+            approved_senders: self.approved_senders,
+            message_limits: self.message_limits,
+            daily_limit: self.daily_limit,
+            idempotency: self.idempotency,
+            fail_on_recipient_errors: self.fail_on_recipient_errors,
+            normalization: self.normalization,
+            mirrors,
             http: Arc::new(ReqwestTransport { client }),
         })
     }
 }
 
+fn parse_pem_bundles(bundles: &[Vec<u8>]) -> Result<Vec<reqwest::Certificate>, SmsRuError> {
+    let mut certificates = Vec::new();
+    for pem in bundles {
+        certificates.extend(
+            reqwest::Certificate::from_pem_bundle(pem)
+                .map_err(|err| SmsRuError::Transport(Box::new(err)))?,
+        );
+    }
+    Ok(certificates)
+}
+
 #[derive(Clone)]
 /// High-level SMS.RU client.
 ///
@@ -409,6 +753,16 @@ pub struct SmsRuClient {
     callback_add_endpoint: String,
     callback_del_endpoint: String,
     callback_get_endpoint: String,
+    spend_guard: Option<SpendGuard>,
+    recipient_policy: Option<RecipientPolicy>,
+    abuse_guard: Option<AbuseGuard>,
+    approved_senders: Option<senders::ApprovedSenders>,
+    message_limits: Option<MessageLimits>,
+    daily_limit: Option<DailyLimitGuard>,
+    idempotency: idempotency::Idempotency,
+    fail_on_recipient_errors: bool,
+    normalization: Option<Normalization>,
+    mirrors: Option<mirrors::Mirrors>,
     http: Arc<dyn HttpTransport>,
 }
 
@@ -435,6 +789,16 @@ impl SmsRuClient {
             callback_add_endpoint: DEFAULT_CALLBACK_ADD_ENDPOINT.to_owned(),
             callback_del_endpoint: DEFAULT_CALLBACK_DEL_ENDPOINT.to_owned(),
             callback_get_endpoint: DEFAULT_CALLBACK_GET_ENDPOINT.to_owned(),
+            spend_guard: None,
+            recipient_policy: None,
+            abuse_guard: None,
+            approved_senders: None,
+            message_limits: None,
+            daily_limit: None,
+            idempotency: idempotency::Idempotency::default(),
+            fail_on_recipient_errors: false,
+            normalization: None,
+            mirrors: None,
             http: Arc::new(ReqwestTransport {
                 client: reqwest::Client::new(),
             }),
@@ -451,27 +815,299 @@ impl SmsRuClient {
     /// Constraints:
     /// - The request must have `SendOptions.json = JsonMode::Json` (plain-text responses are
     ///   currently not supported).
+    /// - A scheduled `SendOptions.time` must pass [`SendOptions::validate_schedule`].
     ///
     /// Errors:
-    /// - Returns [`SmsRuError::Validation`] for invalid domain values,
+    /// - Returns [`SmsRuError::Validation`] for invalid domain values (including recipients
+    ///   that fail [`SmsRuClientBuilder::normalize_recipients`]),
     /// - [`SmsRuError::HttpStatus`] for non-2xx HTTP responses,
-    /// - [`SmsRuError::Api`] when SMS.RU returns a top-level `ERROR`.
+    /// - [`SmsRuError::Api`] when SMS.RU returns a top-level `ERROR`,
+    /// - [`SmsRuError::RecipientPolicy`] when a configured [`RecipientPolicy`] rejects a recipient,
+    /// - [`SmsRuError::Abuse`] when a configured [`AbuseGuard`] blocks a recipient,
+    /// - [`SmsRuError::SpendGuard`] when a configured [`SpendGuard`] refuses the send,
+    /// - [`SmsRuError::DailyLimit`] when a configured [`DailyLimitGuard`] has no room left,
+    /// - [`SmsRuError::RecipientsFailed`] when any recipient was rejected and
+    ///   [`SmsRuClientBuilder::fail_on_recipient_errors`] is enabled.
     pub async fn send_sms(&self, request: SendSms) -> Result<SendSmsResponse, SmsRuError> {
+        let response = self.send_sms_lenient(request).await?;
+        self.check_recipients(response)
+    }
+
+    async fn send_sms_lenient(&self, request: SendSms) -> Result<SendSmsResponse, SmsRuError> {
+        let Some(normalized) = self.normalize(&request)? else {
+            return self.send_sms_limited(request).await;
+        };
+        let response = self.send_sms_limited(normalized.request().clone()).await?;
+        Ok(normalized.restore_keys(response))
+    }
+
+    /// Apply [`SmsRuClientBuilder::normalize_recipients`], or `None` if it is off.
+    fn normalize(&self, request: &SendSms) -> Result<Option<NormalizedSendSms>, SmsRuError> {
+        let Some(normalization) = self.normalization else {
+            return Ok(None);
+        };
+        let normalized = request.normalize(normalization.default_region)?;
+        if let Some((_, err)) = normalized.invalid().first() {
+            return Err(err.clone().into());
+        }
+        Ok(Some(normalized))
+    }
+
+    async fn send_sms_limited(&self, request: SendSms) -> Result<SendSmsResponse, SmsRuError> {
+        let Some(guard) = self.daily_limit.as_ref() else {
+            return self.send_sms_unlimited(request).await;
+        };
+
+        self.refresh_daily_limit(guard).await?;
+        let reserved = daily_limit::recipient_count(&request);
+        guard.reserve(reserved).map_err(SmsRuError::DailyLimit)?;
+        self.send_sms_reserved(guard, reserved, request).await
+    }
+
+    /// Send an SMS at most once per `key`.
+    ///
+    /// The key is marked [`IdempotencyEntry::Started`] with [`IdempotencyStore::try_start`]
+    /// (see [`SmsRuClientBuilder::idempotency_store`]), the request is sent with
+    /// [`SmsRuClient::send_sms`], and the response is stored for the configured TTL. If a
+    /// response for `key` is already stored, it is returned without calling SMS.RU.
+    ///
+    /// Failures that certainly did not send (local checks, SMS.RU errors) clear the mark, so a
+    /// retry sends again. After a failure SMS.RU may still have accepted (e.g. a timeout) the
+    /// mark stays, and retries fail with [`SmsRuError::IdempotencyOutcomeUnknown`] until it
+    /// expires or is removed from the store.
+    ///
+    /// Errors:
+    /// - Everything [`SmsRuClient::send_sms`] returns,
+    /// - [`SmsRuError::IdempotencyKeyInFlight`] when a send with the same key is still running on
+    ///   a client sharing this configuration,
+    /// - [`SmsRuError::IdempotencyOutcomeUnknown`] when an earlier send with the key failed
+    ///   ambiguously, or is still running elsewhere with a shared store,
+    /// - [`SmsRuError::IdempotencyKeyReused`] when the key was used for a different request.
+    pub async fn send_sms_idempotent(
+        &self,
+        key: IdempotencyKey,
+        request: SendSms,
+    ) -> Result<SendSmsResponse, SmsRuError> {
+        let Some(_in_flight) = self.idempotency.begin(&key) else {
+            return Err(SmsRuError::IdempotencyKeyInFlight(key));
+        };
+        let store = &self.idempotency.store;
+        let fingerprint = crate::transport::send_sms_fingerprint(&request);
+        match store.try_start(key.clone(), fingerprint.clone(), self.idempotency.ttl) {
+            Ok(()) => {}
+            Err(entry) if entry.fingerprint() != fingerprint => {
+                return Err(SmsRuError::IdempotencyKeyReused(key));
+            }
+            Err(IdempotencyEntry::Completed { response, .. }) => {
+                return self.check_recipients(response);
+            }
+            Err(IdempotencyEntry::Started { .. }) => {
+                return Err(SmsRuError::IdempotencyOutcomeUnknown(key));
+            }
+        }
+
+        let response = match self.send_sms_lenient(request).await {
+            Ok(response) => response,
+            Err(err) => {
+                if !err.may_have_been_accepted() {
+                    store.remove(&key);
+                }
+                return Err(err);
+            }
+        };
+        // Store partial failures too: retrying them would re-send to accepted recipients.
+        store.insert(
+            key,
+            IdempotencyEntry::Completed {
+                fingerprint,
+                response: response.clone(),
+            },
+            self.idempotency.ttl,
+        );
+        self.check_recipients(response)
+    }
+
+    /// Send as many recipients as today's allowance permits and hand back the rest.
+    ///
+    /// Without a configured [`DailyLimitGuard`] the whole request is sent. Recipients that do
+    /// not fit are returned in [`DailyLimitedSend::deferred`] and are not sent.
+    ///
+    /// Rejected recipients are reported in [`DailyLimitedSend::response`] even with
+    /// [`SmsRuClientBuilder::fail_on_recipient_errors`], so the deferred part is never lost.
+    ///
+    /// With [`SmsRuClientBuilder::normalize_recipients`], recipients are normalized and
+    /// deduplicated before the allowance is applied; the response is keyed by the original
+    /// inputs, and deferred recipients are in canonical form.
+    pub async fn send_sms_within_daily_limit(
+        &self,
+        request: SendSms,
+    ) -> Result<DailyLimitedSend, SmsRuError> {
+        let normalized = self.normalize(&request)?;
+        let request = match &normalized {
+            Some(normalized) => normalized.request().clone(),
+            None => request,
+        };
+        let restore_keys = |response| match &normalized {
+            Some(normalized) => normalized.restore_keys(response),
+            None => response,
+        };
+
+        let Some(guard) = self.daily_limit.as_ref() else {
+            let response = self.send_sms_unlimited(request).await?;
+            return Ok(DailyLimitedSend {
+                response: Some(restore_keys(response)),
+                deferred: None,
+            });
+        };
+
+        self.refresh_daily_limit(guard).await?;
+        let granted = guard.reserve_up_to(daily_limit::recipient_count(&request));
+        let (head, deferred) = request.split_at(granted as usize);
+        let response = match head {
+            Some(head) => Some(restore_keys(
+                self.send_sms_reserved(guard, granted, head).await?,
+            )),
+            None => None,
+        };
+
+        Ok(DailyLimitedSend { response, deferred })
+    }
+
+    /// Send one logical request as one [`SmsRuClient::send_sms`] call per routed sender.
+    ///
+    /// The parts from [`SenderRouter::split`] are sent in order and their responses merged.
+    /// Rejected recipients are reported in [`RoutedSend::response`] even with
+    /// [`SmsRuClientBuilder::fail_on_recipient_errors`].
+    ///
+    /// If a part fails, sending stops and the result holds the merged response so far, the
+    /// error, and the parts that were not sent. A part that failed with an error after which
+    /// SMS.RU may still have accepted it (a transport error, an unreadable response) is
+    /// reported as [`RoutedSend::uncertain`] instead of unsent. An error is returned only if the
+    /// first part was refused locally or by SMS.RU, in which case nothing was sent.
+    pub async fn send_sms_routed(
+        &self,
+        router: &SenderRouter,
+        request: SendSms,
+    ) -> Result<RoutedSend, SmsRuError> {
+        let mut routed = RoutedSend {
+            response: None,
+            uncertain: None,
+            unsent: Vec::new(),
+            error: None,
+        };
+        let mut parts = router.split(&request).into_iter();
+        while let Some(part) = parts.next() {
+            match self.send_sms_lenient(part.clone()).await {
+                Ok(response) => routed.merge(response),
+                Err(err) => {
+                    if err.may_have_been_accepted() {
+                        routed.uncertain = Some(part);
+                    } else if routed.response.is_none() {
+                        return Err(err);
+                    } else {
+                        routed.unsent.push(part);
+                    }
+                    routed.error = Some(err);
+                    routed.unsent.extend(parts);
+                    break;
+                }
+            }
+        }
+        Ok(routed)
+    }
+
+    async fn post_form(
+        &self,
+        endpoint: &str,
+        params: Vec<(String, String)>,
+    ) -> Result<HttpResponse, SmsRuError> {
+        match self.mirrors.as_ref() {
+            Some(mirrors) => mirrors.post_form(&*self.http, endpoint, params).await,
+            None => self
+                .http
+                .post_form(endpoint, params)
+                .await
+                .map_err(SmsRuError::Transport),
+        }
+    }
+
+    fn check_recipients(&self, response: SendSmsResponse) -> Result<SendSmsResponse, SmsRuError> {
+        if self.fail_on_recipient_errors && response.has_failures() {
+            return Err(SmsRuError::RecipientsFailed(Box::new(response)));
+        }
+        Ok(response)
+    }
+
+    async fn refresh_daily_limit(&self, guard: &DailyLimitGuard) -> Result<(), SmsRuError> {
+        if guard.needs_refresh() {
+            guard.seed(&self.get_limit_usage().await?);
+        }
+        Ok(())
+    }
+
+    async fn send_sms_reserved(
+        &self,
+        guard: &DailyLimitGuard,
+        reserved: u32,
+        request: SendSms,
+    ) -> Result<SendSmsResponse, SmsRuError> {
+        let result = self.send_sms_unlimited(request).await;
+        match &result {
+            Ok(response) => guard.settle(reserved, response),
+            Err(SmsRuError::Api { status_code, .. })
+                if status_code.known_kind() == Some(KnownStatusCode::DailyLimitExceeded) =>
+            {
+                guard.exhaust(reserved);
+            }
+            Err(_) => guard.release(reserved),
+        }
+        result
+    }
+
+    async fn send_sms_unlimited(&self, request: SendSms) -> Result<SendSmsResponse, SmsRuError> {
         if send_request_options(&request).json != crate::domain::JsonMode::Json {
             return Err(SmsRuError::UnsupportedResponseFormat(
                 "plain-text responses are not supported; set SendOptions.json = JsonMode::Json",
             ));
         }
+        send_request_options(&request).validate_schedule(SystemTime::now())?;
+
+        if let Some(limits) = self.message_limits.as_ref() {
+            match &request {
+                SendSms::ToMany(to_many) => check_message_limits(limits, [to_many.msg()])?,
+                SendSms::PerRecipient(per_recipient) => {
+                    check_message_limits(limits, per_recipient.messages().values())?
+                }
+            }
+        }
+
+        if let Some(policy) = self.recipient_policy.as_ref() {
+            policy
+                .check_send(&request)
+                .map_err(SmsRuError::RecipientPolicy)?;
+        }
+
+        if let (Some(approved), Some(from)) = (
+            self.approved_senders.as_ref(),
+            send_request_options(&request).from.as_ref(),
+        ) {
+            self.enforce_approved_sender(approved, from).await?;
+        }
+
+        if let Some(guard) = self.spend_guard.as_ref() {
+            self.enforce_spend_guard(guard, &request).await?;
+        }
+
+        // Last, because a passing check counts against the velocity limits.
+        if let Some(guard) = self.abuse_guard.as_ref() {
+            guard.check_send(&request).map_err(SmsRuError::Abuse)?;
+        }
 
         let mut params = Vec::<(String, String)>::new();
         self.auth.push_form_params(&mut params);
         params.extend(crate::transport::encode_send_sms_form(&request));
 
-        let response = self
-            .http
-            .post_form(&self.send_endpoint, params)
-            .await
-            .map_err(SmsRuError::Transport)?;
+        let response = self.post_form(&self.send_endpoint, params).await?;
 
         if !(200..=299).contains(&response.status) {
             let body = if response.body.trim().is_empty() {
@@ -489,6 +1125,14 @@ impl SmsRuClient {
             .map_err(|err| SmsRuError::Parse(Box::new(err)))?;
 
         if parsed.status != Status::Ok {
+            if let Some(approved) = self.approved_senders.as_ref() {
+                if matches!(
+                    parsed.status_code.known_kind(),
+                    Some(KnownStatusCode::SenderNotEnabled | KnownStatusCode::SenderMustMatchBrand)
+                ) {
+                    approved.invalidate();
+                }
+            }
             return Err(SmsRuError::Api {
                 status_code: parsed.status_code,
                 status_text: parsed.status_text,
@@ -507,7 +1151,8 @@ impl SmsRuClient {
     /// Errors:
     /// - Returns [`SmsRuError::Validation`] for invalid domain values,
     /// - [`SmsRuError::HttpStatus`] for non-2xx HTTP responses,
-    /// - [`SmsRuError::Api`] when SMS.RU returns a top-level `ERROR`.
+    /// - [`SmsRuError::Api`] when SMS.RU returns a top-level `ERROR`,
+    /// - [`SmsRuError::RecipientPolicy`] when a configured [`RecipientPolicy`] rejects a recipient.
     pub async fn check_cost(&self, request: CheckCost) -> Result<CheckCostResponse, SmsRuError> {
         if cost_request_options(&request).json != crate::domain::JsonMode::Json {
             return Err(SmsRuError::UnsupportedResponseFormat(
@@ -515,15 +1160,26 @@ impl SmsRuClient {
             ));
         }
 
+        if let Some(limits) = self.message_limits.as_ref() {
+            match &request {
+                CheckCost::ToMany(to_many) => check_message_limits(limits, [to_many.msg()])?,
+                CheckCost::PerRecipient(per_recipient) => {
+                    check_message_limits(limits, per_recipient.messages().values())?
+                }
+            }
+        }
+
+        if let Some(policy) = self.recipient_policy.as_ref() {
+            policy
+                .check_cost(&request)
+                .map_err(SmsRuError::RecipientPolicy)?;
+        }
+
         let mut params = Vec::<(String, String)>::new();
         self.auth.push_form_params(&mut params);
         params.extend(crate::transport::encode_check_cost_form(&request));
 
-        let response = self
-            .http
-            .post_form(&self.cost_endpoint, params)
-            .await
-            .map_err(SmsRuError::Transport)?;
+        let response = self.post_form(&self.cost_endpoint, params).await?;
 
         if !(200..=299).contains(&response.status) {
             let body = if response.body.trim().is_empty() {
@@ -564,11 +1220,7 @@ impl SmsRuClient {
         self.auth.push_form_params(&mut params);
         params.extend(crate::transport::encode_check_status_form(&request));
 
-        let response = self
-            .http
-            .post_form(&self.status_endpoint, params)
-            .await
-            .map_err(SmsRuError::Transport)?;
+        let response = self.post_form(&self.status_endpoint, params).await?;
 
         if !(200..=299).contains(&response.status) {
             let body = if response.body.trim().is_empty() {
@@ -600,6 +1252,9 @@ impl SmsRuClient {
     /// Constraints:
     /// - The request must have `StartCallAuthOptions.json = JsonMode::Json` (plain-text responses
     ///   are currently not supported).
+    ///
+    /// Errors:
+    /// - [`SmsRuError::Abuse`] when a configured [`AbuseGuard`] blocks the number.
     pub async fn start_call_auth(
         &self,
         request: StartCallAuth,
@@ -610,15 +1265,17 @@ impl SmsRuClient {
             ));
         }
 
+        if let Some(guard) = self.abuse_guard.as_ref() {
+            guard
+                .check_call_auth(request.phone())
+                .map_err(SmsRuError::Abuse)?;
+        }
+
         let mut params = Vec::<(String, String)>::new();
         self.auth.push_form_params(&mut params);
         params.extend(crate::transport::encode_start_call_auth_form(&request));
 
-        let response = self
-            .http
-            .post_form(&self.callcheck_add_endpoint, params)
-            .await
-            .map_err(SmsRuError::Transport)?;
+        let response = self.post_form(&self.callcheck_add_endpoint, params).await?;
 
         if !(200..=299).contains(&response.status) {
             let body = if response.body.trim().is_empty() {
@@ -667,10 +1324,8 @@ impl SmsRuClient {
         ));
 
         let response = self
-            .http
             .post_form(&self.callcheck_status_endpoint, params)
-            .await
-            .map_err(SmsRuError::Transport)?;
+            .await?;
 
         if !(200..=299).contains(&response.status) {
             let body = if response.body.trim().is_empty() {
@@ -703,11 +1358,7 @@ impl SmsRuClient {
         self.auth.push_form_params(&mut params);
         params.extend(crate::transport::encode_auth_check_form());
 
-        let response = self
-            .http
-            .post_form(&self.auth_check_endpoint, params)
-            .await
-            .map_err(SmsRuError::Transport)?;
+        let response = self.post_form(&self.auth_check_endpoint, params).await?;
 
         if !(200..=299).contains(&response.status) {
             let body = if response.body.trim().is_empty() {
@@ -740,11 +1391,7 @@ impl SmsRuClient {
         self.auth.push_form_params(&mut params);
         params.extend(crate::transport::encode_get_balance_form());
 
-        let response = self
-            .http
-            .post_form(&self.my_balance_endpoint, params)
-            .await
-            .map_err(SmsRuError::Transport)?;
+        let response = self.post_form(&self.my_balance_endpoint, params).await?;
 
         if !(200..=299).contains(&response.status) {
             let body = if response.body.trim().is_empty() {
@@ -777,11 +1424,7 @@ impl SmsRuClient {
         self.auth.push_form_params(&mut params);
         params.extend(crate::transport::encode_get_free_usage_form());
 
-        let response = self
-            .http
-            .post_form(&self.my_free_endpoint, params)
-            .await
-            .map_err(SmsRuError::Transport)?;
+        let response = self.post_form(&self.my_free_endpoint, params).await?;
 
         if !(200..=299).contains(&response.status) {
             let body = if response.body.trim().is_empty() {
@@ -814,11 +1457,7 @@ impl SmsRuClient {
         self.auth.push_form_params(&mut params);
         params.extend(crate::transport::encode_get_limit_usage_form());
 
-        let response = self
-            .http
-            .post_form(&self.my_limit_endpoint, params)
-            .await
-            .map_err(SmsRuError::Transport)?;
+        let response = self.post_form(&self.my_limit_endpoint, params).await?;
 
         if !(200..=299).contains(&response.status) {
             let body = if response.body.trim().is_empty() {
@@ -851,11 +1490,7 @@ impl SmsRuClient {
         self.auth.push_form_params(&mut params);
         params.extend(crate::transport::encode_get_senders_form());
 
-        let response = self
-            .http
-            .post_form(&self.my_senders_endpoint, params)
-            .await
-            .map_err(SmsRuError::Transport)?;
+        let response = self.post_form(&self.my_senders_endpoint, params).await?;
 
         if !(200..=299).contains(&response.status) {
             let body = if response.body.trim().is_empty() {
@@ -891,11 +1526,7 @@ impl SmsRuClient {
         self.auth.push_form_params(&mut params);
         params.extend(crate::transport::encode_add_stoplist_form(&request));
 
-        let response = self
-            .http
-            .post_form(&self.stoplist_add_endpoint, params)
-            .await
-            .map_err(SmsRuError::Transport)?;
+        let response = self.post_form(&self.stoplist_add_endpoint, params).await?;
 
         if !(200..=299).contains(&response.status) {
             let body = if response.body.trim().is_empty() {
@@ -931,11 +1562,7 @@ impl SmsRuClient {
         self.auth.push_form_params(&mut params);
         params.extend(crate::transport::encode_remove_stoplist_form(&request));
 
-        let response = self
-            .http
-            .post_form(&self.stoplist_del_endpoint, params)
-            .await
-            .map_err(SmsRuError::Transport)?;
+        let response = self.post_form(&self.stoplist_del_endpoint, params).await?;
 
         if !(200..=299).contains(&response.status) {
             let body = if response.body.trim().is_empty() {
@@ -968,11 +1595,7 @@ impl SmsRuClient {
         self.auth.push_form_params(&mut params);
         params.extend(crate::transport::encode_get_stoplist_form());
 
-        let response = self
-            .http
-            .post_form(&self.stoplist_get_endpoint, params)
-            .await
-            .map_err(SmsRuError::Transport)?;
+        let response = self.post_form(&self.stoplist_get_endpoint, params).await?;
 
         if !(200..=299).contains(&response.status) {
             let body = if response.body.trim().is_empty() {
@@ -1008,11 +1631,7 @@ impl SmsRuClient {
         self.auth.push_form_params(&mut params);
         params.extend(crate::transport::encode_add_callback_form(&request));
 
-        let response = self
-            .http
-            .post_form(&self.callback_add_endpoint, params)
-            .await
-            .map_err(SmsRuError::Transport)?;
+        let response = self.post_form(&self.callback_add_endpoint, params).await?;
 
         if !(200..=299).contains(&response.status) {
             let body = if response.body.trim().is_empty() {
@@ -1048,11 +1667,7 @@ impl SmsRuClient {
         self.auth.push_form_params(&mut params);
         params.extend(crate::transport::encode_remove_callback_form(&request));
 
-        let response = self
-            .http
-            .post_form(&self.callback_del_endpoint, params)
-            .await
-            .map_err(SmsRuError::Transport)?;
+        let response = self.post_form(&self.callback_del_endpoint, params).await?;
 
         if !(200..=299).contains(&response.status) {
             let body = if response.body.trim().is_empty() {
@@ -1085,11 +1700,7 @@ impl SmsRuClient {
         self.auth.push_form_params(&mut params);
         params.extend(crate::transport::encode_get_callbacks_form());
 
-        let response = self
-            .http
-            .post_form(&self.callback_get_endpoint, params)
-            .await
-            .map_err(SmsRuError::Transport)?;
+        let response = self.post_form(&self.callback_get_endpoint, params).await?;
 
         if !(200..=299).contains(&response.status) {
             let body = if response.body.trim().is_empty() {
@@ -1115,6 +1726,99 @@ impl SmsRuClient {
 
         Ok(parsed)
     }
+
+    async fn enforce_approved_sender(
+        &self,
+        approved: &senders::ApprovedSenders,
+        sender: &SenderId,
+    ) -> Result<(), SmsRuError> {
+        let known = match approved.contains(sender) {
+            Some(known) => known,
+            None => {
+                approved.store(&self.get_senders().await?.senders);
+                approved.contains(sender).unwrap_or(false)
+            }
+        };
+        if known {
+            Ok(())
+        } else {
+            Err(SmsRuError::SenderNotApproved(sender.clone()))
+        }
+    }
+
+    async fn enforce_spend_guard(
+        &self,
+        guard: &SpendGuard,
+        request: &SendSms,
+    ) -> Result<(), SmsRuError> {
+        let cost = self.check_cost(CheckCost::from(request)).await?;
+        let balance = if guard.requires_balance() {
+            self.get_balance().await?.balance
+        } else {
+            None
+        };
+
+        guard
+            .check(&cost, balance.as_deref())
+            .map_err(SmsRuError::SpendGuard)
+    }
+
+    /// Make sure every `desired` callback URL is configured, optionally pruning the rest.
+    ///
+    /// URLs are compared by [`CallbackUrl::normalized`], so a trailing slash or an explicit
+    /// default port does not cause a duplicate registration. Calling this repeatedly with the
+    /// same input only talks to `callback/get` once the account is in sync.
+    pub async fn ensure_callbacks(
+        &self,
+        desired: &[CallbackUrl],
+        policy: CallbackSyncPolicy,
+    ) -> Result<CallbackSyncReport, SmsRuError> {
+        let mut callback = self.get_callbacks().await?.callback;
+
+        let mut wanted = BTreeMap::<String, &CallbackUrl>::new();
+        for url in desired {
+            wanted.entry(url.normalized()).or_insert(url);
+        }
+
+        let configured = callback
+            .iter()
+            .map(CallbackUrl::normalized)
+            .collect::<BTreeSet<_>>();
+
+        let mut added = Vec::new();
+        for (key, url) in &wanted {
+            if configured.contains(key) {
+                continue;
+            }
+            callback = self
+                .add_callback(AddCallback::new((*url).clone()))
+                .await?
+                .callback;
+            added.push((*url).clone());
+        }
+
+        let mut removed = Vec::new();
+        if policy == CallbackSyncPolicy::AddAndPrune {
+            let stale = callback
+                .iter()
+                .filter(|url| !wanted.contains_key(&url.normalized()))
+                .cloned()
+                .collect::<Vec<_>>();
+            for url in stale {
+                callback = self
+                    .remove_callback(RemoveCallback::new(url.clone()))
+                    .await?
+                    .callback;
+                removed.push(url);
+            }
+        }
+
+        Ok(CallbackSyncReport {
+            added,
+            removed,
+            callback,
+        })
+    }
 }
 
 fn send_request_options(request: &SendSms) -> &SendOptions {
@@ -1124,6 +1828,15 @@ fn send_request_options(request: &SendSms) -> &SendOptions {
     }
 }
 
+fn check_message_limits<'a>(
+    limits: &MessageLimits,
+    texts: impl IntoIterator<Item = &'a MessageText>,
+) -> Result<(), ValidationError> {
+    texts
+        .into_iter()
+        .try_for_each(|text| limits.check(text.as_str()).map(|_| ()))
+}
+
 fn cost_request_options(request: &CheckCost) -> &CheckCostOptions {
     match request {
         CheckCost::ToMany(to_many) => to_many.options(),
@@ -1133,8 +1846,6 @@ fn cost_request_options(request: &CheckCost) -> &CheckCostOptions {
 
 #[cfg(test)]
 mod tests {
-    use std::sync::Mutex;
-
     use crate::domain::{
         AddCallback, AddStoplistEntry, CallCheckId, CallbackUrl, CheckCallAuthStatus,
         CheckCallAuthStatusOptions, CheckCost, CheckCostOptions, CheckStatus, MessageText,
@@ -1142,87 +1853,9 @@ mod tests {
         StartCallAuth, StartCallAuthOptions, StatusCode, StoplistText,
     };
 
+    use super::test_support::{FakeTransport, assert_param, make_client};
     use super::*;
 
-    #[derive(Debug, Clone)]
-    struct FakeTransport {
-        state: Arc<Mutex<FakeTransportState>>,
-    }
-
-    #[derive(Debug)]
-    struct FakeTransportState {
-        last_url: Option<String>,
-        last_params: Vec<(String, String)>,
-        response_status: u16,
-        response_body: String,
-    }
-
-    impl FakeTransport {
-        fn new(response_status: u16, response_body: impl Into<String>) -> Self {
-            Self {
-                state: Arc::new(Mutex::new(FakeTransportState {
-                    last_url: None,
-                    last_params: Vec::new(),
-                    response_status,
-                    response_body: response_body.into(),
-                })),
-            }
-        }
-
-        fn last_request(&self) -> (Option<String>, Vec<(String, String)>) {
-            let state = self.state.lock().unwrap();
-            (state.last_url.clone(), state.last_params.clone())
-        }
-    }
-
-    impl HttpTransport for FakeTransport {
-        fn post_form<'a>(
-            &'a self,
-            url: &'a str,
-            params: Vec<(String, String)>,
-        ) -> BoxFuture<'a, Result<HttpResponse, Box<dyn StdError + Send + Sync>>> {
-            Box::pin(async move {
-                let (status, body) = {
-                    let mut state = self.state.lock().unwrap();
-                    state.last_url = Some(url.to_owned());
-                    state.last_params = params;
-                    (state.response_status, state.response_body.clone())
-                };
-                Ok(HttpResponse { status, body })
-            })
-        }
-    }
-
-    fn assert_param(params: &[(String, String)], key: &str, value: &str) {
-        assert!(
-            params.iter().any(|(k, v)| k == key && v == value),
-            "missing param {key}={value}; got: {params:?}"
-        );
-    }
-
-    fn make_client(auth: Auth, transport: FakeTransport) -> SmsRuClient {
-        SmsRuClient {
-            auth,
-            send_endpoint: "https://example.invalid/sms/send".to_owned(),
-            cost_endpoint: "https://example.invalid/sms/cost".to_owned(),
-            status_endpoint: "https://example.invalid/sms/status".to_owned(),
-            callcheck_add_endpoint: "https://example.invalid/callcheck/add".to_owned(),
-            callcheck_status_endpoint: "https://example.invalid/callcheck/status".to_owned(),
-            auth_check_endpoint: "https://example.invalid/auth/check".to_owned(),
-            my_balance_endpoint: "https://example.invalid/my/balance".to_owned(),
-            my_free_endpoint: "https://example.invalid/my/free".to_owned(),
-            my_limit_endpoint: "https://example.invalid/my/limit".to_owned(),
-            my_senders_endpoint: "https://example.invalid/my/senders".to_owned(),
-            stoplist_add_endpoint: "https://example.invalid/stoplist/add".to_owned(),
-            stoplist_del_endpoint: "https://example.invalid/stoplist/del".to_owned(),
-            stoplist_get_endpoint: "https://example.invalid/stoplist/get".to_owned(),
-            callback_add_endpoint: "https://example.invalid/callback/add".to_owned(),
-            callback_del_endpoint: "https://example.invalid/callback/del".to_owned(),
-            callback_get_endpoint: "https://example.invalid/callback/get".to_owned(),
-            http: Arc::new(transport),
-        }
-    }
-
     #[tokio::test]
     async fn send_sms_includes_api_id_and_parses_ok_response() {
         let json = r#"
@@ -1397,13 +2030,33 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn send_sms_maps_invalid_json_to_parse_error() {
-        let transport = FakeTransport::new(200, "{ not json }");
-        let client = make_client(Auth::api_id("test_key").unwrap(), transport);
+    async fn send_sms_rejects_past_schedule_before_sending() {
+        let transport = FakeTransport::new(200, "{}");
+        let client = make_client(Auth::api_id("test_key").unwrap(), transport.clone());
 
-        let phone = RawPhoneNumber::new("79251234567").unwrap();
         let request = SendSms::to_many(
-            vec![phone],
+            vec![RawPhoneNumber::new("79251234567").unwrap()],
+            MessageText::new("hello").unwrap(),
+            SendOptions::default().schedule_at(crate::domain::UnixTimestamp::new(1)),
+        )
+        .unwrap();
+
+        let err = client.send_sms(request).await.unwrap_err();
+        assert!(matches!(
+            err,
+            SmsRuError::Validation(ValidationError::TimeInPast { .. })
+        ));
+        assert!(transport.requested_urls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn send_sms_maps_invalid_json_to_parse_error() {
+        let transport = FakeTransport::new(200, "{ not json }");
+        let client = make_client(Auth::api_id("test_key").unwrap(), transport);
+
+        let phone = RawPhoneNumber::new("79251234567").unwrap();
+        let request = SendSms::to_many(
+            vec![phone],
             MessageText::new("hello").unwrap(),
             SendOptions::default(),
         )
@@ -2063,6 +2716,774 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn spend_guard_checks_cost_and_balance_before_sending() {
+        let transport = FakeTransport::sequence(vec![
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"total_cost":"2.00","total_sms":2,"sms":{"79251234567":{"status":"OK","status_code":100,"cost":"2.00","sms":2}}}"#,
+            ),
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"balance":"100.00"}"#,
+            ),
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"sms":{"79251234567":{"status":"OK","status_code":100,"sms_id":"1"}}}"#,
+            ),
+        ]);
+        let mut client = make_client(Auth::api_id("test_key").unwrap(), transport.clone());
+        client.spend_guard = Some(SpendGuard::new().max_total_cost(5.0).max_balance_share(0.5));
+
+        let request = SendSms::to_many(
+            vec![RawPhoneNumber::new("79251234567").unwrap()],
+            MessageText::new("hello").unwrap(),
+            SendOptions::default(),
+        )
+        .unwrap();
+        client.send_sms(request).await.unwrap();
+
+        assert_eq!(
+            transport.requested_urls(),
+            vec![
+                "https://example.invalid/sms/cost".to_owned(),
+                "https://example.invalid/my/balance".to_owned(),
+                "https://example.invalid/sms/send".to_owned(),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn spend_guard_refuses_send_over_cap() {
+        let transport = FakeTransport::sequence(vec![(
+            200,
+            r#"{"status":"OK","status_code":100,"total_cost":"12.00","sms":{"79251234567":{"status":"OK","status_code":100,"cost":"12.00","sms":8}}}"#,
+        )]);
+        let mut client = make_client(Auth::api_id("test_key").unwrap(), transport.clone());
+        client.spend_guard = Some(SpendGuard::new().max_total_cost(10.0));
+
+        let request = SendSms::to_many(
+            vec![RawPhoneNumber::new("79251234567").unwrap()],
+            MessageText::new("hello").unwrap(),
+            SendOptions::default(),
+        )
+        .unwrap();
+        let err = client.send_sms(request).await.unwrap_err();
+        assert!(matches!(
+            err,
+            SmsRuError::SpendGuard(SpendGuardViolation::CostExceedsCap { .. })
+        ));
+        let source = StdError::source(&err).unwrap();
+        assert!(source.downcast_ref::<SpendGuardViolation>().is_some());
+        assert_eq!(
+            transport.requested_urls(),
+            vec!["https://example.invalid/sms/cost".to_owned()]
+        );
+    }
+
+    #[tokio::test]
+    async fn recipient_policy_refuses_before_any_request() {
+        let transport = FakeTransport::new(200, "{}");
+        let mut client = make_client(Auth::api_id("test_key").unwrap(), transport.clone());
+        client.recipient_policy = Some(
+            RecipientPolicy::new()
+                .default_region(country::RU)
+                .allow_countries([country::RU]),
+        );
+        client.spend_guard = Some(SpendGuard::new());
+
+        let request = SendSms::to_many(
+            vec![
+                RawPhoneNumber::new("89251234567").unwrap(),
+                RawPhoneNumber::new("+4915123456789").unwrap(),
+            ],
+            MessageText::new("hello").unwrap(),
+            SendOptions::default(),
+        )
+        .unwrap();
+        let err = client.send_sms(request.clone()).await.unwrap_err();
+        let source = StdError::source(&err).unwrap();
+        assert!(source.downcast_ref::<RecipientPolicyViolation>().is_some());
+        let SmsRuError::RecipientPolicy(violation) = err else {
+            panic!("unexpected error: {err:?}");
+        };
+        assert_eq!(
+            violation.rejected.into_iter().collect::<Vec<_>>(),
+            vec![(
+                RawPhoneNumber::new("+4915123456789").unwrap(),
+                RecipientRejection::CountryNotAllowed {
+                    country: country::DE
+                }
+            )]
+        );
+
+        let err = client
+            .check_cost(CheckCost::from(&request))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, SmsRuError::RecipientPolicy(_)));
+        assert!(transport.requested_urls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn abuse_guard_does_not_count_sends_refused_by_other_guards() {
+        let transport = FakeTransport::sequence(vec![
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"total_cost":"12.00","sms":{"79251234567":{"status":"OK","status_code":100,"cost":"12.00","sms":8}}}"#,
+            ),
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"sms":{"79251234567":{"status":"OK","status_code":100,"sms_id":"1"}}}"#,
+            ),
+        ]);
+        let mut client = make_client(Auth::api_id("test_key").unwrap(), transport.clone());
+        client.abuse_guard = Some(
+            AbuseGuard::new()
+                .default_region(country::RU)
+                .prefix_velocity(1, Duration::from_secs(600)),
+        );
+        client.spend_guard = Some(SpendGuard::new().max_total_cost(10.0));
+
+        let request = SendSms::to_many(
+            vec![RawPhoneNumber::new("79251234567").unwrap()],
+            MessageText::new("hello").unwrap(),
+            SendOptions::default(),
+        )
+        .unwrap();
+        let err = client.send_sms(request.clone()).await.unwrap_err();
+        assert!(matches!(err, SmsRuError::SpendGuard(_)));
+
+        // The refused send did not use up the prefix budget.
+        client.spend_guard = None;
+        client.send_sms(request).await.unwrap();
+        assert_eq!(transport.requested_urls().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn abuse_guard_limits_call_auth_per_prefix() {
+        let json = r#"{"status":"OK","status_code":100,"check_id":"1","call_phone":"78005008275"}"#;
+        let transport = FakeTransport::new(200, json);
+        let mut client = make_client(Auth::api_id("test_key").unwrap(), transport.clone());
+        client.abuse_guard = Some(
+            AbuseGuard::new()
+                .default_region(country::RU)
+                .prefix_velocity(1, Duration::from_secs(600)),
+        );
+
+        let request = |raw: &str| {
+            StartCallAuth::new(
+                RawPhoneNumber::new(raw).unwrap(),
+                crate::domain::StartCallAuthOptions::default(),
+            )
+        };
+        client
+            .start_call_auth(request("89251234501"))
+            .await
+            .unwrap();
+        let err = client
+            .start_call_auth(request("+79251234502"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, SmsRuError::Abuse(_)));
+        let source = StdError::source(&err).unwrap();
+        assert!(source.downcast_ref::<AbuseGuardViolation>().is_some());
+        assert_eq!(err.kind(), SmsRuErrorKind::Throttled);
+        assert!(err.retry_after().is_some());
+        assert_eq!(transport.requested_urls().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn approved_senders_are_fetched_once_and_enforced() {
+        let transport = FakeTransport::sequence(vec![
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"senders":["Shop","Brand"]}"#,
+            ),
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"sms":{"79251234567":{"status":"OK","status_code":100,"sms_id":"1"}}}"#,
+            ),
+            (200, r#"{"status":"ERROR","status_code":204}"#),
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"senders":["Shop"]}"#,
+            ),
+        ]);
+        let mut client = make_client(Auth::api_id("test_key").unwrap(), transport.clone());
+        client.approved_senders = Some(senders::ApprovedSenders::new(Duration::from_secs(600)));
+
+        let request = |from: &str| {
+            SendSms::to_many(
+                vec![RawPhoneNumber::new("79251234567").unwrap()],
+                MessageText::new("hello").unwrap(),
+                SendOptions {
+                    from: Some(SenderId::new(from).unwrap()),
+                    ..SendOptions::default()
+                },
+            )
+            .unwrap()
+        };
+
+        client.send_sms(request("Shop")).await.unwrap();
+        let err = client.send_sms(request("Other")).await.unwrap_err();
+        assert!(matches!(err, SmsRuError::SenderNotApproved(ref s) if s.as_str() == "Other"));
+        assert_eq!(err.kind(), SmsRuErrorKind::InvalidRequest);
+
+        // SMS.RU disagrees with the cache: it is dropped and fetched again.
+        client.send_sms(request("Brand")).await.unwrap_err();
+        let err = client.send_sms(request("Brand")).await.unwrap_err();
+        assert!(matches!(err, SmsRuError::SenderNotApproved(_)));
+
+        assert_eq!(
+            transport.requested_urls(),
+            vec![
+                "https://example.invalid/my/senders".to_owned(),
+                "https://example.invalid/sms/send".to_owned(),
+                "https://example.invalid/sms/send".to_owned(),
+                "https://example.invalid/my/senders".to_owned(),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn send_sms_routed_sends_one_request_per_sender_and_merges() {
+        let transport = FakeTransport::sequence(vec![
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"balance":"10.00","sms":{"79251234567":{"status":"OK","status_code":100,"sms_id":"1"}}}"#,
+            ),
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"balance":"8.00","sms":{"4915123456789":{"status":"OK","status_code":100,"sms_id":"2"}}}"#,
+            ),
+            (200, r#"{"status":"ERROR","status_code":220}"#),
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"sms":{"4915123456789":{"status":"OK","status_code":100,"sms_id":"3"}}}"#,
+            ),
+            (200, r#"{"status":"ERROR","status_code":220}"#),
+        ]);
+        let client = make_client(Auth::api_id("test_key").unwrap(), transport.clone());
+        let router = SenderRouter::new(Some(SenderId::new("Intl").unwrap()))
+            .route(country::RU, SenderId::new("Domestic").unwrap());
+        let request = SendSms::to_many(
+            vec![
+                RawPhoneNumber::new("79251234567").unwrap(),
+                RawPhoneNumber::new("4915123456789").unwrap(),
+            ],
+            MessageText::new("hello").unwrap(),
+            SendOptions::default(),
+        )
+        .unwrap();
+
+        let routed = client.send_sms_routed(&router, request).await.unwrap();
+        assert!(routed.error.is_none());
+        let response = routed.response.unwrap();
+        assert_eq!(response.sms.len(), 2);
+        assert_eq!(response.balance.as_deref(), Some("8.00"));
+        let (_, params) = transport.last_request();
+        assert_param(&params, "from", "Intl");
+        assert_param(&params, "to", "4915123456789");
+
+        let request = SendSms::to_many(
+            vec![
+                RawPhoneNumber::new("+4915123456789").unwrap(),
+                RawPhoneNumber::new("+79251234567").unwrap(),
+            ],
+            MessageText::new("hello").unwrap(),
+            SendOptions::default(),
+        )
+        .unwrap();
+        let err = client
+            .send_sms_routed(&router, request.clone())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, SmsRuError::Api { .. }));
+
+        // A later part failing keeps what was sent and hands back the rest.
+        let routed = client.send_sms_routed(&router, request).await.unwrap();
+        assert_eq!(routed.response.unwrap().sms.len(), 1);
+        assert!(matches!(routed.error, Some(SmsRuError::Api { .. })));
+        assert!(routed.uncertain.is_none());
+        assert_eq!(routed.unsent.len(), 1);
+        assert_eq!(routed.unsent[0].recipient_count(), 1);
+        assert_eq!(transport.requested_urls().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn send_sms_routed_keeps_rejected_recipients_and_flags_uncertain_parts() {
+        let transport = FakeTransport::sequence(vec![(
+            200,
+            r#"{"status":"OK","status_code":100,"sms":{"79251234567":{"status":"ERROR","status_code":202}}}"#,
+        )]);
+        transport.queue_timeout();
+        let mut client = make_client(Auth::api_id("test_key").unwrap(), transport.clone());
+        client.fail_on_recipient_errors = true;
+        let router = SenderRouter::new(Some(SenderId::new("Intl").unwrap()))
+            .route(country::RU, SenderId::new("Domestic").unwrap());
+        let request = SendSms::to_many(
+            vec![
+                RawPhoneNumber::new("79251234567").unwrap(),
+                RawPhoneNumber::new("4915123456789").unwrap(),
+                RawPhoneNumber::new("+4915123456780").unwrap(),
+            ],
+            MessageText::new("hello").unwrap(),
+            SendOptions::default(),
+        )
+        .unwrap();
+
+        let routed = client.send_sms_routed(&router, request).await.unwrap();
+        // The first part was billed even though its recipient was rejected.
+        assert!(routed.response.unwrap().has_failures());
+        // The second part timed out: it may have been sent, so it is not offered for resending.
+        assert!(matches!(routed.error, Some(SmsRuError::Transport(_))));
+        assert_eq!(routed.uncertain.unwrap().recipient_count(), 2);
+        assert!(routed.unsent.is_empty());
+        assert_eq!(transport.requested_urls().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn message_limits_reject_long_texts_before_sending() {
+        let transport = FakeTransport::new(200, "{}");
+        let mut client = make_client(Auth::api_id("test_key").unwrap(), transport.clone());
+        client.message_limits = Some(MessageLimits::new().max_segments(1));
+
+        let request = SendSms::to_many(
+            vec![RawPhoneNumber::new("79251234567").unwrap()],
+            MessageText::new("я".repeat(71)).unwrap(),
+            SendOptions::default(),
+        )
+        .unwrap();
+        let err = client.send_sms(request.clone()).await.unwrap_err();
+        assert!(matches!(
+            err,
+            SmsRuError::Validation(ValidationError::TooManySegments { max: 1, length })
+                if length.segments == 2
+        ));
+        let err = client
+            .check_cost(CheckCost::from(&request))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, SmsRuError::Validation(_)));
+        assert!(transport.requested_urls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn daily_limit_guard_seeds_from_my_limit_and_fails_fast() {
+        let transport = FakeTransport::sequence(vec![
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"total_limit":3,"used_today":1}"#,
+            ),
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"sms":{"79250000001":{"status":"OK","status_code":100,"sms_id":"1"}}}"#,
+            ),
+        ]);
+        let mut client = make_client(Auth::api_id("test_key").unwrap(), transport.clone());
+        let guard = DailyLimitGuard::new(Duration::from_secs(3600));
+        client.daily_limit = Some(guard.clone());
+
+        let one = SendSms::to_many(
+            vec![RawPhoneNumber::new("79250000001").unwrap()],
+            MessageText::new("hello").unwrap(),
+            SendOptions::default(),
+        )
+        .unwrap();
+        client.send_sms(one).await.unwrap();
+        assert_eq!(guard.remaining(), Some(1));
+
+        let two = SendSms::to_many(
+            vec![
+                RawPhoneNumber::new("79250000002").unwrap(),
+                RawPhoneNumber::new("79250000003").unwrap(),
+            ],
+            MessageText::new("hello").unwrap(),
+            SendOptions::default(),
+        )
+        .unwrap();
+        let err = client.send_sms(two).await.unwrap_err();
+        assert!(matches!(
+            err,
+            SmsRuError::DailyLimit(DailyLimitExceeded {
+                requested: 2,
+                remaining: 1
+            })
+        ));
+        let source = StdError::source(&err).unwrap();
+        assert!(source.downcast_ref::<DailyLimitExceeded>().is_some());
+        assert_eq!(
+            transport.requested_urls(),
+            vec![
+                "https://example.invalid/my/limit".to_owned(),
+                "https://example.invalid/sms/send".to_owned(),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn send_sms_within_daily_limit_defers_overflow() {
+        let transport = FakeTransport::sequence(vec![
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"total_limit":10,"used_today":9}"#,
+            ),
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"sms":{"79250000001":{"status":"OK","status_code":100,"sms_id":"1"}}}"#,
+            ),
+        ]);
+        let mut client = make_client(Auth::api_id("test_key").unwrap(), transport.clone());
+        client.daily_limit = Some(DailyLimitGuard::new(Duration::from_secs(3600)));
+
+        let request = SendSms::to_many(
+            vec![
+                RawPhoneNumber::new("79250000001").unwrap(),
+                RawPhoneNumber::new("79250000002").unwrap(),
+            ],
+            MessageText::new("hello").unwrap(),
+            SendOptions::default(),
+        )
+        .unwrap();
+        let outcome = client.send_sms_within_daily_limit(request).await.unwrap();
+        assert_eq!(outcome.response.unwrap().sms.len(), 1);
+        assert_eq!(outcome.deferred.unwrap().recipient_count(), 1);
+
+        let (_, params) = transport.last_request();
+        assert_param(&params, "to", "79250000001");
+    }
+
+    #[tokio::test]
+    async fn send_sms_within_daily_limit_keeps_deferred_when_recipients_fail() {
+        let transport = FakeTransport::sequence(vec![
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"total_limit":10,"used_today":9}"#,
+            ),
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"sms":{"79250000001":{"status":"ERROR","status_code":202}}}"#,
+            ),
+        ]);
+        let mut client = make_client(Auth::api_id("test_key").unwrap(), transport);
+        client.daily_limit = Some(DailyLimitGuard::new(Duration::from_secs(3600)));
+        client.fail_on_recipient_errors = true;
+
+        let request = SendSms::to_many(
+            vec![
+                RawPhoneNumber::new("79250000001").unwrap(),
+                RawPhoneNumber::new("79250000002").unwrap(),
+            ],
+            MessageText::new("hello").unwrap(),
+            SendOptions::default(),
+        )
+        .unwrap();
+        let outcome = client.send_sms_within_daily_limit(request).await.unwrap();
+        assert!(outcome.response.unwrap().has_failures());
+        assert_eq!(outcome.deferred.unwrap().recipient_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn send_sms_can_fail_on_rejected_recipients() {
+        let json = r#"{"status":"OK","status_code":100,"sms":{"79250000001":{"status":"OK","status_code":100,"sms_id":"1"},"79250000002":{"status":"ERROR","status_code":202}}}"#;
+        let request = SendSms::to_many(
+            vec![
+                RawPhoneNumber::new("79250000001").unwrap(),
+                RawPhoneNumber::new("79250000002").unwrap(),
+            ],
+            MessageText::new("hello").unwrap(),
+            SendOptions::default(),
+        )
+        .unwrap();
+
+        let mut client = make_client(
+            Auth::api_id("test_key").unwrap(),
+            FakeTransport::new(200, json),
+        );
+        let response = client.send_sms(request.clone()).await.unwrap();
+        assert_eq!(response.failures().count(), 1);
+
+        client.fail_on_recipient_errors = true;
+        let err = client.send_sms(request).await.unwrap_err();
+        match err {
+            SmsRuError::RecipientsFailed(response) => {
+                assert_eq!(response.successes().count(), 1);
+                assert_eq!(
+                    response.failures().next().unwrap().1.failure(),
+                    Some(crate::domain::RecipientFailure::InvalidNumber)
+                );
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn send_sms_normalizes_recipients_and_restores_input_keys() {
+        let json = r#"{"status":"OK","status_code":100,"sms":{"79250000001":{"status":"OK","status_code":100,"sms_id":"1"}}}"#;
+        let transport = FakeTransport::new(200, json);
+        let mut client = make_client(Auth::api_id("test_key").unwrap(), transport.clone());
+        client.normalization = Some(Normalization {
+            default_region: Some(country::RU),
+        });
+
+        let request = SendSms::to_many(
+            vec![
+                RawPhoneNumber::new("8 925 000-00-01").unwrap(),
+                RawPhoneNumber::new("+79250000001").unwrap(),
+            ],
+            MessageText::new("hello").unwrap(),
+            SendOptions::default(),
+        )
+        .unwrap();
+        let response = client.send_sms(request).await.unwrap();
+
+        let (_, params) = transport.last_request();
+        assert_param(&params, "to", "79250000001");
+        assert_eq!(
+            response.sms.keys().map(|p| p.raw()).collect::<Vec<_>>(),
+            vec!["+79250000001", "8 925 000-00-01"]
+        );
+
+        let invalid = SendSms::to_many(
+            vec![
+                RawPhoneNumber::new("79250000001").unwrap(),
+                RawPhoneNumber::new("garbage").unwrap(),
+            ],
+            MessageText::new("hello").unwrap(),
+            SendOptions::default(),
+        )
+        .unwrap();
+        let err = client.send_sms(invalid).await.unwrap_err();
+        assert!(matches!(err, SmsRuError::Validation(_)));
+    }
+
+    #[tokio::test]
+    async fn send_sms_within_daily_limit_counts_normalized_recipients_once() {
+        let transport = FakeTransport::sequence(vec![
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"total_limit":10,"used_today":9}"#,
+            ),
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"sms":{"79250000001":{"status":"OK","status_code":100,"sms_id":"1"}}}"#,
+            ),
+        ]);
+        let mut client = make_client(Auth::api_id("test_key").unwrap(), transport.clone());
+        client.daily_limit = Some(DailyLimitGuard::new(Duration::from_secs(3600)));
+        client.normalization = Some(Normalization {
+            default_region: Some(country::RU),
+        });
+
+        let request = SendSms::to_many(
+            vec![
+                RawPhoneNumber::new("8 925 000-00-01").unwrap(),
+                RawPhoneNumber::new("+79250000001").unwrap(),
+            ],
+            MessageText::new("hello").unwrap(),
+            SendOptions::default(),
+        )
+        .unwrap();
+        let outcome = client.send_sms_within_daily_limit(request).await.unwrap();
+        assert!(outcome.deferred.is_none());
+        assert_eq!(outcome.response.unwrap().sms.len(), 2);
+
+        let (_, params) = transport.last_request();
+        assert_param(&params, "to", "79250000001");
+    }
+
+    #[tokio::test]
+    async fn send_sms_idempotent_returns_stored_response_for_repeated_key() {
+        let transport = FakeTransport::sequence(vec![
+            (200, r#"{"status":"ERROR","status_code":220}"#),
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"sms":{"79250000001":{"status":"OK","status_code":100,"sms_id":"1"}}}"#,
+            ),
+        ]);
+        let client = make_client(Auth::api_id("test_key").unwrap(), transport.clone());
+        let request = SendSms::to_many(
+            vec![RawPhoneNumber::new("79250000001").unwrap()],
+            MessageText::new("code 1234").unwrap(),
+            SendOptions::default(),
+        )
+        .unwrap();
+        let key = IdempotencyKey::new("otp-42").unwrap();
+
+        // SMS.RU refused the send, so the retry reaches SMS.RU.
+        let err = client
+            .send_sms_idempotent(key.clone(), request.clone())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, SmsRuError::Api { .. }));
+        let first = client
+            .send_sms_idempotent(key.clone(), request.clone())
+            .await
+            .unwrap();
+        let second = client
+            .clone()
+            .send_sms_idempotent(key, request)
+            .await
+            .unwrap();
+        assert_eq!(first, second);
+        assert_eq!(transport.requested_urls().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn send_sms_idempotent_does_not_resend_after_a_timeout() {
+        let transport = FakeTransport::sequence(Vec::new());
+        transport.queue_timeout();
+        let client = make_client(Auth::api_id("test_key").unwrap(), transport.clone());
+        let request = SendSms::to_many(
+            vec![RawPhoneNumber::new("79250000001").unwrap()],
+            MessageText::new("code 1234").unwrap(),
+            SendOptions::default(),
+        )
+        .unwrap();
+        let key = IdempotencyKey::new("otp-43").unwrap();
+
+        let err = client
+            .send_sms_idempotent(key.clone(), request.clone())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, SmsRuError::Transport(_)));
+        let err = client
+            .send_sms_idempotent(key.clone(), request)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, SmsRuError::IdempotencyOutcomeUnknown(k) if k == key));
+        assert_eq!(transport.requested_urls().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn send_sms_idempotent_respects_sends_started_through_a_shared_store() {
+        let transport = FakeTransport::new(200, "{}");
+        let store = Arc::new(InMemoryIdempotencyStore::new());
+        let mut client = make_client(Auth::api_id("test_key").unwrap(), transport.clone());
+        client.idempotency = idempotency::Idempotency::new(store.clone(), Duration::from_secs(60));
+        let request = SendSms::to_many(
+            vec![RawPhoneNumber::new("79250000001").unwrap()],
+            MessageText::new("code 1234").unwrap(),
+            SendOptions::default(),
+        )
+        .unwrap();
+        let key = IdempotencyKey::new("otp-44").unwrap();
+
+        // Another process started the same send through the shared store.
+        store
+            .try_start(
+                key.clone(),
+                crate::transport::send_sms_fingerprint(&request),
+                Duration::from_secs(60),
+            )
+            .unwrap();
+        let err = client
+            .send_sms_idempotent(key.clone(), request)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, SmsRuError::IdempotencyOutcomeUnknown(k) if k == key));
+        assert!(transport.requested_urls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn send_sms_idempotent_rejects_a_key_reused_for_another_request() {
+        let transport = FakeTransport::new(
+            200,
+            r#"{"status":"OK","status_code":100,"sms":{"79250000001":{"status":"OK","status_code":100,"sms_id":"1"}}}"#,
+        );
+        let client = make_client(Auth::api_id("test_key").unwrap(), transport.clone());
+        let request = |text: &str| {
+            SendSms::to_many(
+                vec![RawPhoneNumber::new("79250000001").unwrap()],
+                MessageText::new(text).unwrap(),
+                SendOptions::default(),
+            )
+            .unwrap()
+        };
+        let key = IdempotencyKey::new("otp-45").unwrap();
+
+        client
+            .send_sms_idempotent(key.clone(), request("code 1234"))
+            .await
+            .unwrap();
+        let err = client
+            .send_sms_idempotent(key.clone(), request("code 5678"))
+            .await
+            .unwrap_err();
+        assert!(matches!(&err, SmsRuError::IdempotencyKeyReused(k) if k == &key));
+        assert_eq!(err.kind(), SmsRuErrorKind::InvalidRequest);
+        assert_eq!(transport.requested_urls().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn ensure_callbacks_adds_missing_and_keeps_others_by_default() {
+        let transport = FakeTransport::sequence(vec![
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"callback":["https://example.com/a/","https://example.com/old"]}"#,
+            ),
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"callback":["https://example.com/a/","https://example.com/old","https://example.com/b"]}"#,
+            ),
+        ]);
+        let client = make_client(Auth::api_id("test_key").unwrap(), transport.clone());
+        let desired = vec![
+            CallbackUrl::new("https://example.com:443/a").unwrap(),
+            CallbackUrl::new("https://example.com/b").unwrap(),
+        ];
+
+        let report = client
+            .ensure_callbacks(&desired, CallbackSyncPolicy::AddOnly)
+            .await
+            .unwrap();
+        assert_eq!(report.added, vec![desired[1].clone()]);
+        assert!(report.removed.is_empty());
+        assert_eq!(report.callback.len(), 3);
+
+        let (_, params) = transport.last_request();
+        assert_param(&params, "url", "https://example.com/b");
+        assert_eq!(
+            transport.requested_urls(),
+            vec![
+                "https://example.invalid/callback/get".to_owned(),
+                "https://example.invalid/callback/add".to_owned(),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn ensure_callbacks_prunes_stale_urls() {
+        let transport = FakeTransport::sequence(vec![
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"callback":["https://example.com/a","https://example.com/old"]}"#,
+            ),
+            (
+                200,
+                r#"{"status":"OK","status_code":100,"callback":["https://example.com/a"]}"#,
+            ),
+        ]);
+        let client = make_client(Auth::api_id("test_key").unwrap(), transport.clone());
+        let desired = vec![CallbackUrl::new("https://example.com/a/").unwrap()];
+
+        let report = client
+            .ensure_callbacks(&desired, CallbackSyncPolicy::AddAndPrune)
+            .await
+            .unwrap();
+        assert!(report.added.is_empty());
+        assert_eq!(
+            report.removed,
+            vec![CallbackUrl::new("https://example.com/old").unwrap()]
+        );
+        assert_eq!(report.callback.len(), 1);
+
+        let (url, params) = transport.last_request();
+        assert_eq!(url.as_deref(), Some("https://example.invalid/callback/del"));
+        assert_param(&params, "url", "https://example.com/old");
+    }
+
     #[test]
     fn builder_endpoint_overrides_are_applied() {
         let client = SmsRuClient::builder(Auth::api_id("key").unwrap())
@@ -2158,4 +3579,128 @@ mod tests {
             "https://example.invalid/callback/get"
         );
     }
+
+    #[test]
+    fn builder_rejects_invalid_proxy_urls() {
+        let err = SmsRuClient::builder(Auth::api_id("key").unwrap())
+            .proxy("not a url")
+            .build()
+            .err()
+            .unwrap();
+        assert!(matches!(err, SmsRuError::Transport(_)));
+    }
+
+    #[tokio::test]
+    async fn builder_sends_requests_through_the_proxy_with_credentials() {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_url = format!("http://{}", listener.local_addr().unwrap());
+        let proxy = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut head = Vec::new();
+            let mut byte = [0u8; 1];
+            while !head.ends_with(b"\r\n\r\n") {
+                stream.read_exact(&mut byte).unwrap();
+                head.push(byte[0]);
+            }
+            let body = r#"{"status":"OK","status_code":100}"#;
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+            String::from_utf8(head).unwrap()
+        });
+
+        let client = SmsRuClient::builder(Auth::api_id("key").unwrap())
+            .base_url("http://sms.invalid")
+            .proxy(proxy_url)
+            .proxy_basic_auth("user", "secret")
+            .connect_timeout(Duration::from_secs(2))
+            .read_timeout(Duration::from_secs(10))
+            .build()
+            .unwrap();
+        client.check_auth().await.unwrap();
+
+        let head = proxy.join().unwrap();
+        assert!(
+            head.starts_with("POST http://sms.invalid/auth/check HTTP/1.1\r\n"),
+            "{head}"
+        );
+        // base64("user:secret")
+        assert!(
+            head.to_ascii_lowercase()
+                .contains("proxy-authorization: basic dxnlcjpzzwnyzxq="),
+            "{head}"
+        );
+    }
+
+    #[test]
+    fn builder_rejects_unusable_certificate_bundles() {
+        let broken = "-----BEGIN CERTIFICATE-----\n!!!\n-----END CERTIFICATE-----\n";
+        let err = SmsRuClient::builder(Auth::api_id("key").unwrap())
+            .root_certificates_pem(broken)
+            .build()
+            .err()
+            .unwrap();
+        assert!(matches!(err, SmsRuError::Transport(_)));
+
+        let err = SmsRuClient::builder(Auth::api_id("key").unwrap())
+            .only_root_certificates_pem("no certificates here")
+            .build()
+            .err()
+            .unwrap();
+        assert!(matches!(err, SmsRuError::Transport(_)));
+    }
+
+    #[test]
+    fn builder_base_url_derives_every_endpoint() {
+        let client = SmsRuClient::builder(Auth::api_id("key").unwrap())
+            .base_url("https://proxy.invalid/smsru/")
+            .my_limit_endpoint("https://other.invalid/limit")
+            .build()
+            .unwrap();
+        assert_eq!(client.send_endpoint, "https://proxy.invalid/smsru/sms/send");
+        assert_eq!(
+            client.callcheck_status_endpoint,
+            "https://proxy.invalid/smsru/callcheck/status"
+        );
+        assert_eq!(
+            client.my_balance_endpoint,
+            "https://proxy.invalid/smsru/my/balance"
+        );
+        assert_eq!(client.my_limit_endpoint, "https://other.invalid/limit");
+        assert_eq!(
+            client.callback_get_endpoint,
+            "https://proxy.invalid/smsru/callback/get"
+        );
+        assert!(client.mirrors.is_none());
+    }
+
+    #[tokio::test]
+    async fn fallback_base_urls_switch_mirror_on_connect_error() {
+        let transport =
+            FakeTransport::new(200, r#"{"status":"OK","status_code":100,"balance":1.5}"#);
+        transport.refuse("https://sms.ru");
+        let mut client = make_client(Auth::api_id("key").unwrap(), transport.clone());
+        client.my_balance_endpoint = "https://sms.ru/my/balance".to_owned();
+        client.mirrors = Some(mirrors::Mirrors::new(
+            "https://sms.ru".to_owned(),
+            vec!["https://mirror.invalid".to_owned()],
+        ));
+
+        client.get_balance().await.unwrap();
+        client.get_balance().await.unwrap();
+
+        assert_eq!(
+            transport.requested_urls(),
+            vec![
+                "https://sms.ru/my/balance",
+                "https://mirror.invalid/my/balance",
+                "https://mirror.invalid/my/balance",
+            ]
+        );
+    }
 }
@@ -0,0 +1,299 @@
+//! Several SMS.RU accounts behind one sender, with failover between them.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::{SmsRuClient, SmsRuError, SmsRuErrorKind};
+use crate::domain::{KnownStatusCode, SendSms, SendSmsResponse};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// Order in which [`SmsRuPool`] tries its accounts.
+pub enum PoolPolicy {
+    #[default]
+    /// Always start with the first account and fail over in the configured order.
+    Failover,
+    /// Rotate the starting account on every send, failing over to the following ones.
+    RoundRobin,
+}
+
+#[derive(Clone)]
+struct PoolAccount {
+    name: String,
+    tags: BTreeSet<String>,
+    client: SmsRuClient,
+}
+
+#[derive(Clone)]
+/// Multi-account sender built from several [`SmsRuClient`]s.
+///
+/// A send fails over to the next account only when the failure is tied to the account and
+/// SMS.RU did not accept the request: auth errors, insufficient funds, "service temporarily
+/// unavailable"/server errors, and daily limits (see [`is_failover_error`]). Transport and HTTP
+/// failures are not failed over because the request may already have been sent.
+///
+/// Clones share the round-robin position.
+pub struct SmsRuPool {
+    accounts: Vec<PoolAccount>,
+    policy: PoolPolicy,
+    next: Arc<AtomicUsize>,
+}
+
+#[derive(Clone)]
+/// Builder for [`SmsRuPool`].
+pub struct SmsRuPoolBuilder {
+    accounts: Vec<PoolAccount>,
+    policy: PoolPolicy,
+}
+
+#[derive(Debug)]
+/// Successful send through [`SmsRuPool`].
+pub struct PooledSend {
+    /// Name of the account that handled the send.
+    pub account: String,
+    /// Response from that account.
+    pub response: SendSmsResponse,
+    /// Accounts tried before, with the error that caused the failover.
+    pub failed_over: Vec<(String, SmsRuError)>,
+}
+
+#[derive(Debug, thiserror::Error)]
+/// Errors returned by [`SmsRuPool`].
+pub enum SmsRuPoolError {
+    /// No account is configured (for `tag`, when sending by tag).
+    #[error("no SMS.RU account available (tag: {tag:?})")]
+    NoAccount { tag: Option<String> },
+
+    /// The last tried account failed.
+    #[error("account {account:?} failed: {error}")]
+    Send {
+        /// Account that returned `error`.
+        account: String,
+        #[source]
+        error: SmsRuError,
+        /// Accounts tried before, with the error that caused the failover.
+        failed_over: Vec<(String, SmsRuError)>,
+    },
+}
+
+impl SmsRuPool {
+    /// Start building a pool with `policy`.
+    pub fn builder(policy: PoolPolicy) -> SmsRuPoolBuilder {
+        SmsRuPoolBuilder {
+            accounts: Vec::new(),
+            policy,
+        }
+    }
+
+    /// Names of the configured accounts, in configuration order.
+    pub fn accounts(&self) -> impl Iterator<Item = &str> {
+        self.accounts.iter().map(|account| account.name.as_str())
+    }
+
+    /// Send through the accounts chosen by the pool policy.
+    pub async fn send_sms(&self, request: SendSms) -> Result<PooledSend, SmsRuPoolError> {
+        let candidates = self.accounts.iter().collect::<Vec<_>>();
+        self.send_with(candidates, None, request).await
+    }
+
+    /// Send through the accounts tagged with `tag`, following the pool policy among them.
+    pub async fn send_sms_tagged(
+        &self,
+        tag: &str,
+        request: SendSms,
+    ) -> Result<PooledSend, SmsRuPoolError> {
+        let candidates = self
+            .accounts
+            .iter()
+            .filter(|account| account.tags.contains(tag))
+            .collect::<Vec<_>>();
+        self.send_with(candidates, Some(tag), request).await
+    }
+
+    async fn send_with(
+        &self,
+        mut candidates: Vec<&PoolAccount>,
+        tag: Option<&str>,
+        request: SendSms,
+    ) -> Result<PooledSend, SmsRuPoolError> {
+        if candidates.is_empty() {
+            return Err(SmsRuPoolError::NoAccount {
+                tag: tag.map(str::to_owned),
+            });
+        }
+        if self.policy == PoolPolicy::RoundRobin {
+            let start = self.next.fetch_add(1, Ordering::Relaxed) % candidates.len();
+            candidates.rotate_left(start);
+        }
+
+        let mut failed_over = Vec::new();
+        let last = candidates.len() - 1;
+        for (idx, account) in candidates.into_iter().enumerate() {
+            match account.client.send_sms(request.clone()).await {
+                Ok(response) => {
+                    return Ok(PooledSend {
+                        account: account.name.clone(),
+                        response,
+                        failed_over,
+                    });
+                }
+                Err(error) if idx < last && is_failover_error(&error) => {
+                    failed_over.push((account.name.clone(), error));
+                }
+                Err(error) => {
+                    return Err(SmsRuPoolError::Send {
+                        account: account.name.clone(),
+                        error,
+                        failed_over,
+                    });
+                }
+            }
+        }
+        unreachable!("the last candidate always returns")
+    }
+}
+
+impl SmsRuPoolBuilder {
+    /// Add an account named `name`.
+    pub fn account(self, name: impl Into<String>, client: SmsRuClient) -> Self {
+        self.tagged_account(name, std::iter::empty::<String>(), client)
+    }
+
+    /// Add an account named `name` that serves [`SmsRuPool::send_sms_tagged`] for `tags`.
+    pub fn tagged_account<T: Into<String>>(
+        mut self,
+        name: impl Into<String>,
+        tags: impl IntoIterator<Item = T>,
+        client: SmsRuClient,
+    ) -> Self {
+        self.accounts.push(PoolAccount {
+            name: name.into(),
+            tags: tags.into_iter().map(Into::into).collect(),
+            client,
+        });
+        self
+    }
+
+    /// Build the pool.
+    pub fn build(self) -> SmsRuPool {
+        SmsRuPool {
+            accounts: self.accounts,
+            policy: self.policy,
+            next: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+}
+
+/// Whether [`SmsRuPool`] moves on to the next account after `error`.
+///
+/// True for SMS.RU API errors classified as [`SmsRuErrorKind::Auth`],
+/// [`SmsRuErrorKind::InsufficientFunds`], or [`SmsRuErrorKind::Retryable`], for the account
+/// daily limit (`206`), and for [`SmsRuError::DailyLimit`].
+pub fn is_failover_error(error: &SmsRuError) -> bool {
+    match error {
+        SmsRuError::Api { status_code, .. } => {
+            matches!(
+                error.kind(),
+                SmsRuErrorKind::Auth
+                    | SmsRuErrorKind::InsufficientFunds
+                    | SmsRuErrorKind::Retryable
+            ) || status_code.known_kind() == Some(KnownStatusCode::DailyLimitExceeded)
+        }
+        SmsRuError::DailyLimit(_) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Auth;
+    use crate::client::test_support::{FakeTransport, make_client};
+    use crate::domain::{MessageText, RawPhoneNumber, SendOptions};
+
+    const OK: &str = r#"{"status":"OK","status_code":100,"sms":{"79250000001":{"status":"OK","status_code":100,"sms_id":"1"}}}"#;
+
+    fn error(code: i32) -> String {
+        format!(r#"{{"status":"ERROR","status_code":{code}}}"#)
+    }
+
+    fn client(transport: &FakeTransport) -> SmsRuClient {
+        make_client(Auth::api_id("key").unwrap(), transport.clone())
+    }
+
+    fn request() -> SendSms {
+        SendSms::to_many(
+            vec![RawPhoneNumber::new("79250000001").unwrap()],
+            MessageText::new("hello").unwrap(),
+            SendOptions::default(),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn fails_over_on_account_level_errors() {
+        let primary = FakeTransport::new(200, error(201));
+        let backup = FakeTransport::new(200, OK);
+        let pool = SmsRuPool::builder(PoolPolicy::Failover)
+            .account("primary", client(&primary))
+            .account("backup", client(&backup))
+            .build();
+
+        let sent = pool.send_sms(request()).await.unwrap();
+        assert_eq!(sent.account, "backup");
+        assert_eq!(sent.failed_over.len(), 1);
+        assert_eq!(sent.failed_over[0].0, "primary");
+        assert_eq!(
+            sent.failed_over[0].1.kind(),
+            SmsRuErrorKind::InsufficientFunds
+        );
+    }
+
+    #[tokio::test]
+    async fn request_errors_are_not_failed_over() {
+        let primary = FakeTransport::new(200, error(202));
+        let backup = FakeTransport::new(200, OK);
+        let pool = SmsRuPool::builder(PoolPolicy::Failover)
+            .account("primary", client(&primary))
+            .account("backup", client(&backup))
+            .build();
+
+        let err = pool.send_sms(request()).await.unwrap_err();
+        assert!(matches!(err, SmsRuPoolError::Send { ref account, .. } if account == "primary"));
+        assert!(backup.requested_urls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn round_robin_rotates_accounts() {
+        let a = FakeTransport::new(200, OK);
+        let b = FakeTransport::new(200, OK);
+        let pool = SmsRuPool::builder(PoolPolicy::RoundRobin)
+            .account("a", client(&a))
+            .account("b", client(&b))
+            .build();
+
+        let mut handled = Vec::new();
+        for _ in 0..3 {
+            handled.push(pool.send_sms(request()).await.unwrap().account);
+        }
+        assert_eq!(handled, vec!["a", "b", "a"]);
+    }
+
+    #[tokio::test]
+    async fn tagged_sends_use_only_matching_accounts() {
+        let shop = FakeTransport::new(200, OK);
+        let bank = FakeTransport::new(200, OK);
+        let pool = SmsRuPool::builder(PoolPolicy::Failover)
+            .tagged_account("shop", ["shop"], client(&shop))
+            .tagged_account("bank", ["bank", "otp"], client(&bank))
+            .build();
+
+        let sent = pool.send_sms_tagged("otp", request()).await.unwrap();
+        assert_eq!(sent.account, "bank");
+        assert!(shop.requested_urls().is_empty());
+        assert!(matches!(
+            pool.send_sms_tagged("promo", request()).await,
+            Err(SmsRuPoolError::NoAccount { tag: Some(_) })
+        ));
+    }
+}
@@ -0,0 +1,265 @@
+//! Optional country and number-type checks for recipients of sends and cost requests.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+use phonenumber::{Type as NumberType, country, metadata::DATABASE};
+
+use crate::domain::{CheckCost, RawPhoneNumber, SendSms, resolve_phone};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+/// Countries and number types the client is allowed to send to.
+///
+/// When configured through [`super::SmsRuClientBuilder::recipient_policy`], every recipient of
+/// `send_sms` and `check_cost` is resolved with the `phonenumber` metadata and the request is
+/// refused with [`super::SmsRuError::RecipientPolicy`] before anything is sent to SMS.RU.
+///
+/// Numbers are parsed in [`RecipientPolicy::default_region`]. A digits-only number that is not
+/// valid there is read as international (`79251234567` as `+79251234567`), the way SMS.RU reads
+/// it. Numbers that cannot be resolved are always rejected.
+pub struct RecipientPolicy {
+    default_region: Option<country::Id>,
+    allowed_countries: Option<HashSet<country::Id>>,
+    denied_countries: HashSet<country::Id>,
+    allowed_types: Option<HashSet<NumberType>>,
+    denied_types: HashSet<NumberType>,
+}
+
+impl RecipientPolicy {
+    /// Create a policy that accepts every valid number.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Region used for numbers written without a country code.
+    pub fn default_region(mut self, region: country::Id) -> Self {
+        self.default_region = Some(region);
+        self
+    }
+
+    /// Only send to these countries. Calling this again extends the list.
+    pub fn allow_countries(mut self, countries: impl IntoIterator<Item = country::Id>) -> Self {
+        self.allowed_countries
+            .get_or_insert_with(HashSet::new)
+            .extend(countries);
+        self
+    }
+
+    /// Never send to these countries, even if they are allowed.
+    pub fn deny_countries(mut self, countries: impl IntoIterator<Item = country::Id>) -> Self {
+        self.denied_countries.extend(countries);
+        self
+    }
+
+    /// Only send to these number types. Calling this again extends the list.
+    ///
+    /// Some regions do not tell mobile and fixed-line numbers apart; their numbers resolve to
+    /// [`NumberType::FixedLineOrMobile`].
+    pub fn allow_number_types(mut self, types: impl IntoIterator<Item = NumberType>) -> Self {
+        self.allowed_types
+            .get_or_insert_with(HashSet::new)
+            .extend(types);
+        self
+    }
+
+    /// Never send to these number types, e.g. [`NumberType::PremiumRate`].
+    pub fn deny_number_types(mut self, types: impl IntoIterator<Item = NumberType>) -> Self {
+        self.denied_types.extend(types);
+        self
+    }
+
+    /// Check a single recipient.
+    pub fn check(&self, phone: &RawPhoneNumber) -> Result<(), RecipientRejection> {
+        let parsed = resolve_phone(self.default_region, phone.raw())
+            .ok_or(RecipientRejection::InvalidNumber)?;
+
+        let country = parsed
+            .parsed()
+            .country()
+            .id()
+            .ok_or(RecipientRejection::UnknownCountry)?;
+        let country_allowed = self
+            .allowed_countries
+            .as_ref()
+            .is_none_or(|allowed| allowed.contains(&country));
+        if !country_allowed || self.denied_countries.contains(&country) {
+            return Err(RecipientRejection::CountryNotAllowed { country });
+        }
+
+        let number_type = parsed.parsed().number_type(&DATABASE);
+        let type_allowed = self
+            .allowed_types
+            .as_ref()
+            .is_none_or(|allowed| allowed.contains(&number_type));
+        if !type_allowed || self.denied_types.contains(&number_type) {
+            return Err(RecipientRejection::NumberTypeNotAllowed {
+                country,
+                number_type,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Check every recipient and collect the rejected ones.
+    pub fn check_all<'a>(
+        &self,
+        phones: impl IntoIterator<Item = &'a RawPhoneNumber>,
+    ) -> Result<(), RecipientPolicyViolation> {
+        let rejected = phones
+            .into_iter()
+            .filter_map(|phone| {
+                self.check(phone)
+                    .err()
+                    .map(|rejection| (phone.clone(), rejection))
+            })
+            .collect::<BTreeMap<_, _>>();
+        if rejected.is_empty() {
+            Ok(())
+        } else {
+            Err(RecipientPolicyViolation { rejected })
+        }
+    }
+
+    pub(crate) fn check_send(&self, request: &SendSms) -> Result<(), RecipientPolicyViolation> {
+        match request {
+            SendSms::ToMany(to_many) => self.check_all(to_many.recipients()),
+            SendSms::PerRecipient(per_recipient) => self.check_all(per_recipient.messages().keys()),
+        }
+    }
+
+    pub(crate) fn check_cost(&self, request: &CheckCost) -> Result<(), RecipientPolicyViolation> {
+        match request {
+            CheckCost::ToMany(to_many) => self.check_all(to_many.recipients()),
+            CheckCost::PerRecipient(per_recipient) => {
+                self.check_all(per_recipient.messages().keys())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Why [`RecipientPolicy`] rejected a recipient.
+pub enum RecipientRejection {
+    /// The number could not be parsed or is not a valid number.
+    InvalidNumber,
+    /// The number is valid but its country could not be determined.
+    UnknownCountry,
+    /// The recipient's country is not allowed.
+    CountryNotAllowed { country: country::Id },
+    /// The recipient's number type is not allowed.
+    NumberTypeNotAllowed {
+        country: country::Id,
+        number_type: NumberType,
+    },
+}
+
+impl fmt::Display for RecipientRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidNumber => f.write_str("invalid number"),
+            Self::UnknownCountry => f.write_str("unknown country"),
+            Self::CountryNotAllowed { country } => {
+                write!(f, "country {} is not allowed", country.as_ref())
+            }
+            Self::NumberTypeNotAllowed {
+                country,
+                number_type,
+            } => write!(
+                f,
+                "number type {number_type:?} ({}) is not allowed",
+                country.as_ref()
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("{} recipient(s) rejected by recipient policy", .rejected.len())]
+/// Recipients refused by [`RecipientPolicy`], keyed by the number as given in the request.
+pub struct RecipientPolicyViolation {
+    pub rejected: BTreeMap<RawPhoneNumber, RecipientRejection>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phone(raw: &str) -> RawPhoneNumber {
+        RawPhoneNumber::new(raw).unwrap()
+    }
+
+    #[test]
+    fn default_policy_accepts_valid_numbers_only() {
+        let policy = RecipientPolicy::new().default_region(country::RU);
+        assert_eq!(policy.check(&phone("+79251234567")), Ok(()));
+        assert_eq!(policy.check(&phone("89251234567")), Ok(()));
+        assert_eq!(
+            policy.check(&phone("12345")),
+            Err(RecipientRejection::InvalidNumber)
+        );
+    }
+
+    #[test]
+    fn digits_without_plus_are_read_as_international() {
+        let policy = RecipientPolicy::new()
+            .default_region(country::RU)
+            .allow_countries([country::DE]);
+        assert_eq!(policy.check(&phone("4915123456789")), Ok(()));
+        assert_eq!(
+            policy.check(&phone("79251234567")),
+            Err(RecipientRejection::CountryNotAllowed {
+                country: country::RU
+            })
+        );
+    }
+
+    #[test]
+    fn deny_list_wins_over_allow_list() {
+        let policy = RecipientPolicy::new()
+            .allow_countries([country::RU, country::DE])
+            .deny_countries([country::DE]);
+        assert_eq!(policy.check(&phone("+79251234567")), Ok(()));
+        assert_eq!(
+            policy.check(&phone("+4915123456789")),
+            Err(RecipientRejection::CountryNotAllowed {
+                country: country::DE
+            })
+        );
+    }
+
+    #[test]
+    fn number_types_are_checked() {
+        let policy = RecipientPolicy::new().allow_number_types([NumberType::Mobile]);
+        assert_eq!(policy.check(&phone("+79251234567")), Ok(()));
+        assert_eq!(
+            policy.check(&phone("+74951234567")),
+            Err(RecipientRejection::NumberTypeNotAllowed {
+                country: country::RU,
+                number_type: NumberType::FixedLine,
+            })
+        );
+    }
+
+    #[test]
+    fn check_all_reports_each_rejected_recipient() {
+        let policy = RecipientPolicy::new().allow_countries([country::RU]);
+        let phones = [
+            phone("+79251234567"),
+            phone("+4915123456789"),
+            phone("nope"),
+        ];
+        let violation = policy.check_all(&phones).unwrap_err();
+        assert_eq!(violation.rejected.len(), 2);
+        assert_eq!(
+            violation.rejected[&phone("nope")],
+            RecipientRejection::InvalidNumber
+        );
+        assert_eq!(
+            violation.rejected[&phone("+4915123456789")],
+            RecipientRejection::CountryNotAllowed {
+                country: country::DE
+            }
+        );
+    }
+}
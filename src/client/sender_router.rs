@@ -0,0 +1,255 @@
+//! Per-recipient sender selection by recipient country.
+
+use std::collections::{BTreeMap, HashMap};
+
+use phonenumber::country;
+
+use super::SmsRuError;
+use crate::domain::resolve_phone;
+use crate::domain::{MessageText, RawPhoneNumber, SendOptions, SendSms, SendSmsResponse, SenderId};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+/// Rules that pick `SendOptions.from` for each recipient from its country.
+///
+/// Recipients are resolved like in [`super::RecipientPolicy`]: numbers are parsed in
+/// [`SenderRouter::default_region`], and digits-only numbers that are not valid there are read
+/// as international. Recipients without a matching rule (or whose country cannot be resolved)
+/// get the default sender; with no default they keep the request's own `from`.
+///
+/// [`SenderRouter::split`] turns one logical [`SendSms`] into one request per sender;
+/// [`super::SmsRuClient::send_sms_routed`] sends them and merges the responses.
+pub struct SenderRouter {
+    default_region: Option<country::Id>,
+    routes: HashMap<country::Id, SenderId>,
+    default_sender: Option<SenderId>,
+}
+
+impl SenderRouter {
+    /// Create a router with `default_sender` for recipients without a matching rule.
+    pub fn new(default_sender: Option<SenderId>) -> Self {
+        Self {
+            default_sender,
+            ..Self::default()
+        }
+    }
+
+    /// Region used for numbers written without a country code.
+    pub fn default_region(mut self, region: country::Id) -> Self {
+        self.default_region = Some(region);
+        self
+    }
+
+    /// Send to recipients in `country` from `sender`.
+    pub fn route(mut self, country: country::Id, sender: SenderId) -> Self {
+        self.routes.insert(country, sender);
+        self
+    }
+
+    /// Sender chosen for `phone`, or `None` to keep the request's `from`.
+    pub fn sender_for(&self, phone: &RawPhoneNumber) -> Option<&SenderId> {
+        resolve_phone(self.default_region, phone.raw())
+            .and_then(|parsed| parsed.parsed().country().id())
+            .and_then(|country| self.routes.get(&country))
+            .or(self.default_sender.as_ref())
+    }
+
+    /// Split `request` into one request per sender, in order of first appearance.
+    ///
+    /// Each part keeps the request's shape and options, with `from` replaced by the routed
+    /// sender.
+    pub fn split(&self, request: &SendSms) -> Vec<SendSms> {
+        match request {
+            SendSms::ToMany(to_many) => {
+                let groups = self.group(to_many.recipients().iter().map(|phone| (phone, ())));
+                groups
+                    .into_iter()
+                    .map(|(from, recipients)| {
+                        SendSms::to_many(
+                            recipients.into_iter().map(|(phone, ())| phone).collect(),
+                            to_many.msg().clone(),
+                            with_sender(to_many.options(), from),
+                        )
+                        .expect("parts of a valid request are valid")
+                    })
+                    .collect()
+            }
+            SendSms::PerRecipient(per_recipient) => {
+                let groups = self.group(per_recipient.messages().iter());
+                groups
+                    .into_iter()
+                    .map(|(from, messages)| {
+                        SendSms::per_recipient(
+                            messages
+                                .into_iter()
+                                .map(|(phone, text)| (phone, text.clone()))
+                                .collect::<BTreeMap<RawPhoneNumber, MessageText>>(),
+                            with_sender(per_recipient.options(), from),
+                        )
+                        .expect("parts of a valid request are valid")
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    fn group<'a, T>(
+        &'a self,
+        recipients: impl Iterator<Item = (&'a RawPhoneNumber, T)>,
+    ) -> Vec<Group<'a, T>> {
+        let mut groups = Vec::<Group<'a, T>>::new();
+        for (phone, value) in recipients {
+            let sender = self.sender_for(phone);
+            match groups.iter_mut().find(|(from, _)| *from == sender) {
+                Some((_, members)) => members.push((phone.clone(), value)),
+                None => groups.push((sender, vec![(phone.clone(), value)])),
+            }
+        }
+        groups
+    }
+}
+
+/// Recipients that share a sender (`None`: keep the request's `from`).
+type Group<'a, T> = (Option<&'a SenderId>, Vec<(RawPhoneNumber, T)>);
+
+fn with_sender(options: &SendOptions, sender: Option<&SenderId>) -> SendOptions {
+    let mut options = options.clone();
+    if let Some(sender) = sender {
+        options.from = Some(sender.clone());
+    }
+    options
+}
+
+#[derive(Debug)]
+/// Outcome of [`super::SmsRuClient::send_sms_routed`].
+pub struct RoutedSend {
+    /// Merged response of the parts that were sent: per-recipient results of every part
+    /// (including rejected recipients), the status of the first part and the balance reported
+    /// by the last one. `None` if no part is known to have been sent.
+    pub response: Option<SendSmsResponse>,
+    /// Part whose outcome is unknown: it failed with an error after which SMS.RU may still have
+    /// accepted it (e.g. a transport timeout). Check before sending it again.
+    pub uncertain: Option<SendSms>,
+    /// Parts that were not sent because a part failed; the failed part comes first unless it
+    /// is [`RoutedSend::uncertain`].
+    pub unsent: Vec<SendSms>,
+    /// Error of the failed part, if any.
+    pub error: Option<SmsRuError>,
+}
+
+impl RoutedSend {
+    pub(crate) fn merge(&mut self, response: SendSmsResponse) {
+        let Some(merged) = self.response.as_mut() else {
+            self.response = Some(response);
+            return;
+        };
+        if response.balance.is_some() {
+            merged.balance = response.balance;
+        }
+        merged.sms.extend(response.sms);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phone(raw: &str) -> RawPhoneNumber {
+        RawPhoneNumber::new(raw).unwrap()
+    }
+
+    fn sender(name: &str) -> SenderId {
+        SenderId::new(name).unwrap()
+    }
+
+    fn router() -> SenderRouter {
+        SenderRouter::new(Some(sender("Intl")))
+            .default_region(country::RU)
+            .route(country::RU, sender("Domestic"))
+    }
+
+    #[test]
+    fn recipients_are_routed_by_country() {
+        let router = router();
+        assert_eq!(
+            router.sender_for(&phone("89251234567")),
+            Some(&sender("Domestic"))
+        );
+        assert_eq!(
+            router.sender_for(&phone("4915123456789")),
+            Some(&sender("Intl"))
+        );
+        assert_eq!(router.sender_for(&phone("nope")), Some(&sender("Intl")));
+        assert_eq!(
+            SenderRouter::new(None).sender_for(&phone("+79251234567")),
+            None
+        );
+    }
+
+    #[test]
+    fn to_many_is_split_per_sender_in_order() {
+        let request = SendSms::to_many(
+            vec![
+                phone("+4915123456789"),
+                phone("+79251234567"),
+                phone("+79261234567"),
+            ],
+            MessageText::new("hi").unwrap(),
+            SendOptions {
+                from: Some(sender("Original")),
+                ..SendOptions::default()
+            },
+        )
+        .unwrap();
+
+        let parts = router().split(&request);
+        assert_eq!(parts.len(), 2);
+        let SendSms::ToMany(first) = &parts[0] else {
+            panic!("shape changed");
+        };
+        assert_eq!(first.recipients(), [phone("+4915123456789")]);
+        assert_eq!(first.options().from, Some(sender("Intl")));
+        let SendSms::ToMany(second) = &parts[1] else {
+            panic!("shape changed");
+        };
+        assert_eq!(
+            second.recipients(),
+            [phone("+79251234567"), phone("+79261234567")]
+        );
+        assert_eq!(second.options().from, Some(sender("Domestic")));
+    }
+
+    #[test]
+    fn per_recipient_keeps_texts_and_original_sender_without_default() {
+        let mut messages = BTreeMap::new();
+        messages.insert(phone("+4915123456789"), MessageText::new("hallo").unwrap());
+        messages.insert(phone("+79251234567"), MessageText::new("privet").unwrap());
+        let request = SendSms::per_recipient(
+            messages,
+            SendOptions {
+                from: Some(sender("Original")),
+                ..SendOptions::default()
+            },
+        )
+        .unwrap();
+
+        let router = SenderRouter::new(None).route(country::RU, sender("Domestic"));
+        let parts = router.split(&request);
+        let froms = parts
+            .iter()
+            .map(|part| match part {
+                SendSms::PerRecipient(p) => (
+                    p.options().from.clone(),
+                    p.messages().values().next().unwrap().as_str().to_owned(),
+                ),
+                SendSms::ToMany(_) => panic!("shape changed"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            froms,
+            vec![
+                (Some(sender("Original")), "hallo".to_owned()),
+                (Some(sender("Domestic")), "privet".to_owned()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,82 @@
+//! Optional local check of `SendOptions.from` against the approved senders from `my/senders`.
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::domain::SenderId;
+
+#[derive(Debug, Clone)]
+/// Approved sender names, cached for a TTL.
+///
+/// Clones share the same cache.
+pub(crate) struct ApprovedSenders {
+    ttl: Duration,
+    cache: Arc<Mutex<Option<Cached>>>,
+}
+
+#[derive(Debug)]
+struct Cached {
+    fetched_at: Instant,
+    senders: HashSet<String>,
+}
+
+impl ApprovedSenders {
+    pub(crate) fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            cache: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Whether `sender` is approved, or `None` if the cache is empty or expired.
+    pub(crate) fn contains(&self, sender: &SenderId) -> Option<bool> {
+        let cache = self.cache.lock().unwrap();
+        let cached = cache.as_ref()?;
+        if cached.fetched_at.elapsed() >= self.ttl {
+            return None;
+        }
+        Some(cached.senders.contains(sender.as_str()))
+    }
+
+    pub(crate) fn store(&self, senders: &[String]) {
+        let senders = senders
+            .iter()
+            .map(|sender| sender.trim().to_owned())
+            .collect();
+        *self.cache.lock().unwrap() = Some(Cached {
+            fetched_at: Instant::now(),
+            senders,
+        });
+    }
+
+    /// Forget the cached list, e.g. after SMS.RU disagreed with it.
+    pub(crate) fn invalidate(&self) {
+        *self.cache.lock().unwrap() = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_expires_after_ttl() {
+        let sender = SenderId::new("Shop").unwrap();
+
+        let senders = ApprovedSenders::new(Duration::from_secs(60));
+        assert_eq!(senders.contains(&sender), None);
+        senders.store(&["Shop ".to_owned(), "Other".to_owned()]);
+        assert_eq!(senders.contains(&sender), Some(true));
+        assert_eq!(
+            senders.contains(&SenderId::new("Unknown").unwrap()),
+            Some(false)
+        );
+        senders.invalidate();
+        assert_eq!(senders.contains(&sender), None);
+
+        let expired = ApprovedSenders::new(Duration::ZERO);
+        expired.store(&["Shop".to_owned()]);
+        assert_eq!(expired.contains(&sender), None);
+    }
+}
@@ -0,0 +1,220 @@
+//! `tower::Service` implementations for [`SmsRuClient`] (feature `tower`).
+//!
+//! Every input-based method is a typed service (`Service<SendSms>`, `Service<CheckCost>`, ...).
+//! [`SmsRuRequest`] covers all methods, including the no-arg ones, behind one
+//! `Service<SmsRuRequest>` for stacks that route everything through a single service.
+
+use std::task::{Context, Poll};
+
+use tower_service::Service;
+
+use super::{BoxFuture, SmsRuClient, SmsRuError};
+use crate::domain::{
+    AddCallback, AddStoplistEntry, BalanceResponse, CallbacksResponse, CheckCallAuthStatus,
+    CheckCallAuthStatusResponse, CheckCost, CheckCostResponse, CheckStatus, CheckStatusResponse,
+    FreeUsageResponse, LimitUsageResponse, RemoveCallback, RemoveStoplistEntry, SendSms,
+    SendSmsResponse, SendersResponse, StartCallAuth, StartCallAuthResponse, StatusOnlyResponse,
+    StoplistResponse,
+};
+
+/// Future returned by the [`SmsRuClient`] services.
+pub type SmsRuFuture<T> = BoxFuture<'static, Result<T, SmsRuError>>;
+
+#[derive(Debug, Clone)]
+/// One SMS.RU method call, for `Service<SmsRuRequest>`.
+pub enum SmsRuRequest {
+    SendSms(SendSms),
+    CheckCost(CheckCost),
+    CheckStatus(CheckStatus),
+    StartCallAuth(StartCallAuth),
+    CheckCallAuthStatus(CheckCallAuthStatus),
+    CheckAuth,
+    GetBalance,
+    GetFreeUsage,
+    GetLimitUsage,
+    GetSenders,
+    AddStoplistEntry(AddStoplistEntry),
+    RemoveStoplistEntry(RemoveStoplistEntry),
+    GetStoplist,
+    AddCallback(AddCallback),
+    RemoveCallback(RemoveCallback),
+    GetCallbacks,
+}
+
+#[derive(Debug, Clone)]
+/// Response to an [`SmsRuRequest`]; the variant matches the request.
+pub enum SmsRuResponse {
+    SendSms(SendSmsResponse),
+    CheckCost(CheckCostResponse),
+    CheckStatus(CheckStatusResponse),
+    StartCallAuth(StartCallAuthResponse),
+    CheckCallAuthStatus(CheckCallAuthStatusResponse),
+    CheckAuth(StatusOnlyResponse),
+    GetBalance(BalanceResponse),
+    GetFreeUsage(FreeUsageResponse),
+    GetLimitUsage(LimitUsageResponse),
+    GetSenders(SendersResponse),
+    AddStoplistEntry(StatusOnlyResponse),
+    RemoveStoplistEntry(StatusOnlyResponse),
+    GetStoplist(StoplistResponse),
+    AddCallback(CallbacksResponse),
+    RemoveCallback(CallbacksResponse),
+    GetCallbacks(CallbacksResponse),
+}
+
+macro_rules! typed_service {
+    ($($request:ident => $response:ident, $method:ident;)+) => {
+        $(
+            impl Service<$request> for SmsRuClient {
+                type Response = $response;
+                type Error = SmsRuError;
+                type Future = SmsRuFuture<$response>;
+
+                fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), SmsRuError>> {
+                    Poll::Ready(Ok(()))
+                }
+
+                fn call(&mut self, request: $request) -> Self::Future {
+                    let client = self.clone();
+                    Box::pin(async move { client.$method(request).await })
+                }
+            }
+
+            impl From<$request> for SmsRuRequest {
+                fn from(request: $request) -> Self {
+                    Self::$request(request)
+                }
+            }
+        )+
+    };
+}
+
+typed_service! {
+    SendSms => SendSmsResponse, send_sms;
+    CheckCost => CheckCostResponse, check_cost;
+    CheckStatus => CheckStatusResponse, check_status;
+    StartCallAuth => StartCallAuthResponse, start_call_auth;
+    CheckCallAuthStatus => CheckCallAuthStatusResponse, check_call_auth_status;
+    AddStoplistEntry => StatusOnlyResponse, add_stoplist_entry;
+    RemoveStoplistEntry => StatusOnlyResponse, remove_stoplist_entry;
+    AddCallback => CallbacksResponse, add_callback;
+    RemoveCallback => CallbacksResponse, remove_callback;
+}
+
+impl Service<SmsRuRequest> for SmsRuClient {
+    type Response = SmsRuResponse;
+    type Error = SmsRuError;
+    type Future = SmsRuFuture<SmsRuResponse>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), SmsRuError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: SmsRuRequest) -> Self::Future {
+        let client = self.clone();
+        Box::pin(async move {
+            Ok(match request {
+                SmsRuRequest::SendSms(r) => SmsRuResponse::SendSms(client.send_sms(r).await?),
+                SmsRuRequest::CheckCost(r) => SmsRuResponse::CheckCost(client.check_cost(r).await?),
+                SmsRuRequest::CheckStatus(r) => {
+                    SmsRuResponse::CheckStatus(client.check_status(r).await?)
+                }
+                SmsRuRequest::StartCallAuth(r) => {
+                    SmsRuResponse::StartCallAuth(client.start_call_auth(r).await?)
+                }
+                SmsRuRequest::CheckCallAuthStatus(r) => {
+                    SmsRuResponse::CheckCallAuthStatus(client.check_call_auth_status(r).await?)
+                }
+                SmsRuRequest::CheckAuth => SmsRuResponse::CheckAuth(client.check_auth().await?),
+                SmsRuRequest::GetBalance => SmsRuResponse::GetBalance(client.get_balance().await?),
+                SmsRuRequest::GetFreeUsage => {
+                    SmsRuResponse::GetFreeUsage(client.get_free_usage().await?)
+                }
+                SmsRuRequest::GetLimitUsage => {
+                    SmsRuResponse::GetLimitUsage(client.get_limit_usage().await?)
+                }
+                SmsRuRequest::GetSenders => SmsRuResponse::GetSenders(client.get_senders().await?),
+                SmsRuRequest::AddStoplistEntry(r) => {
+                    SmsRuResponse::AddStoplistEntry(client.add_stoplist_entry(r).await?)
+                }
+                SmsRuRequest::RemoveStoplistEntry(r) => {
+                    SmsRuResponse::RemoveStoplistEntry(client.remove_stoplist_entry(r).await?)
+                }
+                SmsRuRequest::GetStoplist => {
+                    SmsRuResponse::GetStoplist(client.get_stoplist().await?)
+                }
+                SmsRuRequest::AddCallback(r) => {
+                    SmsRuResponse::AddCallback(client.add_callback(r).await?)
+                }
+                SmsRuRequest::RemoveCallback(r) => {
+                    SmsRuResponse::RemoveCallback(client.remove_callback(r).await?)
+                }
+                SmsRuRequest::GetCallbacks => {
+                    SmsRuResponse::GetCallbacks(client.get_callbacks().await?)
+                }
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Auth;
+    use crate::client::test_support::{FakeTransport, make_client};
+    use crate::domain::{CheckStatus, SmsId, Status};
+
+    fn assert_service<S: Service<R, Error = SmsRuError> + Clone + Send + 'static, R>(_: &S)
+    where
+        S::Future: Send + 'static,
+    {
+    }
+
+    #[tokio::test]
+    async fn typed_service_calls_the_matching_method() {
+        let transport = FakeTransport::new(
+            200,
+            r#"{"status":"OK","status_code":100,"sms":{"000000-000001":{"status":"OK","status_code":103}}}"#,
+        );
+        let mut client = make_client(Auth::api_id("key").unwrap(), transport.clone());
+        assert_service::<_, CheckStatus>(&client);
+
+        std::future::poll_fn(|cx| Service::<CheckStatus>::poll_ready(&mut client, cx))
+            .await
+            .unwrap();
+        let response = client
+            .call(CheckStatus::one(SmsId::new("000000-000001").unwrap()))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status, Status::Ok);
+        let (url, _) = transport.last_request();
+        assert_eq!(url.as_deref(), Some("https://example.invalid/sms/status"));
+    }
+
+    #[tokio::test]
+    async fn enum_service_dispatches_no_arg_methods() {
+        let transport =
+            FakeTransport::new(200, r#"{"status":"OK","status_code":100,"balance":4.5}"#);
+        let mut client = make_client(Auth::api_id("key").unwrap(), transport.clone());
+        assert_service::<_, SmsRuRequest>(&client);
+
+        let response = client.call(SmsRuRequest::GetBalance).await.unwrap();
+
+        assert!(
+            matches!(response, SmsRuResponse::GetBalance(b) if b.balance.as_deref() == Some("4.5"))
+        );
+        let (url, _) = transport.last_request();
+        assert_eq!(url.as_deref(), Some("https://example.invalid/my/balance"));
+    }
+
+    #[tokio::test]
+    async fn service_errors_are_sms_ru_errors() {
+        let transport = FakeTransport::new(200, r#"{"status":"ERROR","status_code":200}"#);
+        let mut client = make_client(Auth::api_id("key").unwrap(), transport);
+
+        let err = client.call(SmsRuRequest::CheckAuth).await.unwrap_err();
+
+        assert!(matches!(err, SmsRuError::Api { .. }));
+    }
+}
@@ -0,0 +1,262 @@
+//! Optional pre-send spending checks for [`super::SmsRuClient::send_sms`].
+
+use std::collections::BTreeMap;
+
+use crate::domain::{CheckCostResponse, RawPhoneNumber, SmsCostResult, Status};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+/// Spending limits checked before every `send_sms` call.
+///
+/// When configured through [`super::SmsRuClientBuilder::spend_guard`], the client first runs the
+/// `sms/cost` request matching the send (and `my/balance` if a balance share is set) and
+/// refuses the send with [`super::SmsRuError::SpendGuard`] when a limit is exceeded or any
+/// recipient is rejected by the cost check.
+pub struct SpendGuard {
+    max_total_cost: Option<f64>,
+    max_balance_share: Option<f64>,
+}
+
+impl SpendGuard {
+    /// Create a guard that only rejects sends with non-OK per-recipient cost results.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Refuse sends whose total cost exceeds `cap` (in account currency).
+    pub fn max_total_cost(mut self, cap: f64) -> Self {
+        self.max_total_cost = Some(cap);
+        self
+    }
+
+    /// Refuse sends whose total cost exceeds `share` of the current balance.
+    ///
+    /// `share` is a fraction in `0.0..=1.0`; out-of-range values are clamped and `NaN` is
+    /// treated as `0.0`.
+    pub fn max_balance_share(mut self, share: f64) -> Self {
+        let share = if share.is_nan() {
+            0.0
+        } else {
+            share.clamp(0.0, 1.0)
+        };
+        self.max_balance_share = Some(share);
+        self
+    }
+
+    /// Configured per-request cost cap.
+    pub fn total_cost_cap(&self) -> Option<f64> {
+        self.max_total_cost
+    }
+
+    /// Configured balance share.
+    pub fn balance_share(&self) -> Option<f64> {
+        self.max_balance_share
+    }
+
+    pub(crate) fn requires_balance(&self) -> bool {
+        self.max_balance_share.is_some()
+    }
+
+    pub(crate) fn check(
+        &self,
+        cost: &CheckCostResponse,
+        balance: Option<&str>,
+    ) -> Result<(), SpendGuardViolation> {
+        let rejected = cost
+            .sms
+            .iter()
+            .filter(|(_, result)| result.status != Status::Ok)
+            .map(|(phone, result)| (phone.clone(), result.clone()))
+            .collect::<BTreeMap<_, _>>();
+        if !rejected.is_empty() {
+            return Err(SpendGuardViolation::RecipientsRejected {
+                recipients: rejected,
+            });
+        }
+
+        if self.max_total_cost.is_none() && self.max_balance_share.is_none() {
+            return Ok(());
+        }
+
+        let total_cost = total_cost(cost)?;
+
+        if let Some(cap) = self.max_total_cost {
+            if total_cost > cap {
+                return Err(SpendGuardViolation::CostExceedsCap { total_cost, cap });
+            }
+        }
+
+        if let Some(max_share) = self.max_balance_share {
+            let balance = parse_amount("balance", balance)?;
+            if total_cost > balance * max_share {
+                return Err(SpendGuardViolation::CostExceedsBalanceShare {
+                    total_cost,
+                    balance,
+                    max_share,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+/// Reason a send was refused by [`SpendGuard`].
+pub enum SpendGuardViolation {
+    /// The cost check returned a non-OK result for these recipients.
+    #[error("{} recipient(s) rejected by cost check", .recipients.len())]
+    RecipientsRejected {
+        recipients: BTreeMap<RawPhoneNumber, SmsCostResult>,
+    },
+    /// The total cost is above the configured per-request cap.
+    #[error("total cost {total_cost} exceeds cap {cap}")]
+    CostExceedsCap { total_cost: f64, cap: f64 },
+    /// The total cost is above the configured share of the balance.
+    #[error("total cost {total_cost} exceeds {max_share} of balance {balance}")]
+    CostExceedsBalanceShare {
+        total_cost: f64,
+        balance: f64,
+        max_share: f64,
+    },
+    /// A cost or balance value was missing or not a number, so limits could not be checked.
+    #[error("cannot read {field} amount: {value:?}")]
+    UnreadableAmount {
+        field: &'static str,
+        value: Option<String>,
+    },
+}
+
+fn total_cost(cost: &CheckCostResponse) -> Result<f64, SpendGuardViolation> {
+    if cost.total_cost.is_some() {
+        return parse_amount("total_cost", cost.total_cost.as_deref());
+    }
+
+    cost.sms
+        .values()
+        .map(|result| parse_amount("cost", result.cost.as_deref()))
+        .sum()
+}
+
+fn parse_amount(field: &'static str, value: Option<&str>) -> Result<f64, SpendGuardViolation> {
+    value
+        .and_then(|value| value.trim().parse::<f64>().ok())
+        .filter(|amount| amount.is_finite())
+        .ok_or_else(|| SpendGuardViolation::UnreadableAmount {
+            field,
+            value: value.map(str::to_owned),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::StatusCode;
+
+    fn cost_response(
+        total_cost: Option<&str>,
+        results: &[(&str, Status, &str)],
+    ) -> CheckCostResponse {
+        CheckCostResponse {
+            status: Status::Ok,
+            status_code: StatusCode::new(100),
+            status_text: None,
+            total_cost: total_cost.map(str::to_owned),
+            total_sms: None,
+            sms: results
+                .iter()
+                .map(|(phone, status, cost)| {
+                    (
+                        RawPhoneNumber::new(*phone).unwrap(),
+                        SmsCostResult {
+                            status: *status,
+                            status_code: StatusCode::new(if *status == Status::Ok {
+                                100
+                            } else {
+                                207
+                            }),
+                            status_text: None,
+                            cost: Some((*cost).to_owned()),
+                            sms: Some(1),
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn rejects_non_ok_recipients_even_without_limits() {
+        let cost = cost_response(
+            Some("1.00"),
+            &[
+                ("79250000001", Status::Ok, "1.00"),
+                ("79250000002", Status::Error, "0"),
+            ],
+        );
+        let err = SpendGuard::new().check(&cost, None).unwrap_err();
+        match err {
+            SpendGuardViolation::RecipientsRejected { recipients } => {
+                assert_eq!(recipients.len(), 1);
+                assert!(recipients.contains_key(&RawPhoneNumber::new("79250000002").unwrap()));
+            }
+            other => panic!("unexpected violation: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn enforces_total_cost_cap_and_sums_recipients_without_total() {
+        let cost = cost_response(
+            None,
+            &[
+                ("79250000001", Status::Ok, "3.50"),
+                ("79250000002", Status::Ok, "3.50"),
+            ],
+        );
+        assert!(
+            SpendGuard::new()
+                .max_total_cost(7.0)
+                .check(&cost, None)
+                .is_ok()
+        );
+        assert_eq!(
+            SpendGuard::new().max_total_cost(5.0).check(&cost, None),
+            Err(SpendGuardViolation::CostExceedsCap {
+                total_cost: 7.0,
+                cap: 5.0
+            })
+        );
+    }
+
+    #[test]
+    fn enforces_balance_share() {
+        let cost = cost_response(Some("30"), &[("79250000001", Status::Ok, "30")]);
+        let guard = SpendGuard::new().max_balance_share(0.25);
+        assert!(guard.requires_balance());
+        assert!(guard.check(&cost, Some("200.00")).is_ok());
+        assert!(matches!(
+            guard.check(&cost, Some("100.00")),
+            Err(SpendGuardViolation::CostExceedsBalanceShare { .. })
+        ));
+        assert!(matches!(
+            guard.check(&cost, None),
+            Err(SpendGuardViolation::UnreadableAmount {
+                field: "balance",
+                value: None
+            })
+        ));
+    }
+
+    #[test]
+    fn balance_share_is_clamped() {
+        assert_eq!(
+            SpendGuard::new().max_balance_share(2.0).balance_share(),
+            Some(1.0)
+        );
+        assert_eq!(
+            SpendGuard::new()
+                .max_balance_share(f64::NAN)
+                .balance_share(),
+            Some(0.0)
+        );
+    }
+}
@@ -0,0 +1,142 @@
+//! Fake transport and client constructors shared by unit tests.
+
+use std::collections::VecDeque;
+use std::error::Error as StdError;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use super::{Auth, BoxFuture, HttpResponse, HttpTransport, SmsRuClient};
+
+#[derive(Debug, Clone)]
+pub(crate) struct FakeTransport {
+    state: Arc<Mutex<FakeTransportState>>,
+}
+
+#[derive(Debug)]
+pub(crate) struct FakeTransportState {
+    last_url: Option<String>,
+    last_params: Vec<(String, String)>,
+    response_status: u16,
+    response_body: String,
+    /// Queued responses; `None` fails the request like a timeout.
+    queued: VecDeque<Option<(u16, String)>>,
+    urls: Vec<String>,
+    refused: Vec<String>,
+}
+
+impl FakeTransport {
+    pub(crate) fn new(response_status: u16, response_body: impl Into<String>) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(FakeTransportState {
+                last_url: None,
+                last_params: Vec::new(),
+                response_status,
+                response_body: response_body.into(),
+                queued: VecDeque::new(),
+                urls: Vec::new(),
+                refused: Vec::new(),
+            })),
+        }
+    }
+
+    /// Answer requests with `responses` in order, then fall back to `200 {}`.
+    pub(crate) fn sequence(responses: Vec<(u16, &str)>) -> Self {
+        let transport = Self::new(200, "{}");
+        transport.state.lock().unwrap().queued = responses
+            .into_iter()
+            .map(|(status, body)| Some((status, body.to_owned())))
+            .collect();
+        transport
+    }
+
+    /// Fail the request after the queued responses as if it timed out.
+    pub(crate) fn queue_timeout(&self) {
+        self.state.lock().unwrap().queued.push_back(None);
+    }
+
+    pub(crate) fn last_request(&self) -> (Option<String>, Vec<(String, String)>) {
+        let state = self.state.lock().unwrap();
+        (state.last_url.clone(), state.last_params.clone())
+    }
+
+    /// Fail requests to URLs starting with `prefix` as if the connection was refused.
+    pub(crate) fn refuse(&self, prefix: &str) {
+        self.state.lock().unwrap().refused.push(prefix.to_owned());
+    }
+
+    pub(crate) fn requested_urls(&self) -> Vec<String> {
+        self.state.lock().unwrap().urls.clone()
+    }
+}
+
+impl HttpTransport for FakeTransport {
+    fn post_form<'a>(
+        &'a self,
+        url: &'a str,
+        params: Vec<(String, String)>,
+    ) -> BoxFuture<'a, Result<HttpResponse, Box<dyn StdError + Send + Sync>>> {
+        Box::pin(async move {
+            let (status, body) = {
+                let mut state = self.state.lock().unwrap();
+                state.last_url = Some(url.to_owned());
+                state.last_params = params;
+                state.urls.push(url.to_owned());
+                if state.refused.iter().any(|prefix| url.starts_with(prefix)) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::ConnectionRefused,
+                        "connection refused",
+                    )
+                    .into());
+                }
+                match state.queued.pop_front() {
+                    Some(Some(queued)) => queued,
+                    Some(None) => {
+                        return Err(io::Error::new(io::ErrorKind::TimedOut, "timed out").into());
+                    }
+                    None => (state.response_status, state.response_body.clone()),
+                }
+            };
+            Ok(HttpResponse { status, body })
+        })
+    }
+}
+
+pub(crate) fn assert_param(params: &[(String, String)], key: &str, value: &str) {
+    assert!(
+        params.iter().any(|(k, v)| k == key && v == value),
+        "missing param {key}={value}; got: {params:?}"
+    );
+}
+
+pub(crate) fn make_client(auth: Auth, transport: FakeTransport) -> SmsRuClient {
+    SmsRuClient {
+        auth,
+        send_endpoint: "https://example.invalid/sms/send".to_owned(),
+        cost_endpoint: "https://example.invalid/sms/cost".to_owned(),
+        status_endpoint: "https://example.invalid/sms/status".to_owned(),
+        callcheck_add_endpoint: "https://example.invalid/callcheck/add".to_owned(),
+        callcheck_status_endpoint: "https://example.invalid/callcheck/status".to_owned(),
+        auth_check_endpoint: "https://example.invalid/auth/check".to_owned(),
+        my_balance_endpoint: "https://example.invalid/my/balance".to_owned(),
+        my_free_endpoint: "https://example.invalid/my/free".to_owned(),
+        my_limit_endpoint: "https://example.invalid/my/limit".to_owned(),
+        my_senders_endpoint: "https://example.invalid/my/senders".to_owned(),
+        stoplist_add_endpoint: "https://example.invalid/stoplist/add".to_owned(),
+        stoplist_del_endpoint: "https://example.invalid/stoplist/del".to_owned(),
+        stoplist_get_endpoint: "https://example.invalid/stoplist/get".to_owned(),
+        callback_add_endpoint: "https://example.invalid/callback/add".to_owned(),
+        callback_del_endpoint: "https://example.invalid/callback/del".to_owned(),
+        callback_get_endpoint: "https://example.invalid/callback/get".to_owned(),
+        spend_guard: None,
+        recipient_policy: None,
+        abuse_guard: None,
+        approved_senders: None,
+        message_limits: None,
+        daily_limit: None,
+        idempotency: super::idempotency::Idempotency::default(),
+        fail_on_recipient_errors: false,
+        normalization: None,
+        mirrors: None,
+        http: Arc::new(transport),
+    }
+}
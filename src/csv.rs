@@ -0,0 +1,176 @@
+//! Minimal RFC 4180 CSV support: `,` separators, `"` quoting with `""` escapes.
+
+use std::io;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// One CSV record and the 1-based line it starts on.
+pub(crate) struct Record {
+    pub(crate) line: usize,
+    pub(crate) fields: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Malformed CSV input.
+pub(crate) struct CsvSyntaxError {
+    pub(crate) line: usize,
+    pub(crate) reason: &'static str,
+}
+
+/// Split `input` into records, skipping blank lines and a leading byte-order mark.
+pub(crate) fn parse(input: &str) -> Result<Vec<Record>, CsvSyntaxError> {
+    let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+    let mut records = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut line = 1;
+    let mut record_line = 1;
+    let mut in_quotes = false;
+    // Whether the current field started with a quote and the closing quote was seen.
+    let mut quoted = false;
+    let mut chars = input.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        if in_quotes {
+            match ch {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => {
+                    in_quotes = false;
+                    quoted = true;
+                }
+                '\n' => {
+                    line += 1;
+                    field.push('\n');
+                }
+                _ => field.push(ch),
+            }
+            continue;
+        }
+
+        match ch {
+            '"' if field.is_empty() && !quoted => in_quotes = true,
+            '"' => {
+                return Err(CsvSyntaxError {
+                    line,
+                    reason: "unexpected `\"` inside an unquoted field",
+                });
+            }
+            ',' => {
+                fields.push(std::mem::take(&mut field));
+                quoted = false;
+            }
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                fields.push(std::mem::take(&mut field));
+                quoted = false;
+                push_record(&mut records, record_line, std::mem::take(&mut fields));
+                line += 1;
+                record_line = line;
+            }
+            _ if quoted => {
+                return Err(CsvSyntaxError {
+                    line,
+                    reason: "unexpected character after a closing `\"`",
+                });
+            }
+            _ => field.push(ch),
+        }
+    }
+
+    if in_quotes {
+        return Err(CsvSyntaxError {
+            line: record_line,
+            reason: "unclosed quoted field",
+        });
+    }
+    if !field.is_empty() || quoted || !fields.is_empty() {
+        fields.push(field);
+        push_record(&mut records, record_line, fields);
+    }
+    Ok(records)
+}
+
+/// Write one record terminated by CRLF, quoting fields that need it.
+pub(crate) fn write_record<W: io::Write>(out: &mut W, fields: &[&str]) -> io::Result<()> {
+    for (index, field) in fields.iter().enumerate() {
+        if index > 0 {
+            out.write_all(b",")?;
+        }
+        if field.contains([',', '"', '\r', '\n']) {
+            write!(out, "\"{}\"", field.replace('"', "\"\""))?;
+        } else {
+            out.write_all(field.as_bytes())?;
+        }
+    }
+    out.write_all(b"\r\n")
+}
+
+fn push_record(records: &mut Vec<Record>, line: usize, fields: Vec<String>) {
+    let blank = fields.len() == 1 && fields[0].is_empty();
+    if !blank {
+        records.push(Record { line, fields });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(records: &[Record]) -> Vec<Vec<&str>> {
+        records
+            .iter()
+            .map(|record| record.fields.iter().map(String::as_str).collect())
+            .collect()
+    }
+
+    #[test]
+    fn parses_quotes_escapes_and_line_endings() {
+        let input = "\u{feff}phone,name\r\n79250000001,\"Ann, \"\"A\"\"\"\r\n\r\n79250000002,\"two\nlines\"\n";
+        let records = parse(input).unwrap();
+        assert_eq!(
+            fields(&records),
+            vec![
+                vec!["phone", "name"],
+                vec!["79250000001", "Ann, \"A\""],
+                vec!["79250000002", "two\nlines"],
+            ]
+        );
+        assert_eq!(
+            records.iter().map(|r| r.line).collect::<Vec<_>>(),
+            vec![1, 2, 4]
+        );
+    }
+
+    #[test]
+    fn keeps_trailing_empty_fields_without_final_newline() {
+        let records = parse("a,b\n1,").unwrap();
+        assert_eq!(fields(&records), vec![vec!["a", "b"], vec!["1", ""]]);
+    }
+
+    #[test]
+    fn written_records_parse_back() {
+        let mut out = Vec::new();
+        write_record(&mut out, &["plain", "a,b", "say \"hi\"", "two\nlines", ""]).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(text, "plain,\"a,b\",\"say \"\"hi\"\"\",\"two\nlines\",\r\n");
+        assert_eq!(
+            fields(&parse(&text).unwrap()),
+            vec![vec!["plain", "a,b", "say \"hi\"", "two\nlines", ""]]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_quotes() {
+        assert_eq!(
+            parse("a\n\"open").unwrap_err(),
+            CsvSyntaxError {
+                line: 2,
+                reason: "unclosed quoted field"
+            }
+        );
+        assert_eq!(parse("a\"b").unwrap_err().line, 1);
+        assert_eq!(parse("\"a\"b").unwrap_err().line, 1);
+    }
+}
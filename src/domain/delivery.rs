@@ -0,0 +1,185 @@
+//! Delivery lifecycle derived from `sms/status` codes.
+
+use std::fmt;
+
+use crate::domain::response::SmsStatusResult;
+use crate::domain::value::{KnownStatusCode, StatusCode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Where a message is in its delivery lifecycle.
+pub enum DeliveryState {
+    /// Accepted by SMS.RU and waiting to be sent (`100`).
+    Queued,
+    /// Handed over to the operator or on its way to the handset (`101`, `102`).
+    InTransit,
+    /// Delivered to the handset (`103`).
+    Delivered,
+    /// Read by the recipient (`110`).
+    Read,
+    /// Delivery failed for good (`104`-`108`, `150`).
+    Failed(DeliveryFailure),
+    /// SMS.RU does not know the message id (`-1`).
+    NotFound,
+    /// The code is not a delivery status known to this crate.
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Why a message was not delivered.
+pub enum DeliveryFailure {
+    /// The message TTL expired before the handset became reachable (`104`).
+    TtlExpired,
+    /// The operator deleted the message (`105`).
+    DeletedByOperator,
+    /// The handset failed to receive the message (`106`).
+    PhoneFailure,
+    /// Not delivered for an unknown reason (`107`).
+    Unknown,
+    /// The message was rejected (`108`).
+    Rejected,
+    /// There is no route to the number (`150`).
+    NoRoute,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A delivery state change that would move backwards out of a terminal state.
+pub struct DeliveryTransitionError {
+    /// Current state.
+    pub from: DeliveryState,
+    /// Rejected next state.
+    pub to: DeliveryState,
+}
+
+impl DeliveryState {
+    /// Map a status code to a delivery state.
+    pub fn from_status_code(code: StatusCode) -> Self {
+        let Some(known) = code.known_kind() else {
+            return Self::Unknown;
+        };
+        match known {
+            KnownStatusCode::MessageNotFound => Self::NotFound,
+            KnownStatusCode::RequestOkOrQueued => Self::Queued,
+            KnownStatusCode::BeingDeliveredToOperator | KnownStatusCode::SentInTransit => {
+                Self::InTransit
+            }
+            KnownStatusCode::Delivered => Self::Delivered,
+            KnownStatusCode::Read => Self::Read,
+            KnownStatusCode::NotDeliveredTtlExpired => Self::Failed(DeliveryFailure::TtlExpired),
+            KnownStatusCode::NotDeliveredDeletedByOperator => {
+                Self::Failed(DeliveryFailure::DeletedByOperator)
+            }
+            KnownStatusCode::NotDeliveredPhoneFailure => {
+                Self::Failed(DeliveryFailure::PhoneFailure)
+            }
+            KnownStatusCode::NotDeliveredUnknown => Self::Failed(DeliveryFailure::Unknown),
+            KnownStatusCode::NotDeliveredRejected => Self::Failed(DeliveryFailure::Rejected),
+            KnownStatusCode::NotDeliveredNoRoute => Self::Failed(DeliveryFailure::NoRoute),
+            _ => Self::Unknown,
+        }
+    }
+
+    /// Whether the state is final: delivered, read, or failed.
+    ///
+    /// A delivered message can still become [`DeliveryState::Read`].
+    pub fn is_terminal(self) -> bool {
+        matches!(self, Self::Delivered | Self::Read | Self::Failed(_))
+    }
+
+    /// Whether moving from `self` to `next` is a valid lifecycle step.
+    ///
+    /// Staying in the same state is always allowed. Terminal states only allow
+    /// `Delivered -> Read`, and `InTransit` cannot go back to `Queued`. `NotFound` and
+    /// `Unknown` may move to any state.
+    pub fn can_transition_to(self, next: Self) -> bool {
+        if self == next {
+            return true;
+        }
+        match self {
+            Self::Delivered => next == Self::Read,
+            Self::Read | Self::Failed(_) => false,
+            Self::InTransit => next != Self::Queued,
+            Self::Queued | Self::NotFound | Self::Unknown => true,
+        }
+    }
+
+    /// Move to `next` if [`DeliveryState::can_transition_to`] allows it.
+    pub fn transition_to(self, next: Self) -> Result<Self, DeliveryTransitionError> {
+        if self.can_transition_to(next) {
+            Ok(next)
+        } else {
+            Err(DeliveryTransitionError {
+                from: self,
+                to: next,
+            })
+        }
+    }
+}
+
+impl StatusCode {
+    /// Delivery state for this code; see [`DeliveryState::from_status_code`].
+    pub fn delivery_state(self) -> DeliveryState {
+        DeliveryState::from_status_code(self)
+    }
+}
+
+impl SmsStatusResult {
+    /// Delivery state reported for this message.
+    pub fn delivery_state(&self) -> DeliveryState {
+        self.status_code.delivery_state()
+    }
+}
+
+impl fmt::Display for DeliveryTransitionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid delivery state transition from {:?} to {:?}",
+            self.from, self.to
+        )
+    }
+}
+
+impl std::error::Error for DeliveryTransitionError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_codes_map_to_delivery_states() {
+        let state = |code| StatusCode::new(code).delivery_state();
+        assert_eq!(state(-1), DeliveryState::NotFound);
+        assert_eq!(state(100), DeliveryState::Queued);
+        assert_eq!(state(102), DeliveryState::InTransit);
+        assert_eq!(state(103), DeliveryState::Delivered);
+        assert_eq!(state(110), DeliveryState::Read);
+        assert_eq!(
+            state(104),
+            DeliveryState::Failed(DeliveryFailure::TtlExpired)
+        );
+        assert_eq!(state(150), DeliveryState::Failed(DeliveryFailure::NoRoute));
+        assert_eq!(state(202), DeliveryState::Unknown);
+        assert_eq!(state(777), DeliveryState::Unknown);
+        assert!(state(103).is_terminal());
+        assert!(!state(101).is_terminal());
+    }
+
+    #[test]
+    fn terminal_states_do_not_go_backwards() {
+        use DeliveryState::*;
+
+        assert_eq!(Queued.transition_to(InTransit), Ok(InTransit));
+        assert_eq!(Delivered.transition_to(Read), Ok(Read));
+        assert_eq!(Read.transition_to(Read), Ok(Read));
+        assert!(InTransit.transition_to(Queued).is_err());
+        assert_eq!(
+            Delivered.transition_to(InTransit),
+            Err(DeliveryTransitionError {
+                from: Delivered,
+                to: InTransit
+            })
+        );
+        assert!(!Failed(DeliveryFailure::Rejected).can_transition_to(Delivered));
+        assert!(Unknown.can_transition_to(Delivered));
+    }
+}
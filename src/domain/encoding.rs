@@ -0,0 +1,210 @@
+//! SMS length accounting: GSM-7 / UCS-2 detection and segment counting.
+
+use std::fmt;
+
+use crate::domain::validation::ValidationError;
+
+/// Characters from the GSM 03.38 basic set (one septet each).
+const GSM7_BASIC: &str = "@£$¥èéùìòÇ\nØø\rÅåΔ_ΦΓΛΩΠΨΣΘΞÆæßÉ !\"#¤%&'()*+,-./0123456789:;<=>?\
+¡ABCDEFGHIJKLMNOPQRSTUVWXYZÄÖÑÜ§¿abcdefghijklmnopqrstuvwxyzäöñüà";
+/// Characters from the GSM 03.38 extension table (escape + char, two septets each).
+const GSM7_EXTENSION: &str = "^{}\\[~]|€\u{000C}";
+
+const GSM7_SINGLE_SEGMENT: usize = 160;
+const GSM7_MULTI_SEGMENT: usize = 153;
+const UCS2_SINGLE_SEGMENT: usize = 70;
+const UCS2_MULTI_SEGMENT: usize = 67;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Character encoding an SMS will be sent with.
+pub enum SmsEncoding {
+    /// GSM 03.38 7-bit alphabet (160 characters per single SMS).
+    Gsm7,
+    /// UCS-2 / UTF-16 (70 characters per single SMS), used for Cyrillic and other text.
+    Ucs2,
+}
+
+impl fmt::Display for SmsEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Gsm7 => "GSM-7",
+            Self::Ucs2 => "UCS-2",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Measured length of a message text.
+pub struct MessageLength {
+    /// Encoding required to send the text.
+    pub encoding: SmsEncoding,
+    /// Number of characters (Unicode scalar values).
+    pub chars: usize,
+    /// Encoded length: GSM-7 septets or UTF-16 code units.
+    pub units: usize,
+    /// Number of SMS segments the text is split into.
+    pub segments: u32,
+}
+
+impl MessageLength {
+    /// Measure `text` the way a handset or SMSC would split it into segments.
+    pub fn measure(text: &str) -> Self {
+        let encoding = if text.chars().all(is_gsm7) {
+            SmsEncoding::Gsm7
+        } else {
+            SmsEncoding::Ucs2
+        };
+        let (single, multi) = match encoding {
+            SmsEncoding::Gsm7 => (GSM7_SINGLE_SEGMENT, GSM7_MULTI_SEGMENT),
+            SmsEncoding::Ucs2 => (UCS2_SINGLE_SEGMENT, UCS2_MULTI_SEGMENT),
+        };
+
+        let unit_len = |ch: char| match encoding {
+            SmsEncoding::Gsm7 if GSM7_EXTENSION.contains(ch) => 2,
+            SmsEncoding::Gsm7 => 1,
+            SmsEncoding::Ucs2 => ch.len_utf16(),
+        };
+        let units = text.chars().map(unit_len).sum::<usize>();
+
+        let segments = if units <= single {
+            1
+        } else {
+            // Escape sequences and surrogate pairs are never split across segments.
+            let mut segments = 1;
+            let mut used = 0;
+            for len in text.chars().map(unit_len) {
+                if used + len > multi {
+                    segments += 1;
+                    used = 0;
+                }
+                used += len;
+            }
+            segments
+        };
+
+        Self {
+            encoding,
+            chars: text.chars().count(),
+            units,
+            segments,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+/// Upper bounds for a message text, checked offline before sending.
+///
+/// Both limits are unset by default. Characters are counted as Unicode scalar values; segments
+/// follow [`MessageLength::measure`], so the same number of characters allows fewer segments'
+/// worth of Cyrillic (UCS-2) text than Latin (GSM-7) text.
+pub struct MessageLimits {
+    max_chars: Option<usize>,
+    max_segments: Option<u32>,
+}
+
+impl MessageLimits {
+    /// Create limits that accept any length.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reject texts longer than `max` characters.
+    pub fn max_chars(mut self, max: usize) -> Self {
+        self.max_chars = Some(max);
+        self
+    }
+
+    /// Reject texts that need more than `max` SMS segments.
+    pub fn max_segments(mut self, max: u32) -> Self {
+        self.max_segments = Some(max);
+        self
+    }
+
+    /// Measure `text` and check it against the limits.
+    pub fn check(&self, text: &str) -> Result<MessageLength, ValidationError> {
+        let length = MessageLength::measure(text);
+        if let Some(max) = self.max_chars {
+            if length.chars > max {
+                return Err(ValidationError::MessageTooLong { max, length });
+            }
+        }
+        if let Some(max) = self.max_segments {
+            if length.segments > max {
+                return Err(ValidationError::TooManySegments { max, length });
+            }
+        }
+        Ok(length)
+    }
+}
+
+fn is_gsm7(ch: char) -> bool {
+    GSM7_BASIC.contains(ch) || GSM7_EXTENSION.contains(ch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gsm7_text_uses_160_and_153_septet_segments() {
+        let one = MessageLength::measure(&"a".repeat(160));
+        assert_eq!(one.encoding, SmsEncoding::Gsm7);
+        assert_eq!(one.encoding.to_string(), "GSM-7");
+        assert_eq!(one.segments, 1);
+
+        let two = MessageLength::measure(&"a".repeat(161));
+        assert_eq!(two.segments, 2);
+        assert_eq!(MessageLength::measure(&"a".repeat(306)).segments, 2);
+        assert_eq!(MessageLength::measure(&"a".repeat(307)).segments, 3);
+    }
+
+    #[test]
+    fn gsm7_extension_characters_take_two_septets() {
+        let len = MessageLength::measure("€[]");
+        assert_eq!(len.encoding, SmsEncoding::Gsm7);
+        assert_eq!(len.chars, 3);
+        assert_eq!(len.units, 6);
+
+        // 152 plain septets + one escape pair does not fit into the first 153-septet segment.
+        let text = format!("{}€{}", "a".repeat(152), "a".repeat(10));
+        assert_eq!(MessageLength::measure(&text).segments, 2);
+    }
+
+    #[test]
+    fn limits_report_measured_length() {
+        let limits = MessageLimits::new().max_chars(200).max_segments(2);
+        assert_eq!(limits.check(&"a".repeat(200)).unwrap().segments, 2);
+
+        let err = limits.check(&"a".repeat(201)).unwrap_err();
+        assert_eq!(
+            err,
+            ValidationError::MessageTooLong {
+                max: 200,
+                length: MessageLength::measure(&"a".repeat(201)),
+            }
+        );
+
+        // 150 Cyrillic characters fit the character limit but need three UCS-2 segments.
+        let err = limits.check(&"я".repeat(150)).unwrap_err();
+        match err {
+            ValidationError::TooManySegments { max: 2, length } => {
+                assert_eq!(length.encoding, SmsEncoding::Ucs2);
+                assert_eq!(length.chars, 150);
+                assert_eq!(length.segments, 3);
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+
+        assert!(MessageLimits::new().check(&"a".repeat(10_000)).is_ok());
+    }
+
+    #[test]
+    fn cyrillic_text_switches_to_ucs2() {
+        let len = MessageLength::measure(&"я".repeat(70));
+        assert_eq!(len.encoding, SmsEncoding::Ucs2);
+        assert_eq!(len.units, 70);
+        assert_eq!(len.segments, 1);
+        assert_eq!(MessageLength::measure(&"я".repeat(71)).segments, 2);
+        assert_eq!(MessageLength::measure(&"я".repeat(135)).segments, 3);
+    }
+}
@@ -1,26 +1,37 @@
 //! Domain layer: strong types with validation and invariants (no I/O).
 
+mod delivery;
+mod encoding;
+mod normalize;
 mod request;
 mod response;
+mod status_text;
+mod template;
 mod validation;
 mod value;
 
+pub use delivery::{DeliveryFailure, DeliveryState, DeliveryTransitionError};
+pub use encoding::{MessageLength, MessageLimits, SmsEncoding};
+pub use normalize::NormalizedSendSms;
+pub(crate) use normalize::{canonicalize_phone, resolve_phone};
 pub use request::JsonMode;
 pub use request::{
     AddCallback, AddStoplistEntry, CHECK_COST_MAX_RECIPIENTS, CHECK_STATUS_MAX_SMS_IDS,
-    CheckCallAuthStatus, CheckCallAuthStatusOptions, CheckCost, CheckCostOptions, CheckStatus,
-    RemoveCallback, RemoveStoplistEntry, SEND_SMS_MAX_RECIPIENTS, SendOptions, SendSms,
-    StartCallAuth, StartCallAuthOptions,
+    CallbackSyncPolicy, CheckCallAuthStatus, CheckCallAuthStatusOptions, CheckCost,
+    CheckCostOptions, CheckStatus, RemoveCallback, RemoveStoplistEntry, SEND_SMS_MAX_RECIPIENTS,
+    SendOptions, SendSms, StartCallAuth, StartCallAuthOptions,
 };
 pub use response::{
-    BalanceResponse, CallbacksResponse, CheckCallAuthStatusResponse, CheckCostResponse,
-    CheckStatusResponse, FreeUsageResponse, LimitUsageResponse, SendSmsResponse, SendersResponse,
-    SmsCostResult, SmsResult, SmsStatusResult, StartCallAuthResponse, Status, StatusOnlyResponse,
-    StoplistResponse,
+    BalanceResponse, CallbackSyncReport, CallbacksResponse, CheckCallAuthStatusResponse,
+    CheckCostResponse, CheckStatusResponse, FreeUsageResponse, LimitUsageResponse,
+    RecipientFailure, SendSmsResponse, SendersResponse, SmsCostResult, SmsResult, SmsStatusResult,
+    StartCallAuthResponse, Status, StatusOnlyResponse, StoplistResponse,
 };
+pub use status_text::Lang;
+pub use template::{MessageTemplate, RenderedMessages, TemplateError};
 pub use validation::ValidationError;
 pub use value::{
-    ApiId, CallCheckId, CallCheckStatusCode, CallbackUrl, KnownCallCheckStatusCode,
+    ApiId, CallCheckId, CallCheckStatusCode, CallbackUrl, IdempotencyKey, KnownCallCheckStatusCode,
     KnownStatusCode, Login, MessageText, PartnerId, Password, PhoneNumber, RawPhoneNumber,
     SenderId, SmsId, StatusCode, StoplistText, TtlMinutes, UnixTimestamp,
 };
@@ -0,0 +1,345 @@
+//! Opt-in E.164 normalization and deduplication of `SendSms` recipients.
+
+use std::collections::BTreeMap;
+use std::collections::btree_map::Entry;
+
+use phonenumber::country;
+
+use crate::domain::request::SendSms;
+use crate::domain::response::{SendSmsResponse, SmsResult};
+use crate::domain::validation::ValidationError;
+use crate::domain::value::{MessageText, PhoneNumber, RawPhoneNumber};
+
+#[derive(Debug, Clone)]
+/// A [`SendSms`] whose recipients were canonicalized by [`SendSms::normalize`].
+///
+/// Recipients are sent as E.164 digits without `+` (the form SMS.RU uses in responses).
+/// Inputs that normalize to the same number are sent once; [`NormalizedSendSms::restore_keys`]
+/// maps a response back to every original input.
+pub struct NormalizedSendSms {
+    request: SendSms,
+    originals: BTreeMap<RawPhoneNumber, Vec<RawPhoneNumber>>,
+    invalid: Vec<(RawPhoneNumber, ValidationError)>,
+}
+
+impl SendSms {
+    /// Canonicalize recipients and drop duplicates.
+    ///
+    /// Numbers are parsed in `default_region`; a digits-only number that is not valid there is
+    /// read as international (`4915123456789` as `+4915123456789`), the way SMS.RU reads it.
+    ///
+    /// Per-recipient inputs that normalize to the same number must have the same text, or this
+    /// fails with [`ValidationError::ConflictingTexts`]. Inputs that do not parse are left out
+    /// and reported by [`NormalizedSendSms::invalid`]; if no recipient is left, this fails with
+    /// [`ValidationError::Empty`].
+    pub fn normalize(
+        &self,
+        default_region: Option<country::Id>,
+    ) -> Result<NormalizedSendSms, ValidationError> {
+        let mut originals = BTreeMap::<RawPhoneNumber, Vec<RawPhoneNumber>>::new();
+        let mut invalid = Vec::new();
+        // Returns the canonical number and whether this is its first input.
+        let mut canonicalize = |input: &RawPhoneNumber| -> Option<(RawPhoneNumber, bool)> {
+            match canonicalize_phone(default_region, input.raw()) {
+                Ok(canonical) => {
+                    let inputs = originals.entry(canonical.clone()).or_default();
+                    inputs.push(input.clone());
+                    Some((canonical, inputs.len() == 1))
+                }
+                Err(err) => {
+                    invalid.push((input.clone(), err));
+                    None
+                }
+            }
+        };
+
+        let request = match self {
+            Self::ToMany(to_many) => {
+                let recipients = to_many.recipients().iter().filter_map(|phone| {
+                    canonicalize(phone).and_then(|(c, first)| first.then_some(c))
+                });
+                Self::to_many(
+                    recipients.collect(),
+                    to_many.msg().clone(),
+                    to_many.options().clone(),
+                )?
+            }
+            Self::PerRecipient(per_recipient) => {
+                let mut messages = BTreeMap::<RawPhoneNumber, MessageText>::new();
+                for (phone, text) in per_recipient.messages() {
+                    let Some((canonical, _)) = canonicalize(phone) else {
+                        continue;
+                    };
+                    match messages.entry(canonical) {
+                        Entry::Vacant(entry) => {
+                            entry.insert(text.clone());
+                        }
+                        Entry::Occupied(entry) => {
+                            if entry.get() != text {
+                                return Err(ValidationError::ConflictingTexts {
+                                    phone: entry.key().raw().to_owned(),
+                                });
+                            }
+                        }
+                    }
+                }
+                Self::per_recipient(messages, per_recipient.options().clone())?
+            }
+        };
+
+        Ok(NormalizedSendSms {
+            request,
+            originals,
+            invalid,
+        })
+    }
+}
+
+/// Parse `raw` in `default_region`, reading digits-only numbers that are not valid there as
+/// international (the way SMS.RU reads them). Returns `None` for invalid numbers.
+pub(crate) fn resolve_phone(default_region: Option<country::Id>, raw: &str) -> Option<PhoneNumber> {
+    let local = PhoneNumber::parse(default_region, raw)
+        .ok()
+        .filter(|phone| phone.parsed().is_valid());
+    if local.is_some() {
+        return local;
+    }
+
+    let digits = raw.trim();
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    PhoneNumber::parse(None, format!("+{digits}"))
+        .ok()
+        .filter(|phone| phone.parsed().is_valid())
+}
+
+/// Resolve `raw` with [`resolve_phone`] and return it as E.164 digits without `+`.
+pub(crate) fn canonicalize_phone(
+    default_region: Option<country::Id>,
+    raw: &str,
+) -> Result<RawPhoneNumber, ValidationError> {
+    let input = raw.trim();
+    if input.is_empty() {
+        return Err(ValidationError::Empty {
+            field: PhoneNumber::FIELD,
+        });
+    }
+    let phone = resolve_phone(default_region, input).ok_or_else(|| {
+        ValidationError::InvalidPhoneNumber {
+            input: input.to_owned(),
+        }
+    })?;
+    Ok(RawPhoneNumber::new(phone.e164().trim_start_matches('+'))
+        .expect("E.164 numbers are never empty"))
+}
+
+impl NormalizedSendSms {
+    /// The request with canonical, unique recipients.
+    pub fn request(&self) -> &SendSms {
+        &self.request
+    }
+
+    /// Consume and return the canonical request.
+    pub fn into_request(self) -> SendSms {
+        self.request
+    }
+
+    /// Inputs that could not be parsed, with the parse error.
+    pub fn invalid(&self) -> &[(RawPhoneNumber, ValidationError)] {
+        &self.invalid
+    }
+
+    /// Inputs dropped because an earlier input normalized to the same number, as
+    /// `(canonical, dropped input)` pairs.
+    pub fn duplicates(&self) -> impl Iterator<Item = (&RawPhoneNumber, &RawPhoneNumber)> {
+        self.originals.iter().flat_map(|(canonical, inputs)| {
+            inputs.iter().skip(1).map(move |input| (canonical, input))
+        })
+    }
+
+    /// Original inputs that normalized to `canonical`, in input order.
+    pub fn originals(&self, canonical: &RawPhoneNumber) -> &[RawPhoneNumber] {
+        self.originals.get(canonical).map_or(&[], Vec::as_slice)
+    }
+
+    /// Re-key a response to the request by the caller's original inputs.
+    ///
+    /// Every input that normalized to a sent number gets that number's result, so duplicates
+    /// share one `sms_id`. Results for numbers this request did not send are kept as-is.
+    pub fn restore_keys(&self, mut response: SendSmsResponse) -> SendSmsResponse {
+        let sms = std::mem::take(&mut response.sms);
+        for (phone, result) in sms {
+            match self.originals.get(&phone) {
+                Some(inputs) => {
+                    for input in inputs {
+                        response.sms.insert(input.clone(), result.clone());
+                    }
+                }
+                None => {
+                    response.sms.insert(phone, result);
+                }
+            }
+        }
+        response
+    }
+
+    /// Result for one original input in a response keyed by canonical numbers.
+    pub fn result_for<'a>(
+        &self,
+        response: &'a SendSmsResponse,
+        input: &RawPhoneNumber,
+    ) -> Option<&'a SmsResult> {
+        self.originals
+            .iter()
+            .find(|(_, inputs)| inputs.contains(input))
+            .and_then(|(canonical, _)| response.sms.get(canonical))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{SendOptions, SmsId, Status, StatusCode};
+
+    fn phone(raw: &str) -> RawPhoneNumber {
+        RawPhoneNumber::new(raw).unwrap()
+    }
+
+    fn ok(id: &str) -> SmsResult {
+        SmsResult {
+            status: Status::Ok,
+            status_code: StatusCode::new(100),
+            status_text: None,
+            sms_id: Some(SmsId::new(id).unwrap()),
+        }
+    }
+
+    #[test]
+    fn to_many_is_canonicalized_and_deduplicated() {
+        let request = SendSms::to_many(
+            vec![
+                phone("89251234567"),
+                phone("+79251234567"),
+                phone("7 925 123-45-67"),
+                phone("not a number"),
+                phone("+7 926 000 00 01"),
+            ],
+            MessageText::new("hi").unwrap(),
+            SendOptions::default(),
+        )
+        .unwrap();
+
+        let normalized = request.normalize(Some(country::RU)).unwrap();
+        let SendSms::ToMany(to_many) = normalized.request() else {
+            panic!("shape changed");
+        };
+        assert_eq!(
+            to_many.recipients(),
+            [phone("79251234567"), phone("79260000001")]
+        );
+        assert_eq!(normalized.invalid().len(), 1);
+        assert_eq!(normalized.invalid()[0].0, phone("not a number"));
+        assert_eq!(
+            normalized.duplicates().collect::<Vec<_>>(),
+            vec![
+                (&phone("79251234567"), &phone("+79251234567")),
+                (&phone("79251234567"), &phone("7 925 123-45-67")),
+            ]
+        );
+    }
+
+    #[test]
+    fn digits_only_international_numbers_keep_their_country() {
+        let request = SendSms::to_many(
+            vec![
+                phone("4915123456789"),
+                phone("380501234567"),
+                phone("9251234567"),
+                phone("12345"),
+            ],
+            MessageText::new("hi").unwrap(),
+            SendOptions::default(),
+        )
+        .unwrap();
+
+        let normalized = request.normalize(Some(country::RU)).unwrap();
+        let SendSms::ToMany(to_many) = normalized.request() else {
+            panic!("shape changed");
+        };
+        assert_eq!(
+            to_many.recipients(),
+            [
+                phone("4915123456789"),
+                phone("380501234567"),
+                phone("79251234567")
+            ]
+        );
+        assert_eq!(normalized.invalid().len(), 1);
+        assert_eq!(normalized.invalid()[0].0, phone("12345"));
+    }
+
+    #[test]
+    fn responses_map_back_to_original_inputs() {
+        let mut messages = BTreeMap::new();
+        messages.insert(phone("89251234567"), MessageText::new("a").unwrap());
+        messages.insert(phone("+79251234567"), MessageText::new("a").unwrap());
+        let request = SendSms::per_recipient(messages, SendOptions::default()).unwrap();
+        let normalized = request.normalize(Some(country::RU)).unwrap();
+
+        let SendSms::PerRecipient(per_recipient) = normalized.request() else {
+            panic!("shape changed");
+        };
+        assert_eq!(per_recipient.messages().len(), 1);
+
+        let mut sms = BTreeMap::new();
+        sms.insert(phone("79251234567"), ok("000-1"));
+        let response = SendSmsResponse {
+            status: Status::Ok,
+            status_code: StatusCode::new(100),
+            status_text: None,
+            balance: None,
+            sms,
+        };
+        assert_eq!(
+            normalized
+                .result_for(&response, &phone("89251234567"))
+                .and_then(|r| r.sms_id.clone()),
+            Some(SmsId::new("000-1").unwrap())
+        );
+
+        let restored = normalized.restore_keys(response);
+        assert_eq!(
+            restored.sms.keys().collect::<Vec<_>>(),
+            vec![&phone("+79251234567"), &phone("89251234567")]
+        );
+    }
+
+    #[test]
+    fn different_texts_for_one_number_are_rejected() {
+        let mut messages = BTreeMap::new();
+        messages.insert(phone("89251234567"), MessageText::new("a").unwrap());
+        messages.insert(phone("+79251234567"), MessageText::new("b").unwrap());
+        let request = SendSms::per_recipient(messages, SendOptions::default()).unwrap();
+        assert_eq!(
+            request.normalize(Some(country::RU)).unwrap_err(),
+            ValidationError::ConflictingTexts {
+                phone: "79251234567".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn all_invalid_is_an_error() {
+        let request = SendSms::to_many(
+            vec![phone("nope")],
+            MessageText::new("hi").unwrap(),
+            SendOptions::default(),
+        )
+        .unwrap();
+        assert!(matches!(
+            request.normalize(Some(country::RU)),
+            Err(ValidationError::Empty { .. })
+        ));
+    }
+}
@@ -1,5 +1,6 @@
 use std::collections::BTreeMap;
 use std::net::IpAddr;
+use std::time::{Duration, SystemTime};
 
 use crate::domain::validation::ValidationError;
 use crate::domain::value::{
@@ -51,6 +52,50 @@ pub struct SendOptions {
     pub partner_id: Option<PartnerId>,
 }
 
+impl SendOptions {
+    /// Schedule the send for `time`; clears `daytime`, which SMS.RU cannot combine with it.
+    pub fn schedule_at(mut self, time: UnixTimestamp) -> Self {
+        self.time = Some(time);
+        self.daytime = false;
+        self
+    }
+
+    /// Schedule the send `delay` from now; see [`SendOptions::schedule_at`].
+    pub fn schedule_in(self, delay: Duration) -> Self {
+        self.schedule_at(UnixTimestamp::in_duration(delay))
+    }
+
+    /// Stop delivery attempts `ttl` after the (scheduled) send time.
+    pub fn with_ttl(mut self, ttl: TtlMinutes) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Deliver only during the recipient's daytime; clears any scheduled `time`.
+    pub fn daytime_only(mut self) -> Self {
+        self.daytime = true;
+        self.time = None;
+        self
+    }
+
+    /// Validate scheduling fields against `now`.
+    ///
+    /// Rejects a `time` in the past or beyond [`UnixTimestamp::MAX_SCHEDULE_AHEAD`], and `time`
+    /// combined with `daytime`. [`crate::SmsRuClient::send_sms`] runs this before sending.
+    pub fn validate_schedule(&self, now: SystemTime) -> Result<(), ValidationError> {
+        let Some(time) = self.time else {
+            return Ok(());
+        };
+        if self.daytime {
+            return Err(ValidationError::ConflictingOptions {
+                first: UnixTimestamp::FIELD,
+                second: "daytime",
+            });
+        }
+        time.validate_schedule(now)
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 /// Optional parameters for the "check cost" request.
 ///
@@ -179,6 +224,16 @@ pub struct RemoveCallback {
     url: CallbackUrl,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// How [`crate::SmsRuClient::ensure_callbacks`] reconciles the configured callback list.
+pub enum CallbackSyncPolicy {
+    #[default]
+    /// Add missing URLs and leave any other configured URLs in place.
+    AddOnly,
+    /// Add missing URLs and remove every configured URL that is not desired.
+    AddAndPrune,
+}
+
 impl SendSms {
     /// Create a "one message to many recipients" request.
     ///
@@ -230,6 +285,53 @@ impl SendSms {
         }
         Ok(Self::PerRecipient(PerRecipient { messages, options }))
     }
+
+    /// Number of recipients in this request.
+    pub fn recipient_count(&self) -> usize {
+        match self {
+            Self::ToMany(to_many) => to_many.recipients.len(),
+            Self::PerRecipient(per_recipient) => per_recipient.messages.len(),
+        }
+    }
+
+    /// Split the request after the first `mid` recipients, keeping text and options.
+    ///
+    /// Per-recipient requests are split in phone-number order. Either half is `None` when it
+    /// would have no recipients.
+    pub fn split_at(self, mid: usize) -> (Option<Self>, Option<Self>) {
+        if mid == 0 {
+            return (None, Some(self));
+        }
+        if mid >= self.recipient_count() {
+            return (Some(self), None);
+        }
+
+        match self {
+            Self::ToMany(mut to_many) => {
+                let tail = to_many.recipients.split_off(mid);
+                let rest = Self::ToMany(ToMany {
+                    recipients: tail,
+                    msg: to_many.msg.clone(),
+                    options: to_many.options.clone(),
+                });
+                (Some(Self::ToMany(to_many)), Some(rest))
+            }
+            Self::PerRecipient(mut per_recipient) => {
+                let split_key = per_recipient
+                    .messages
+                    .keys()
+                    .nth(mid)
+                    .cloned()
+                    .expect("mid is within bounds");
+                let tail = per_recipient.messages.split_off(&split_key);
+                let rest = Self::PerRecipient(PerRecipient {
+                    messages: tail,
+                    options: per_recipient.options.clone(),
+                });
+                (Some(Self::PerRecipient(per_recipient)), Some(rest))
+            }
+        }
+    }
 }
 
 impl CheckCost {
@@ -285,6 +387,36 @@ impl CheckCost {
     }
 }
 
+impl From<&SendSms> for CheckCost {
+    /// Build the `sms/cost` request matching a `sms/send` request.
+    ///
+    /// Recipients, texts, `from`, `translit`, and `json` are carried over; send-only options
+    /// (`time`, `ttl`, `test`, ...) have no cost equivalent and are dropped.
+    fn from(request: &SendSms) -> Self {
+        match request {
+            SendSms::ToMany(to_many) => Self::ToMany(CostToMany {
+                recipients: to_many.recipients.clone(),
+                msg: to_many.msg.clone(),
+                options: CheckCostOptions::from(&to_many.options),
+            }),
+            SendSms::PerRecipient(per_recipient) => Self::PerRecipient(CostPerRecipient {
+                messages: per_recipient.messages.clone(),
+                options: CheckCostOptions::from(&per_recipient.options),
+            }),
+        }
+    }
+}
+
+impl From<&SendOptions> for CheckCostOptions {
+    fn from(options: &SendOptions) -> Self {
+        Self {
+            json: options.json,
+            from: options.from.clone(),
+            translit: options.translit,
+        }
+    }
+}
+
 impl ToMany {
     /// Recipient phone numbers as provided (not normalized).
     pub fn recipients(&self) -> &[RawPhoneNumber] {
@@ -657,6 +789,114 @@ mod tests {
         }
     }
 
+    #[test]
+    fn split_at_divides_both_request_shapes() {
+        let recipients = make_recipients(3);
+        let msg = MessageText::new("hello").unwrap();
+        let req = SendSms::to_many(recipients.clone(), msg, SendOptions::default()).unwrap();
+        assert_eq!(req.recipient_count(), 3);
+
+        let (head, tail) = req.clone().split_at(2);
+        assert_eq!(head.unwrap().recipient_count(), 2);
+        match tail.unwrap() {
+            SendSms::ToMany(to_many) => assert_eq!(to_many.recipients(), &recipients[2..]),
+            SendSms::PerRecipient(_) => panic!("expected to_many request"),
+        }
+        assert!(req.clone().split_at(0).0.is_none());
+        assert!(req.split_at(3).1.is_none());
+
+        let messages = recipients
+            .iter()
+            .map(|phone| (phone.clone(), MessageText::new(phone.raw()).unwrap()))
+            .collect::<BTreeMap<_, _>>();
+        let req = SendSms::per_recipient(messages, SendOptions::default()).unwrap();
+        let (head, tail) = req.split_at(1);
+        match (head.unwrap(), tail.unwrap()) {
+            (SendSms::PerRecipient(head), SendSms::PerRecipient(tail)) => {
+                assert_eq!(head.messages().len(), 1);
+                assert!(head.messages().contains_key(&recipients[0]));
+                assert_eq!(tail.messages().len(), 2);
+                assert_eq!(
+                    tail.messages()[&recipients[2]].as_str(),
+                    recipients[2].raw()
+                );
+            }
+            _ => panic!("expected per_recipient requests"),
+        }
+    }
+
+    #[test]
+    fn check_cost_from_send_sms_keeps_recipients_and_cost_options() {
+        let recipients = make_recipients(2);
+        let msg = MessageText::new("hello").unwrap();
+        let options = SendOptions {
+            from: Some(SenderId::new("brand").unwrap()),
+            translit: true,
+            test: true,
+            ..Default::default()
+        };
+        let send = SendSms::to_many(recipients.clone(), msg.clone(), options).unwrap();
+
+        match CheckCost::from(&send) {
+            CheckCost::ToMany(to_many) => {
+                assert_eq!(to_many.recipients(), recipients.as_slice());
+                assert_eq!(to_many.msg(), &msg);
+                assert_eq!(to_many.options().from.as_ref().unwrap().as_str(), "brand");
+                assert!(to_many.options().translit);
+            }
+            CheckCost::PerRecipient(_) => panic!("expected to_many request"),
+        }
+    }
+
+    #[test]
+    fn send_options_schedule_helpers_keep_fields_consistent() {
+        let ttl = TtlMinutes::new(30).unwrap();
+        let options = SendOptions::default()
+            .daytime_only()
+            .schedule_at(UnixTimestamp::new(1_000))
+            .with_ttl(ttl);
+        assert_eq!(options.time, Some(UnixTimestamp::new(1_000)));
+        assert_eq!(options.ttl, Some(ttl));
+        assert!(!options.daytime);
+
+        let options = options.daytime_only();
+        assert!(options.daytime);
+        assert_eq!(options.time, None);
+        assert!(options.validate_schedule(SystemTime::now()).is_ok());
+    }
+
+    #[test]
+    fn send_options_validate_schedule_rejects_bad_times() {
+        let now = SystemTime::now();
+        let ok = SendOptions::default().schedule_in(Duration::from_secs(3600));
+        assert!(ok.validate_schedule(now).is_ok());
+
+        let past = SendOptions::default().schedule_at(UnixTimestamp::new(1));
+        assert!(matches!(
+            past.validate_schedule(now),
+            Err(ValidationError::TimeInPast { .. })
+        ));
+
+        let far = SendOptions::default()
+            .schedule_in(UnixTimestamp::MAX_SCHEDULE_AHEAD + Duration::from_secs(3600));
+        assert!(matches!(
+            far.validate_schedule(now),
+            Err(ValidationError::TimeTooFarAhead { .. })
+        ));
+
+        let conflicting = SendOptions {
+            daytime: true,
+            ..ok
+        };
+        assert_eq!(
+            conflicting.validate_schedule(now),
+            Err(ValidationError::ConflictingOptions {
+                first: "time",
+                second: "daytime"
+            })
+        );
+    }
+
     #[test]
     fn check_status_rejects_empty_sms_ids() {
         let err = CheckStatus::new(Vec::new()).unwrap_err();
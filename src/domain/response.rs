@@ -1,7 +1,10 @@
 use std::collections::BTreeMap;
 
+use crate::domain::request::SendSms;
+
 use crate::domain::value::{
-    CallCheckId, CallCheckStatusCode, CallbackUrl, RawPhoneNumber, SmsId, StatusCode,
+    CallCheckId, CallCheckStatusCode, CallbackUrl, KnownStatusCode, RawPhoneNumber, SmsId,
+    StatusCode,
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -44,6 +47,128 @@ pub struct SmsResult {
     pub sms_id: Option<SmsId>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Why SMS.RU did not accept a single recipient of a send.
+pub enum RecipientFailure {
+    /// The number is malformed or not a valid recipient (`202`).
+    InvalidNumber,
+    /// The number is in the account or global stoplist (`209`, `215`).
+    Stoplisted,
+    /// No delivery route to the number or its country (`150`, `207`, `214`, `550`).
+    NoRoute,
+    /// A daily, per-number, duplicate-message, or anti-fraud limit was hit (`206`, `230`-`233`,
+    /// `501`-`506`).
+    LimitExceeded,
+    /// Any other per-recipient error, including unknown codes.
+    Other,
+}
+
+impl RecipientFailure {
+    /// Classify a per-recipient status code.
+    pub fn from_status_code(code: StatusCode) -> Self {
+        match code.known_kind() {
+            Some(KnownStatusCode::InvalidRecipientOrNoRoute) => Self::InvalidNumber,
+            Some(
+                KnownStatusCode::RecipientInStopList | KnownStatusCode::RecipientInGlobalStopList,
+            ) => Self::Stoplisted,
+            Some(
+                KnownStatusCode::NotDeliveredNoRoute
+                | KnownStatusCode::NoDeliveryRoute
+                | KnownStatusCode::RecipientAbroadBlocked
+                | KnownStatusCode::CountryBlocked,
+            ) => Self::NoRoute,
+            Some(
+                KnownStatusCode::DailyLimitExceeded
+                | KnownStatusCode::ExceededDailyLimitToNumber
+                | KnownStatusCode::ExceededIdenticalPerMinute
+                | KnownStatusCode::ExceededIdenticalPerDay
+                | KnownStatusCode::ExceededRepeatSendLimit
+                | KnownStatusCode::LimitIpCountryMismatchCategory1
+                | KnownStatusCode::LimitIpCountryMismatchCategory2
+                | KnownStatusCode::LimitTooManyToCountry
+                | KnownStatusCode::LimitTooManyForeignAuth
+                | KnownStatusCode::LimitTooManyFromIp
+                | KnownStatusCode::LimitHostingProviderIp,
+            ) => Self::LimitExceeded,
+            _ => Self::Other,
+        }
+    }
+}
+
+impl SmsResult {
+    /// Whether SMS.RU accepted this recipient.
+    pub fn is_success(&self) -> bool {
+        self.status == Status::Ok
+    }
+
+    /// Failure class for a rejected recipient, or `None` if it was accepted.
+    pub fn failure(&self) -> Option<RecipientFailure> {
+        if self.is_success() {
+            None
+        } else {
+            Some(RecipientFailure::from_status_code(self.status_code))
+        }
+    }
+}
+
+impl SendSmsResponse {
+    /// Recipients accepted by SMS.RU.
+    pub fn successes(&self) -> impl Iterator<Item = (&RawPhoneNumber, &SmsResult)> {
+        self.sms.iter().filter(|(_, result)| result.is_success())
+    }
+
+    /// Recipients rejected by SMS.RU, even though the top-level status may be `OK`.
+    pub fn failures(&self) -> impl Iterator<Item = (&RawPhoneNumber, &SmsResult)> {
+        self.sms.iter().filter(|(_, result)| !result.is_success())
+    }
+
+    /// Assigned SMS ids per accepted recipient.
+    pub fn sms_ids(&self) -> impl Iterator<Item = (&RawPhoneNumber, &SmsId)> {
+        self.sms
+            .iter()
+            .filter_map(|(phone, result)| result.sms_id.as_ref().map(|id| (phone, id)))
+    }
+
+    /// Whether any recipient was rejected.
+    pub fn has_failures(&self) -> bool {
+        self.failures().next().is_some()
+    }
+
+    /// Build a request that re-sends `request` to the recipients rejected with a retryable
+    /// status code (see [`StatusCode::is_retryable`]).
+    ///
+    /// Text and options are kept, including per-recipient texts. Returns `None` when no
+    /// recipient should be retried.
+    pub fn retry_request(&self, request: &SendSms) -> Option<SendSms> {
+        let retryable = |phone: &RawPhoneNumber| {
+            self.sms
+                .get(phone)
+                .is_some_and(|result| !result.is_success() && result.status_code.is_retryable())
+        };
+
+        match request {
+            SendSms::ToMany(to_many) => {
+                let recipients = to_many
+                    .recipients()
+                    .iter()
+                    .filter(|phone| retryable(phone))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                SendSms::to_many(recipients, to_many.msg().clone(), to_many.options().clone()).ok()
+            }
+            SendSms::PerRecipient(per_recipient) => {
+                let messages = per_recipient
+                    .messages()
+                    .iter()
+                    .filter(|(phone, _)| retryable(phone))
+                    .map(|(phone, msg)| (phone.clone(), msg.clone()))
+                    .collect::<BTreeMap<_, _>>();
+                SendSms::per_recipient(messages, per_recipient.options().clone()).ok()
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 /// Parsed response from the SMS.RU "check status" API.
 ///
@@ -245,3 +370,154 @@ pub struct CallbacksResponse {
     /// Configured callback URLs.
     pub callback: Vec<CallbackUrl>,
 }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Outcome of [`crate::SmsRuClient::ensure_callbacks`].
+pub struct CallbackSyncReport {
+    /// Desired URLs that were missing and have been added.
+    pub added: Vec<CallbackUrl>,
+    /// Configured URLs that were not desired and have been removed.
+    pub removed: Vec<CallbackUrl>,
+    /// Callback URLs configured after reconciliation.
+    pub callback: Vec<CallbackUrl>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(status: Status, code: i32, sms_id: Option<&str>) -> SmsResult {
+        SmsResult {
+            status,
+            status_code: StatusCode::new(code),
+            status_text: None,
+            sms_id: sms_id.map(|id| SmsId::new(id).unwrap()),
+        }
+    }
+
+    #[test]
+    fn send_response_splits_successes_and_failures() {
+        let mut sms = BTreeMap::new();
+        sms.insert(
+            RawPhoneNumber::new("79250000001").unwrap(),
+            result(Status::Ok, 100, Some("000-1")),
+        );
+        sms.insert(
+            RawPhoneNumber::new("79250000002").unwrap(),
+            result(Status::Error, 209, None),
+        );
+        sms.insert(
+            RawPhoneNumber::new("79250000003").unwrap(),
+            result(Status::Error, 232, None),
+        );
+        let response = SendSmsResponse {
+            status: Status::Ok,
+            status_code: StatusCode::new(100),
+            status_text: None,
+            balance: None,
+            sms,
+        };
+
+        assert_eq!(response.successes().count(), 1);
+        assert!(response.has_failures());
+        let failures = response
+            .failures()
+            .map(|(phone, result)| (phone.raw(), result.failure().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            failures,
+            vec![
+                ("79250000002", RecipientFailure::Stoplisted),
+                ("79250000003", RecipientFailure::LimitExceeded),
+            ]
+        );
+        let ids = response
+            .sms_ids()
+            .map(|(phone, id)| (phone.raw(), id.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![("79250000001", "000-1")]);
+    }
+
+    fn response_with(results: &[(&str, Status, i32)]) -> SendSmsResponse {
+        SendSmsResponse {
+            status: Status::Ok,
+            status_code: StatusCode::new(100),
+            status_text: None,
+            balance: None,
+            sms: results
+                .iter()
+                .map(|(phone, status, code)| {
+                    (
+                        RawPhoneNumber::new(*phone).unwrap(),
+                        result(*status, *code, None),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn retry_request_keeps_text_and_options_for_retryable_recipients() {
+        use crate::domain::{MessageText, SendOptions};
+
+        let options = SendOptions {
+            translit: true,
+            ..SendOptions::default()
+        };
+        let phones = ["79250000001", "79250000002", "79250000003"]
+            .map(|phone| RawPhoneNumber::new(phone).unwrap());
+        let response = response_with(&[
+            ("79250000001", Status::Ok, 100),
+            ("79250000002", Status::Error, 220),
+            ("79250000003", Status::Error, 202),
+        ]);
+
+        let to_many = SendSms::to_many(
+            phones.to_vec(),
+            MessageText::new("hello").unwrap(),
+            options.clone(),
+        )
+        .unwrap();
+        match response.retry_request(&to_many).unwrap() {
+            SendSms::ToMany(retry) => {
+                assert_eq!(retry.recipients(), &phones[1..2]);
+                assert_eq!(retry.msg().as_str(), "hello");
+                assert!(retry.options().translit);
+            }
+            other => panic!("unexpected request: {other:?}"),
+        }
+
+        let per_recipient = SendSms::per_recipient(
+            phones
+                .iter()
+                .enumerate()
+                .map(|(idx, phone)| (phone.clone(), MessageText::new(format!("m{idx}")).unwrap()))
+                .collect(),
+            options,
+        )
+        .unwrap();
+        match response.retry_request(&per_recipient).unwrap() {
+            SendSms::PerRecipient(retry) => {
+                assert_eq!(retry.messages().len(), 1);
+                assert_eq!(retry.messages()[&phones[1]].as_str(), "m1");
+            }
+            other => panic!("unexpected request: {other:?}"),
+        }
+
+        let all_ok = response_with(&[("79250000001", Status::Ok, 100)]);
+        assert!(all_ok.retry_request(&to_many).is_none());
+    }
+
+    #[test]
+    fn recipient_failures_are_classified_by_code() {
+        let classify = |code| RecipientFailure::from_status_code(StatusCode::new(code));
+        assert_eq!(classify(202), RecipientFailure::InvalidNumber);
+        assert_eq!(classify(215), RecipientFailure::Stoplisted);
+        assert_eq!(classify(207), RecipientFailure::NoRoute);
+        assert_eq!(classify(550), RecipientFailure::NoRoute);
+        assert_eq!(classify(206), RecipientFailure::LimitExceeded);
+        assert_eq!(classify(220), RecipientFailure::Other);
+        assert_eq!(classify(999), RecipientFailure::Other);
+        assert_eq!(result(Status::Ok, 100, None).failure(), None);
+    }
+}
@@ -0,0 +1,569 @@
+//! Human-readable descriptions and remediation hints for [`KnownStatusCode`].
+
+use std::fmt;
+
+use crate::domain::value::KnownStatusCode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+/// Language for status code descriptions.
+pub enum Lang {
+    /// Russian (wording close to the SMS.RU documentation).
+    #[default]
+    Ru,
+    /// English.
+    En,
+}
+
+/// Description and remediation hint in both languages.
+struct StatusText {
+    ru: &'static str,
+    en: &'static str,
+    hint_ru: &'static str,
+    hint_en: &'static str,
+}
+
+impl KnownStatusCode {
+    /// Every known status code, in SMS.RU documentation order.
+    pub const ALL: [Self; 57] = [
+        Self::MessageNotFound,
+        Self::RequestOkOrQueued,
+        Self::BeingDeliveredToOperator,
+        Self::SentInTransit,
+        Self::Delivered,
+        Self::NotDeliveredTtlExpired,
+        Self::NotDeliveredDeletedByOperator,
+        Self::NotDeliveredPhoneFailure,
+        Self::NotDeliveredUnknown,
+        Self::NotDeliveredRejected,
+        Self::Read,
+        Self::NotDeliveredNoRoute,
+        Self::InvalidApiId,
+        Self::InsufficientFunds,
+        Self::InvalidRecipientOrNoRoute,
+        Self::EmptyMessageText,
+        Self::SenderNotEnabled,
+        Self::MessageTooLong,
+        Self::DailyLimitExceeded,
+        Self::NoDeliveryRoute,
+        Self::InvalidTime,
+        Self::RecipientInStopList,
+        Self::UsedGetInsteadOfPost,
+        Self::MethodNotFound,
+        Self::MessageNotUtf8,
+        Self::TooManyNumbers,
+        Self::RecipientAbroadBlocked,
+        Self::RecipientInGlobalStopList,
+        Self::ForbiddenWordInText,
+        Self::MissingDisclaimerPhrase,
+        Self::ServiceTemporarilyUnavailable,
+        Self::SenderMustMatchBrand,
+        Self::ExceededDailyLimitToNumber,
+        Self::ExceededIdenticalPerMinute,
+        Self::ExceededIdenticalPerDay,
+        Self::ExceededRepeatSendLimit,
+        Self::InvalidToken,
+        Self::InvalidAuth,
+        Self::AccountNotConfirmed,
+        Self::ConfirmationCodeWrong,
+        Self::TooManyConfirmationCodes,
+        Self::TooManyWrongAttempts,
+        Self::ServerError,
+        Self::LimitIpCountryMismatchCategory1,
+        Self::LimitIpCountryMismatchCategory2,
+        Self::LimitTooManyToCountry,
+        Self::LimitTooManyForeignAuth,
+        Self::LimitTooManyFromIp,
+        Self::LimitHostingProviderIp,
+        Self::InvalidEndUserIp,
+        Self::LimitTooManyCalls,
+        Self::CountryBlocked,
+        Self::CallbackUrlInvalid,
+        Self::CallbackHandlerNotFound,
+        Self::CallCheckNotConfirmedYet,
+        Self::CallCheckConfirmed,
+        Self::CallCheckExpiredOrInvalidCheckId,
+    ];
+
+    /// Integer code as used by SMS.RU (reverse of [`KnownStatusCode::from_code`]).
+    pub fn code(self) -> i32 {
+        match self {
+            Self::MessageNotFound => -1,
+            Self::RequestOkOrQueued => 100,
+            Self::BeingDeliveredToOperator => 101,
+            Self::SentInTransit => 102,
+            Self::Delivered => 103,
+            Self::NotDeliveredTtlExpired => 104,
+            Self::NotDeliveredDeletedByOperator => 105,
+            Self::NotDeliveredPhoneFailure => 106,
+            Self::NotDeliveredUnknown => 107,
+            Self::NotDeliveredRejected => 108,
+            Self::Read => 110,
+            Self::NotDeliveredNoRoute => 150,
+            Self::InvalidApiId => 200,
+            Self::InsufficientFunds => 201,
+            Self::InvalidRecipientOrNoRoute => 202,
+            Self::EmptyMessageText => 203,
+            Self::SenderNotEnabled => 204,
+            Self::MessageTooLong => 205,
+            Self::DailyLimitExceeded => 206,
+            Self::NoDeliveryRoute => 207,
+            Self::InvalidTime => 208,
+            Self::RecipientInStopList => 209,
+            Self::UsedGetInsteadOfPost => 210,
+            Self::MethodNotFound => 211,
+            Self::MessageNotUtf8 => 212,
+            Self::TooManyNumbers => 213,
+            Self::RecipientAbroadBlocked => 214,
+            Self::RecipientInGlobalStopList => 215,
+            Self::ForbiddenWordInText => 216,
+            Self::MissingDisclaimerPhrase => 217,
+            Self::ServiceTemporarilyUnavailable => 220,
+            Self::SenderMustMatchBrand => 221,
+            Self::ExceededDailyLimitToNumber => 230,
+            Self::ExceededIdenticalPerMinute => 231,
+            Self::ExceededIdenticalPerDay => 232,
+            Self::ExceededRepeatSendLimit => 233,
+            Self::InvalidToken => 300,
+            Self::InvalidAuth => 301,
+            Self::AccountNotConfirmed => 302,
+            Self::ConfirmationCodeWrong => 303,
+            Self::TooManyConfirmationCodes => 304,
+            Self::TooManyWrongAttempts => 305,
+            Self::ServerError => 500,
+            Self::LimitIpCountryMismatchCategory1 => 501,
+            Self::LimitIpCountryMismatchCategory2 => 502,
+            Self::LimitTooManyToCountry => 503,
+            Self::LimitTooManyForeignAuth => 504,
+            Self::LimitTooManyFromIp => 505,
+            Self::LimitHostingProviderIp => 506,
+            Self::InvalidEndUserIp => 507,
+            Self::LimitTooManyCalls => 508,
+            Self::CountryBlocked => 550,
+            Self::CallbackUrlInvalid => 901,
+            Self::CallbackHandlerNotFound => 902,
+            Self::CallCheckNotConfirmedYet => 400,
+            Self::CallCheckConfirmed => 401,
+            Self::CallCheckExpiredOrInvalidCheckId => 402,
+        }
+    }
+
+    /// Short description of the status.
+    pub fn description(self, lang: Lang) -> &'static str {
+        let text = self.text();
+        match lang {
+            Lang::Ru => text.ru,
+            Lang::En => text.en,
+        }
+    }
+
+    /// Operator-facing hint on what to do about the status.
+    pub fn remediation(self, lang: Lang) -> &'static str {
+        let text = self.text();
+        match lang {
+            Lang::Ru => text.hint_ru,
+            Lang::En => text.hint_en,
+        }
+    }
+
+    fn text(self) -> StatusText {
+        let (ru, en, hint_ru, hint_en) = match self {
+            Self::MessageNotFound => (
+                "Сообщение не найдено",
+                "Message not found",
+                "Проверьте sms_id; статусы хранятся ограниченное время.",
+                "Check the sms_id; statuses are kept for a limited time.",
+            ),
+            Self::RequestOkOrQueued => (
+                "Запрос выполнен или сообщение находится в очереди",
+                "Request succeeded or message is queued",
+                "Действий не требуется.",
+                "No action needed.",
+            ),
+            Self::BeingDeliveredToOperator => (
+                "Сообщение передается оператору",
+                "Message is being handed over to the operator",
+                "Действий не требуется; проверьте статус позже.",
+                "No action needed; check the status later.",
+            ),
+            Self::SentInTransit => (
+                "Сообщение отправлено (в пути)",
+                "Message sent (in transit)",
+                "Действий не требуется; проверьте статус позже.",
+                "No action needed; check the status later.",
+            ),
+            Self::Delivered => (
+                "Сообщение доставлено",
+                "Message delivered",
+                "Действий не требуется.",
+                "No action needed.",
+            ),
+            Self::NotDeliveredTtlExpired => (
+                "Не может быть доставлено: время жизни истекло",
+                "Not delivered: TTL expired",
+                "Телефон был недоступен; отправьте позже или увеличьте ttl.",
+                "The handset was unreachable; resend later or increase ttl.",
+            ),
+            Self::NotDeliveredDeletedByOperator => (
+                "Не может быть доставлено: удалено оператором",
+                "Not delivered: deleted by the operator",
+                "Проверьте текст на содержимое, которое блокирует оператор; при повторении обратитесь в поддержку SMS.RU.",
+                "Check the text for content the operator blocks; contact SMS.RU support if it repeats.",
+            ),
+            Self::NotDeliveredPhoneFailure => (
+                "Не может быть доставлено: сбой в телефоне",
+                "Not delivered: handset failure",
+                "Попросите получателя проверить телефон и повторите позже.",
+                "Ask the recipient to check their phone and resend later.",
+            ),
+            Self::NotDeliveredUnknown => (
+                "Не может быть доставлено: неизвестная причина",
+                "Not delivered: unknown reason",
+                "Повторите позже; при повторении обратитесь в поддержку SMS.RU.",
+                "Resend later; contact SMS.RU support if it repeats.",
+            ),
+            Self::NotDeliveredRejected => (
+                "Не может быть доставлено: отклонено",
+                "Not delivered: rejected",
+                "Оператор отклонил сообщение; проверьте отправителя и текст.",
+                "The operator rejected the message; check the sender and text.",
+            ),
+            Self::Read => (
+                "Сообщение прочитано",
+                "Message read",
+                "Действий не требуется.",
+                "No action needed.",
+            ),
+            Self::NotDeliveredNoRoute => (
+                "Не может быть доставлено: не найден маршрут на данный номер",
+                "Not delivered: no route to this number",
+                "Проверьте номер и страну; для подключения направления обратитесь в поддержку SMS.RU.",
+                "Check the number and country; contact SMS.RU support to enable the route.",
+            ),
+            Self::InvalidApiId => (
+                "Неправильный api_id",
+                "Invalid api_id",
+                "Скопируйте api_id из личного кабинета SMS.RU.",
+                "Copy the api_id from your SMS.RU account.",
+            ),
+            Self::InsufficientFunds => (
+                "Не хватает средств на лицевом счету",
+                "Insufficient funds on the account",
+                "Пополните баланс.",
+                "Top up the account balance.",
+            ),
+            Self::InvalidRecipientOrNoRoute => (
+                "Неправильно указан номер телефона получателя, либо на него нет маршрута",
+                "Invalid recipient number or no route to it",
+                "Проверьте формат номера (международный, только цифры).",
+                "Check the number format (international, digits only).",
+            ),
+            Self::EmptyMessageText => (
+                "Нет текста сообщения",
+                "Message text is empty",
+                "Укажите непустой текст сообщения.",
+                "Provide a non-empty message text.",
+            ),
+            Self::SenderNotEnabled => (
+                "Имя отправителя не согласовано с администрацией",
+                "Sender name is not approved",
+                "Согласуйте отправителя в личном кабинете или не указывайте from.",
+                "Enable the sender in your account or omit `from`.",
+            ),
+            Self::MessageTooLong => (
+                "Сообщение слишком длинное (превышает 8 СМС)",
+                "Message is too long (more than 8 SMS)",
+                "Сократите текст или разбейте его на несколько сообщений.",
+                "Shorten the text or split it into several messages.",
+            ),
+            Self::DailyLimitExceeded => (
+                "Будет превышен или уже превышен дневной лимит на отправку сообщений",
+                "Daily sending limit is or would be exceeded",
+                "Дождитесь следующего дня (по Москве) или увеличьте лимит в настройках.",
+                "Wait for the next day (Moscow time) or raise the limit in account settings.",
+            ),
+            Self::NoDeliveryRoute => (
+                "На этот номер нет маршрута для доставки сообщений",
+                "No delivery route for this number",
+                "Для подключения направления обратитесь в поддержку SMS.RU.",
+                "Contact SMS.RU support to enable this destination.",
+            ),
+            Self::InvalidTime => (
+                "Параметр time указан неправильно",
+                "Invalid time parameter",
+                "Укажите unix-время в пределах ближайших 7 дней.",
+                "Use a unix timestamp within the next 7 days.",
+            ),
+            Self::RecipientInStopList => (
+                "Номер добавлен в стоп-лист",
+                "Recipient is in your stoplist",
+                "Удалите номер из стоп-листа, если отправка нужна.",
+                "Remove the number from the stoplist if sending is intended.",
+            ),
+            Self::UsedGetInsteadOfPost => (
+                "Используется GET, где необходимо использовать POST",
+                "GET used where POST is required",
+                "Отправляйте запрос методом POST.",
+                "Send the request as POST.",
+            ),
+            Self::MethodNotFound => (
+                "Метод не найден",
+                "Method not found",
+                "Проверьте адрес метода.",
+                "Check the endpoint URL.",
+            ),
+            Self::MessageNotUtf8 => (
+                "Текст сообщения необходимо передать в кодировке UTF-8",
+                "Message text must be UTF-8",
+                "Передавайте текст в кодировке UTF-8.",
+                "Encode the text as UTF-8.",
+            ),
+            Self::TooManyNumbers => (
+                "Указано более 100 номеров в списке получателей",
+                "More than 100 recipients",
+                "Разбейте запрос на части до 100 получателей.",
+                "Split the request into batches of up to 100 recipients.",
+            ),
+            Self::RecipientAbroadBlocked => (
+                "Номер находится за рубежом, а включена отправка только на номера РФ",
+                "Number is abroad while sending is restricted to Russian numbers",
+                "Отключите ограничение на отправку только на номера РФ в личном кабинете.",
+                "Disable the Russia-only restriction in your account to send abroad.",
+            ),
+            Self::RecipientInGlobalStopList => (
+                "Номер находится в стоп-листе SMS.RU",
+                "Number is in the SMS.RU global stoplist",
+                "Обратитесь в поддержку SMS.RU; получатель мог отказаться от сообщений.",
+                "Contact SMS.RU support; the recipient may have opted out.",
+            ),
+            Self::ForbiddenWordInText => (
+                "В тексте сообщения содержится запрещенное слово",
+                "Message text contains a forbidden word",
+                "Измените формулировку сообщения.",
+                "Rephrase the message.",
+            ),
+            Self::MissingDisclaimerPhrase => (
+                "В тексте сообщения не указана обязательная фраза",
+                "A mandatory phrase is missing from the text",
+                "Добавьте фразу, которую требуют условия вашего отправителя.",
+                "Add the phrase required for your sender.",
+            ),
+            Self::ServiceTemporarilyUnavailable => (
+                "Сервис временно недоступен, попробуйте чуть позже",
+                "Service temporarily unavailable",
+                "Повторите запрос позже.",
+                "Retry later with backoff.",
+            ),
+            Self::SenderMustMatchBrand => (
+                "Имя отправителя должно соответствовать названию бренда",
+                "Sender name must match the brand",
+                "Зарегистрируйте отправителя, соответствующего бренду.",
+                "Register a sender that matches your brand.",
+            ),
+            Self::ExceededDailyLimitToNumber => (
+                "Превышен общий лимит количества сообщений на этот номер в день",
+                "Daily message limit for this number exceeded",
+                "Повторите завтра (по Москве).",
+                "Retry tomorrow (Moscow time).",
+            ),
+            Self::ExceededIdenticalPerMinute => (
+                "Превышен лимит одинаковых сообщений на этот номер в минуту",
+                "Identical messages per minute limit for this number exceeded",
+                "Подождите минуту или измените текст.",
+                "Wait a minute or change the text.",
+            ),
+            Self::ExceededIdenticalPerDay => (
+                "Превышен лимит одинаковых сообщений на этот номер в день",
+                "Identical messages per day limit for this number exceeded",
+                "Измените текст или повторите завтра; не отправляйте дубли повторно.",
+                "Change the text or retry tomorrow; avoid re-sending duplicates.",
+            ),
+            Self::ExceededRepeatSendLimit => (
+                "Превышен лимит повторных сообщений на этот номер за короткое время",
+                "Too many repeated messages to this number in a short time",
+                "Ограничьте частоту повторных запросов на номер.",
+                "Throttle repeated requests per number.",
+            ),
+            Self::InvalidToken => (
+                "Неправильный token (возможно, истек срок действия, либо ваш IP изменился)",
+                "Invalid token (expired or your IP changed)",
+                "Получите новый token.",
+                "Request a new token.",
+            ),
+            Self::InvalidAuth => (
+                "Неправильный api_id, либо логин/пароль",
+                "Invalid api_id or login/password",
+                "Проверьте учетные данные в личном кабинете SMS.RU.",
+                "Check the credentials in your SMS.RU account.",
+            ),
+            Self::AccountNotConfirmed => (
+                "Пользователь авторизован, но аккаунт не подтвержден",
+                "Account is not confirmed",
+                "Подтвердите аккаунт кодом, отправленным при регистрации.",
+                "Confirm the account with the code sent at registration.",
+            ),
+            Self::ConfirmationCodeWrong => (
+                "Код подтверждения неверен",
+                "Confirmation code is wrong",
+                "Введите код подтверждения повторно.",
+                "Re-enter the confirmation code.",
+            ),
+            Self::TooManyConfirmationCodes => (
+                "Отправлено слишком много кодов подтверждения",
+                "Too many confirmation codes sent",
+                "Повторите позже.",
+                "Retry later.",
+            ),
+            Self::TooManyWrongAttempts => (
+                "Слишком много неверных вводов кода",
+                "Too many wrong code attempts",
+                "Повторите позже.",
+                "Retry later.",
+            ),
+            Self::ServerError => (
+                "Ошибка на сервере",
+                "Server error",
+                "Повторите запрос.",
+                "Retry the request.",
+            ),
+            Self::LimitIpCountryMismatchCategory1 => (
+                "Превышен лимит: страна IP и номера не совпадают (категория 1)",
+                "Limit exceeded: IP and number countries differ (category 1)",
+                "Передавайте реальный IP пользователя в параметре ip и проверьте настройки антифрода.",
+                "Pass the real end-user IP in `ip` and review anti-fraud settings.",
+            ),
+            Self::LimitIpCountryMismatchCategory2 => (
+                "Превышен лимит: страна IP и номера не совпадают (категория 2)",
+                "Limit exceeded: IP and number countries differ (category 2)",
+                "Передавайте реальный IP пользователя в параметре ip и проверьте настройки антифрода.",
+                "Pass the real end-user IP in `ip` and review anti-fraud settings.",
+            ),
+            Self::LimitTooManyToCountry => (
+                "Превышен лимит: слишком много сообщений в эту страну",
+                "Limit exceeded: too many messages to this country",
+                "Увеличьте лимит на страну в настройках.",
+                "Raise the per-country limit in account settings.",
+            ),
+            Self::LimitTooManyForeignAuth => (
+                "Превышен лимит: слишком много авторизаций на зарубежные номера",
+                "Limit exceeded: too many authorizations to foreign numbers",
+                "Проверьте лимиты на зарубежные номера в настройках.",
+                "Review foreign-number limits in account settings.",
+            ),
+            Self::LimitTooManyFromIp => (
+                "Превышен лимит: слишком много сообщений с одного IP",
+                "Limit exceeded: too many messages from one IP",
+                "Передавайте IP пользователя и блокируйте злоупотребляющих клиентов.",
+                "Pass the end-user IP and block abusive clients.",
+            ),
+            Self::LimitHostingProviderIp => (
+                "Превышен лимит: IP принадлежит хостинг-провайдеру",
+                "Limit exceeded: IP belongs to a hosting provider",
+                "Передавайте IP пользователя, а не вашего сервера.",
+                "Pass the end-user IP, not your server's.",
+            ),
+            Self::InvalidEndUserIp => (
+                "Неверный IP-адрес пользователя",
+                "Invalid end-user IP address",
+                "Передавайте корректный публичный IP в параметре ip.",
+                "Pass a valid public IP address in `ip`.",
+            ),
+            Self::LimitTooManyCalls => (
+                "Превышен лимит: слишком много звонков",
+                "Limit exceeded: too many calls",
+                "Ограничьте частоту авторизаций звонком.",
+                "Throttle call authentication requests.",
+            ),
+            Self::CountryBlocked => (
+                "Отправка в эту страну заблокирована",
+                "Sending to this country is blocked",
+                "Разрешите страну в настройках или обратитесь в поддержку SMS.RU.",
+                "Allow the country in account settings or contact SMS.RU support.",
+            ),
+            Self::CallbackUrlInvalid => (
+                "Callback: URL неверный (не начинается на http://)",
+                "Callback URL is invalid",
+                "Укажите абсолютный URL с http:// или https://.",
+                "Use an absolute http:// or https:// URL.",
+            ),
+            Self::CallbackHandlerNotFound => (
+                "Callback: обработчик не найден (возможно, был удален ранее)",
+                "Callback handler not found",
+                "Сверьтесь со списком callback/get и удаляйте только существующие обработчики.",
+                "Check callback/get and remove only configured handlers.",
+            ),
+            Self::CallCheckNotConfirmedYet => (
+                "Номер пока не подтвержден",
+                "Number is not confirmed yet",
+                "Дождитесь звонка пользователя и проверьте снова.",
+                "Wait for the user's call and check again.",
+            ),
+            Self::CallCheckConfirmed => (
+                "Номер подтвержден",
+                "Number confirmed",
+                "Действий не требуется.",
+                "No action needed.",
+            ),
+            Self::CallCheckExpiredOrInvalidCheckId => (
+                "Истекло время проверки, либо неправильно указан check_id",
+                "Check expired or check_id is invalid",
+                "Запустите новую проверку звонком.",
+                "Start a new call check.",
+            ),
+        };
+        StatusText {
+            ru,
+            en,
+            hint_ru,
+            hint_en,
+        }
+    }
+}
+
+impl fmt::Display for KnownStatusCode {
+    /// Formats as `<code>: <English description>`, e.g. `204: Sender name is not approved`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code(), self.description(Lang::En))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn all_round_trips_through_codes() {
+        let mut codes = HashSet::new();
+        for known in KnownStatusCode::ALL {
+            assert_eq!(KnownStatusCode::from_code(known.code()), Some(known));
+            assert!(
+                codes.insert(known.code()),
+                "duplicate code {}",
+                known.code()
+            );
+        }
+        for code in -1..1000 {
+            if let Some(known) = KnownStatusCode::from_code(code) {
+                assert!(KnownStatusCode::ALL.contains(&known));
+            }
+        }
+    }
+
+    #[test]
+    fn every_code_has_descriptions_and_hints() {
+        for known in KnownStatusCode::ALL {
+            for lang in [Lang::Ru, Lang::En] {
+                assert!(!known.description(lang).is_empty(), "{known:?}");
+                assert!(!known.remediation(lang).is_empty(), "{known:?}");
+            }
+        }
+        let sender = KnownStatusCode::SenderNotEnabled;
+        assert_eq!(sender.to_string(), "204: Sender name is not approved");
+        assert_eq!(
+            sender.description(Lang::Ru),
+            "Имя отправителя не согласовано с администрацией"
+        );
+        assert!(sender.remediation(Lang::En).contains("Enable the sender"));
+    }
+}
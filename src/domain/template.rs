@@ -0,0 +1,361 @@
+//! Message templates with named `{placeholder}` variables.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::domain::validation::ValidationError;
+use crate::domain::value::{MessageText, RawPhoneNumber};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Message text with named placeholders, e.g. `Hi {name}, your code is {code}`.
+///
+/// Placeholder names consist of ASCII letters, digits, and `_`. Use `{{` and `}}` for literal
+/// braces. The template is validated once by [`MessageTemplate::parse`].
+pub struct MessageTemplate {
+    source: String,
+    parts: Vec<Part>,
+    placeholders: BTreeSet<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Placeholder(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Messages rendered by [`MessageTemplate::render_per_recipient`].
+pub struct RenderedMessages {
+    /// Rendered text per recipient, ready for [`crate::SendSms::per_recipient`].
+    pub messages: BTreeMap<RawPhoneNumber, MessageText>,
+    /// Largest segment count among the rendered messages.
+    pub max_segments: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Errors produced while parsing or rendering a [`MessageTemplate`].
+pub enum TemplateError {
+    /// The template text is malformed at byte offset `position`.
+    Syntax {
+        position: usize,
+        reason: &'static str,
+    },
+    /// Placeholders without a value (for `recipient`, when rendering per recipient).
+    MissingVariables {
+        recipient: Option<RawPhoneNumber>,
+        names: Vec<String>,
+    },
+    /// Variables that do not match any placeholder.
+    UnusedVariables {
+        recipient: Option<RawPhoneNumber>,
+        names: Vec<String>,
+    },
+    /// The rendered text is not a valid [`MessageText`].
+    InvalidMessage {
+        recipient: Option<RawPhoneNumber>,
+        source: ValidationError,
+    },
+}
+
+impl MessageTemplate {
+    /// Parse and validate a template.
+    pub fn parse(input: impl Into<String>) -> Result<Self, TemplateError> {
+        let source = input.into();
+        let mut parts = Vec::new();
+        let mut placeholders = BTreeSet::new();
+        let mut literal = String::new();
+        let mut chars = source.char_indices().peekable();
+
+        while let Some((position, ch)) = chars.next() {
+            match ch {
+                '{' if chars.peek().map(|(_, next)| *next) == Some('{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek().map(|(_, next)| *next) == Some('}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some((_, '}')) => break,
+                            Some((_, ch)) if ch.is_ascii_alphanumeric() || ch == '_' => {
+                                name.push(ch);
+                            }
+                            Some((position, _)) => {
+                                return Err(TemplateError::Syntax {
+                                    position,
+                                    reason: "invalid character in placeholder name",
+                                });
+                            }
+                            None => {
+                                return Err(TemplateError::Syntax {
+                                    position,
+                                    reason: "unclosed placeholder",
+                                });
+                            }
+                        }
+                    }
+                    if name.is_empty() {
+                        return Err(TemplateError::Syntax {
+                            position,
+                            reason: "empty placeholder name",
+                        });
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    placeholders.insert(name.clone());
+                    parts.push(Part::Placeholder(name));
+                }
+                '}' => {
+                    return Err(TemplateError::Syntax {
+                        position,
+                        reason: "unmatched `}`",
+                    });
+                }
+                _ => literal.push(ch),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+
+        Ok(Self {
+            source,
+            parts,
+            placeholders,
+        })
+    }
+
+    /// Borrow the template as provided.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Placeholder names used by the template.
+    pub fn placeholders(&self) -> &BTreeSet<String> {
+        &self.placeholders
+    }
+
+    /// Render the template with `variables`.
+    ///
+    /// Every placeholder must have a value and every variable must be used.
+    pub fn render(
+        &self,
+        variables: &BTreeMap<String, String>,
+    ) -> Result<MessageText, TemplateError> {
+        self.render_for(None, variables)
+    }
+
+    /// Render one message per recipient and report the worst-case segment count.
+    pub fn render_per_recipient(
+        &self,
+        variables: &BTreeMap<RawPhoneNumber, BTreeMap<String, String>>,
+    ) -> Result<RenderedMessages, TemplateError> {
+        let mut messages = BTreeMap::new();
+        let mut max_segments = 0;
+        for (phone, vars) in variables {
+            let text = self.render_for(Some(phone), vars)?;
+            max_segments = max_segments.max(text.length().segments);
+            messages.insert(phone.clone(), text);
+        }
+        Ok(RenderedMessages {
+            messages,
+            max_segments,
+        })
+    }
+
+    fn render_for(
+        &self,
+        recipient: Option<&RawPhoneNumber>,
+        variables: &BTreeMap<String, String>,
+    ) -> Result<MessageText, TemplateError> {
+        let missing = self
+            .placeholders
+            .iter()
+            .filter(|name| !variables.contains_key(*name))
+            .cloned()
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(TemplateError::MissingVariables {
+                recipient: recipient.cloned(),
+                names: missing,
+            });
+        }
+
+        let unused = variables
+            .keys()
+            .filter(|name| !self.placeholders.contains(*name))
+            .cloned()
+            .collect::<Vec<_>>();
+        if !unused.is_empty() {
+            return Err(TemplateError::UnusedVariables {
+                recipient: recipient.cloned(),
+                names: unused,
+            });
+        }
+
+        let mut text = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => text.push_str(literal),
+                Part::Placeholder(name) => text.push_str(&variables[name]),
+            }
+        }
+
+        MessageText::new(text).map_err(|source| TemplateError::InvalidMessage {
+            recipient: recipient.cloned(),
+            source,
+        })
+    }
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Syntax { position, reason } => {
+                write!(f, "invalid template at byte {position}: {reason}")
+            }
+            Self::MissingVariables { recipient, names } => {
+                write!(f, "missing template variables: {}", names.join(", "))?;
+                write_recipient(f, recipient.as_ref())
+            }
+            Self::UnusedVariables { recipient, names } => {
+                write!(f, "unused template variables: {}", names.join(", "))?;
+                write_recipient(f, recipient.as_ref())
+            }
+            Self::InvalidMessage { recipient, source } => {
+                write!(f, "rendered message is invalid: {source}")?;
+                write_recipient(f, recipient.as_ref())
+            }
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::InvalidMessage { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+fn write_recipient(f: &mut fmt::Formatter<'_>, recipient: Option<&RawPhoneNumber>) -> fmt::Result {
+    match recipient {
+        Some(phone) => write!(f, " (recipient {})", phone.raw()),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| ((*k).to_owned(), (*v).to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_collects_placeholders_and_escapes() {
+        let template = MessageTemplate::parse("Hi {name}, code {code} {{ok}}").unwrap();
+        assert_eq!(
+            template.placeholders().iter().collect::<Vec<_>>(),
+            vec!["code", "name"]
+        );
+        let text = template
+            .render(&vars(&[("name", "Ann"), ("code", "1234")]))
+            .unwrap();
+        assert_eq!(text.as_str(), "Hi Ann, code 1234 {ok}");
+    }
+
+    #[test]
+    fn parse_rejects_malformed_templates() {
+        assert_eq!(
+            MessageTemplate::parse("Hi {name").unwrap_err(),
+            TemplateError::Syntax {
+                position: 3,
+                reason: "unclosed placeholder"
+            }
+        );
+        assert!(matches!(
+            MessageTemplate::parse("Hi {}"),
+            Err(TemplateError::Syntax {
+                reason: "empty placeholder name",
+                ..
+            })
+        ));
+        assert!(matches!(
+            MessageTemplate::parse("Hi {first name}"),
+            Err(TemplateError::Syntax {
+                position: 9,
+                reason: "invalid character in placeholder name"
+            })
+        ));
+        assert!(matches!(
+            MessageTemplate::parse("oops }"),
+            Err(TemplateError::Syntax { position: 5, .. })
+        ));
+    }
+
+    #[test]
+    fn render_rejects_missing_and_unused_variables() {
+        let template = MessageTemplate::parse("Hi {name}").unwrap();
+        assert_eq!(
+            template.render(&vars(&[])).unwrap_err(),
+            TemplateError::MissingVariables {
+                recipient: None,
+                names: vec!["name".to_owned()]
+            }
+        );
+        assert_eq!(
+            template
+                .render(&vars(&[("name", "Ann"), ("extra", "x")]))
+                .unwrap_err(),
+            TemplateError::UnusedVariables {
+                recipient: None,
+                names: vec!["extra".to_owned()]
+            }
+        );
+
+        let blank = MessageTemplate::parse("{name}").unwrap();
+        assert!(matches!(
+            blank.render(&vars(&[("name", "  ")])),
+            Err(TemplateError::InvalidMessage { .. })
+        ));
+    }
+
+    #[test]
+    fn render_per_recipient_reports_worst_case_segments() {
+        let template = MessageTemplate::parse("Hello {name}").unwrap();
+        let short = RawPhoneNumber::new("79250000001").unwrap();
+        let long = RawPhoneNumber::new("79250000002").unwrap();
+        let mut variables = BTreeMap::new();
+        variables.insert(short.clone(), vars(&[("name", "Ann")]));
+        variables.insert(long.clone(), vars(&[("name", &"я".repeat(80))]));
+
+        let rendered = template.render_per_recipient(&variables).unwrap();
+        assert_eq!(rendered.messages[&short].as_str(), "Hello Ann");
+        assert_eq!(rendered.max_segments, 2);
+
+        variables.insert(short.clone(), vars(&[]));
+        let err = template.render_per_recipient(&variables).unwrap_err();
+        assert_eq!(
+            err,
+            TemplateError::MissingVariables {
+                recipient: Some(short),
+                names: vec!["name".to_owned()]
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "missing template variables: name (recipient 79250000001)"
+        );
+    }
+}
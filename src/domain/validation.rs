@@ -1,13 +1,58 @@
 use std::fmt;
 
+use crate::domain::encoding::MessageLength;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ValidationError {
-    Empty { field: &'static str },
-    TooManyRecipients { max: usize, actual: usize },
-    TooManySmsIds { max: usize, actual: usize },
-    InvalidPhoneNumber { input: String },
-    InvalidCallbackUrl { input: String },
-    TtlOutOfRange { min: u16, max: u16, actual: u16 },
+    Empty {
+        field: &'static str,
+    },
+    TooManyRecipients {
+        max: usize,
+        actual: usize,
+    },
+    TooManySmsIds {
+        max: usize,
+        actual: usize,
+    },
+    InvalidPhoneNumber {
+        input: String,
+    },
+    InvalidCallbackUrl {
+        input: String,
+    },
+    InvalidSenderId {
+        input: String,
+        reason: &'static str,
+    },
+    TtlOutOfRange {
+        min: u16,
+        max: u16,
+        actual: u16,
+    },
+    TimeInPast {
+        time: u64,
+        now: u64,
+    },
+    TimeTooFarAhead {
+        time: u64,
+        max: u64,
+    },
+    ConflictingOptions {
+        first: &'static str,
+        second: &'static str,
+    },
+    MessageTooLong {
+        max: usize,
+        length: MessageLength,
+    },
+    TooManySegments {
+        max: u32,
+        length: MessageLength,
+    },
+    ConflictingTexts {
+        phone: String,
+    },
 }
 
 impl fmt::Display for ValidationError {
@@ -22,12 +67,41 @@ impl fmt::Display for ValidationError {
             }
             Self::InvalidPhoneNumber { input } => write!(f, "invalid phone number: {input}"),
             Self::InvalidCallbackUrl { input } => write!(f, "invalid callback url: {input}"),
+            Self::InvalidSenderId { input, reason } => {
+                write!(f, "invalid sender id {input:?}: {reason}")
+            }
             Self::TtlOutOfRange { min, max, actual } => {
                 write!(
                     f,
                     "ttl minutes out of range: {actual} (expected {min}..={max})"
                 )
             }
+            Self::TimeInPast { time, now } => {
+                write!(f, "scheduled time {time} is in the past (now {now})")
+            }
+            Self::TimeTooFarAhead { time, max } => {
+                write!(f, "scheduled time {time} is too far ahead (max {max})")
+            }
+            Self::ConflictingOptions { first, second } => {
+                write!(f, "{first} and {second} cannot be combined")
+            }
+            Self::MessageTooLong { max, length } => {
+                write!(
+                    f,
+                    "message too long: {} characters (max {max})",
+                    length.chars
+                )
+            }
+            Self::TooManySegments { max, length } => {
+                write!(
+                    f,
+                    "message needs {} {} segments (max {max})",
+                    length.segments, length.encoding
+                )
+            }
+            Self::ConflictingTexts { phone } => {
+                write!(f, "different texts for the same recipient {phone}")
+            }
         }
     }
 }
@@ -59,6 +133,18 @@ mod tests {
         };
         assert_eq!(err.to_string(), "invalid callback url: bad-url");
 
+        let length = crate::domain::MessageLength::measure(&"я".repeat(71));
+        let err = ValidationError::MessageTooLong { max: 70, length };
+        assert_eq!(err.to_string(), "message too long: 71 characters (max 70)");
+        let err = ValidationError::TooManySegments { max: 1, length };
+        assert_eq!(err.to_string(), "message needs 2 UCS-2 segments (max 1)");
+
+        let err = ValidationError::InvalidSenderId {
+            input: "A".to_owned(),
+            reason: "too short",
+        };
+        assert_eq!(err.to_string(), "invalid sender id \"A\": too short");
+
         let err = ValidationError::TtlOutOfRange {
             min: 1,
             max: 10,
@@ -68,5 +154,28 @@ mod tests {
             err.to_string(),
             "ttl minutes out of range: 11 (expected 1..=10)"
         );
+
+        let err = ValidationError::TimeInPast { time: 5, now: 10 };
+        assert_eq!(err.to_string(), "scheduled time 5 is in the past (now 10)");
+
+        let err = ValidationError::TimeTooFarAhead { time: 20, max: 15 };
+        assert_eq!(
+            err.to_string(),
+            "scheduled time 20 is too far ahead (max 15)"
+        );
+
+        let err = ValidationError::ConflictingOptions {
+            first: "time",
+            second: "daytime",
+        };
+        assert_eq!(err.to_string(), "time and daytime cannot be combined");
+
+        let err = ValidationError::ConflictingTexts {
+            phone: "79251234567".to_owned(),
+        };
+        assert_eq!(
+            err.to_string(),
+            "different texts for the same recipient 79251234567"
+        );
     }
 }
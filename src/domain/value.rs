@@ -1,7 +1,10 @@
+use crate::domain::encoding::{MessageLength, MessageLimits};
 use crate::domain::validation::ValidationError;
 
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use phonenumber::country;
-use url::Url;
+use url::{Position, Url};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 /// SMS.RU `api_id` token.
@@ -109,13 +112,22 @@ impl PartnerId {
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 /// SMS.RU sender id (`from`).
 ///
-/// Invariant: non-empty after trimming. The value must be enabled in your SMS.RU account.
+/// Invariant: non-empty after trimming and follows SMS.RU's sender-name rules: either
+/// 2..=11 characters of Latin letters, digits, spaces, `.`, `-` and `_` with at least one letter,
+/// or a numeric sender of up to 15 digits. The value must also be enabled in your SMS.RU account
+/// (see [`crate::SmsRuClientBuilder::approved_senders_ttl`]).
 pub struct SenderId(String);
 
 impl SenderId {
     /// Form field name used by SMS.RU (`from`).
     pub const FIELD: &'static str = "from";
 
+    /// Maximum length of an alphanumeric sender name.
+    pub const MAX_ALPHANUMERIC_LEN: usize = 11;
+
+    /// Maximum length of a numeric sender.
+    pub const MAX_NUMERIC_LEN: usize = 15;
+
     /// Create a validated [`SenderId`].
     pub fn new(value: impl Into<String>) -> Result<Self, ValidationError> {
         let value = value.into();
@@ -123,9 +135,44 @@ impl SenderId {
         if trimmed.is_empty() {
             return Err(ValidationError::Empty { field: Self::FIELD });
         }
+
+        let invalid = |reason| ValidationError::InvalidSenderId {
+            input: trimmed.to_owned(),
+            reason,
+        };
+        if trimmed.bytes().all(|b| b.is_ascii_digit()) {
+            if trimmed.len() > Self::MAX_NUMERIC_LEN {
+                return Err(invalid("numeric sender is longer than 15 digits"));
+            }
+            return Ok(Self(trimmed.to_owned()));
+        }
+
+        let allowed = |c: char| c.is_ascii_alphanumeric() || matches!(c, ' ' | '.' | '-' | '_');
+        if !trimmed.chars().all(allowed) {
+            return Err(invalid(
+                "only Latin letters, digits, spaces, '.', '-' and '_' are allowed",
+            ));
+        }
+        if !trimmed.chars().any(|c| c.is_ascii_alphabetic()) {
+            return Err(invalid(
+                "sender name must contain at least one Latin letter",
+            ));
+        }
+        let len = trimmed.chars().count();
+        if !(2..=Self::MAX_ALPHANUMERIC_LEN).contains(&len) {
+            return Err(invalid("sender name must be 2 to 11 characters long"));
+        }
         Ok(Self(trimmed.to_owned()))
     }
 
+    /// Restore a sender persisted by an earlier version, which only required it to be non-empty.
+    pub(crate) fn stored(value: &str) -> Result<Self, ValidationError> {
+        match Self::new(value) {
+            Err(ValidationError::InvalidSenderId { input, .. }) => Ok(Self(input)),
+            result => result,
+        }
+    }
+
     /// Borrow the validated sender id.
     pub fn as_str(&self) -> &str {
         &self.0
@@ -155,6 +202,21 @@ impl MessageText {
     pub fn as_str(&self) -> &str {
         &self.0
     }
+
+    /// Create message text that also fits `limits`.
+    pub fn with_limits(
+        value: impl Into<String>,
+        limits: &MessageLimits,
+    ) -> Result<Self, ValidationError> {
+        let text = Self::new(value)?;
+        limits.check(&text.0)?;
+        Ok(text)
+    }
+
+    /// Measure encoding, length, and segment count of the text.
+    pub fn length(&self) -> MessageLength {
+        MessageLength::measure(&self.0)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -219,6 +281,22 @@ impl CallbackUrl {
     pub fn as_str(&self) -> &str {
         &self.0
     }
+
+    /// Canonical form used to compare callback URLs.
+    ///
+    /// Scheme and host are lowercased, default ports are dropped, and trailing slashes are
+    /// removed from the path, so `https://Example.com:443/hook/` and `https://example.com/hook`
+    /// normalize to the same value.
+    pub fn normalized(&self) -> String {
+        let Ok(parsed) = Url::parse(&self.0) else {
+            return self.0.clone();
+        };
+
+        let mut normalized = parsed[..Position::BeforePath].to_owned();
+        normalized.push_str(parsed.path().trim_end_matches('/'));
+        normalized.push_str(&parsed[Position::AfterPath..]);
+        normalized
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -247,6 +325,33 @@ impl SmsId {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// Caller-chosen key identifying one logical send for
+/// [`crate::SmsRuClient::send_sms_idempotent`].
+///
+/// Invariant: non-empty after trimming.
+pub struct IdempotencyKey(String);
+
+impl IdempotencyKey {
+    /// Field name used in validation errors (`idempotency_key`).
+    pub const FIELD: &'static str = "idempotency_key";
+
+    /// Create a validated [`IdempotencyKey`].
+    pub fn new(value: impl Into<String>) -> Result<Self, ValidationError> {
+        let value = value.into();
+        let trimmed = value.trim();
+        if trimmed.is_empty() {
+            return Err(ValidationError::Empty { field: Self::FIELD });
+        }
+        Ok(Self(trimmed.to_owned()))
+    }
+
+    /// Borrow the validated key.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 /// SMS.RU call-auth check id (`check_id`) returned by `callcheck/add`.
 ///
@@ -397,15 +502,49 @@ impl UnixTimestamp {
     /// Form field name used by SMS.RU (`time`).
     pub const FIELD: &'static str = "time";
 
+    /// How far ahead SMS.RU accepts a scheduled send (7 days).
+    pub const MAX_SCHEDULE_AHEAD: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
     /// Create a timestamp value (no range validation is performed).
     pub fn new(value: u64) -> Self {
         Self(value)
     }
 
+    /// Convert a [`SystemTime`] to whole seconds; times before the epoch become `0`.
+    pub fn from_system_time(time: SystemTime) -> Self {
+        Self(
+            time.duration_since(UNIX_EPOCH)
+                .map_or(0, |since_epoch| since_epoch.as_secs()),
+        )
+    }
+
+    /// Timestamp `delay` from now.
+    pub fn in_duration(delay: Duration) -> Self {
+        Self::from_system_time(SystemTime::now() + delay)
+    }
+
     /// Get the underlying timestamp in seconds.
     pub fn value(self) -> u64 {
         self.0
     }
+
+    /// Convert back to a [`SystemTime`].
+    pub fn to_system_time(self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.0)
+    }
+
+    /// Check that the timestamp is not before `now` and within [`Self::MAX_SCHEDULE_AHEAD`].
+    pub fn validate_schedule(self, now: SystemTime) -> Result<(), ValidationError> {
+        let now = Self::from_system_time(now).0;
+        if self.0 < now {
+            return Err(ValidationError::TimeInPast { time: self.0, now });
+        }
+        let max = now + Self::MAX_SCHEDULE_AHEAD.as_secs();
+        if self.0 > max {
+            return Err(ValidationError::TimeTooFarAhead { time: self.0, max });
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -725,6 +864,80 @@ mod tests {
         assert!(CallCheckId::new("  ").is_err());
     }
 
+    #[test]
+    fn message_text_with_limits_checks_length() {
+        let limits = MessageLimits::new().max_chars(5);
+        assert_eq!(
+            MessageText::with_limits("hello", &limits).unwrap().as_str(),
+            "hello"
+        );
+        assert!(matches!(
+            MessageText::with_limits("hello!", &limits),
+            Err(ValidationError::MessageTooLong { max: 5, length }) if length.chars == 6
+        ));
+        assert!(matches!(
+            MessageText::with_limits(" ", &limits),
+            Err(ValidationError::Empty { .. })
+        ));
+    }
+
+    #[test]
+    fn sender_id_follows_sender_name_rules() {
+        assert_eq!(
+            SenderId::new("My-Shop_1.r").unwrap().as_str(),
+            "My-Shop_1.r"
+        );
+        assert_eq!(SenderId::new("My Shop").unwrap().as_str(), "My Shop");
+        assert!(SenderId::new("79251234567").is_ok());
+
+        for invalid in [
+            "A",
+            "TwelveChars!",
+            "VeryLongSender",
+            "Магазин",
+            "1234567890123456",
+            "--",
+            "..",
+            "12-34",
+        ] {
+            assert!(
+                matches!(
+                    SenderId::new(invalid),
+                    Err(ValidationError::InvalidSenderId { .. })
+                ),
+                "{invalid} should be rejected"
+            );
+        }
+
+        assert!(matches!(
+            SenderId::new("TwelveChars1"),
+            Err(ValidationError::InvalidSenderId { reason, .. })
+                if reason == "sender name must be 2 to 11 characters long"
+        ));
+        assert!(matches!(
+            SenderId::new("12-34"),
+            Err(ValidationError::InvalidSenderId { reason, .. })
+                if reason == "sender name must contain at least one Latin letter"
+        ));
+    }
+
+    #[test]
+    fn callback_url_normalization_ignores_trailing_slash_and_default_port() {
+        let a = CallbackUrl::new("https://Example.com:443/hook/").unwrap();
+        let b = CallbackUrl::new("https://example.com/hook").unwrap();
+        assert_eq!(a.normalized(), "https://example.com/hook");
+        assert_eq!(a.normalized(), b.normalized());
+
+        let root = CallbackUrl::new("http://example.com:80/").unwrap();
+        assert_eq!(root.normalized(), "http://example.com");
+
+        let custom_port = CallbackUrl::new("https://example.com:8443/hook?x=1").unwrap();
+        assert_eq!(
+            custom_port.normalized(),
+            "https://example.com:8443/hook?x=1"
+        );
+    }
+
     #[test]
     fn raw_phone_number_trims_and_exposes_raw() {
         let raw = RawPhoneNumber::new(" +79251234567 ").unwrap();
@@ -745,6 +958,39 @@ mod tests {
         assert!(PhoneNumber::parse(None, "not-a-number").is_err());
     }
 
+    #[test]
+    fn unix_timestamp_converts_and_validates_schedule() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        assert_eq!(UnixTimestamp::from_system_time(now).value(), 1_000_000);
+        assert_eq!(
+            UnixTimestamp::new(1_000_000).to_system_time(),
+            UNIX_EPOCH + Duration::from_secs(1_000_000)
+        );
+        assert_eq!(
+            UnixTimestamp::from_system_time(UNIX_EPOCH - Duration::from_secs(1)).value(),
+            0
+        );
+
+        assert!(UnixTimestamp::new(1_000_000).validate_schedule(now).is_ok());
+        assert_eq!(
+            UnixTimestamp::new(999_999).validate_schedule(now),
+            Err(ValidationError::TimeInPast {
+                time: 999_999,
+                now: 1_000_000
+            })
+        );
+
+        let max = 1_000_000 + UnixTimestamp::MAX_SCHEDULE_AHEAD.as_secs();
+        assert!(UnixTimestamp::new(max).validate_schedule(now).is_ok());
+        assert_eq!(
+            UnixTimestamp::new(max + 1).validate_schedule(now),
+            Err(ValidationError::TimeTooFarAhead { time: max + 1, max })
+        );
+
+        let later = UnixTimestamp::in_duration(Duration::from_secs(60));
+        assert!(later.validate_schedule(SystemTime::now()).is_ok());
+    }
+
     #[test]
     fn ttl_minutes_enforces_range() {
         assert!(TtlMinutes::new(TtlMinutes::MIN).is_ok());
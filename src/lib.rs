@@ -33,19 +33,44 @@
 //! ```
 #![forbid(unsafe_code)]
 
+pub mod campaign;
 pub mod client;
+mod csv;
 pub mod domain;
+pub mod outbox;
+pub mod report;
 mod transport;
 
-pub use client::{Auth, SmsRuClient, SmsRuClientBuilder, SmsRuError};
+pub use campaign::{
+    Campaign, CampaignError, CampaignFailure, CampaignMessage, CampaignRecipient, CampaignReport,
+    CostPreview, CsvImport, RejectReason, RejectedRow,
+};
+pub use client::{
+    AbuseDecision, AbuseGuard, AbuseGuardViolation, AbuseOperation, AbuseReason, Auth, BulkCancel,
+    BulkChunk, BulkProgress, BulkReport, BulkSend, BulkSender, DailyLimitExceeded, DailyLimitGuard,
+    DailyLimitedSend, IdempotencyEntry, IdempotencyStore, InMemoryIdempotencyStore, PoolPolicy,
+    PooledSend, RecipientPolicy, RecipientPolicyViolation, RecipientRejection, RoutedSend,
+    SenderRouter, SmsRuClient, SmsRuClientBuilder, SmsRuError, SmsRuErrorKind, SmsRuPool,
+    SmsRuPoolBuilder, SmsRuPoolError, SpendGuard, SpendGuardViolation,
+};
+#[cfg(feature = "tower")]
+pub use client::{SmsRuFuture, SmsRuRequest, SmsRuResponse};
 pub use domain::{
     AddCallback, AddStoplistEntry, ApiId, BalanceResponse, CallCheckId, CallCheckStatusCode,
-    CallbackUrl, CallbacksResponse, CheckCallAuthStatus, CheckCallAuthStatusOptions,
-    CheckCallAuthStatusResponse, CheckCost, CheckCostOptions, CheckCostResponse, CheckStatus,
-    CheckStatusResponse, FreeUsageResponse, JsonMode, KnownCallCheckStatusCode, KnownStatusCode,
-    LimitUsageResponse, Login, MessageText, PartnerId, Password, PhoneNumber, RawPhoneNumber,
-    RemoveCallback, RemoveStoplistEntry, SendOptions, SendSms, SendSmsResponse, SenderId,
-    SendersResponse, SmsCostResult, SmsId, SmsResult, SmsStatusResult, StartCallAuth,
-    StartCallAuthOptions, StartCallAuthResponse, Status, StatusCode, StatusOnlyResponse,
-    StoplistResponse, StoplistText, TtlMinutes, UnixTimestamp, ValidationError,
+    CallbackSyncPolicy, CallbackSyncReport, CallbackUrl, CallbacksResponse, CheckCallAuthStatus,
+    CheckCallAuthStatusOptions, CheckCallAuthStatusResponse, CheckCost, CheckCostOptions,
+    CheckCostResponse, CheckStatus, CheckStatusResponse, DeliveryFailure, DeliveryState,
+    DeliveryTransitionError, FreeUsageResponse, IdempotencyKey, JsonMode, KnownCallCheckStatusCode,
+    KnownStatusCode, Lang, LimitUsageResponse, Login, MessageLength, MessageLimits,
+    MessageTemplate, MessageText, NormalizedSendSms, PartnerId, Password, PhoneNumber,
+    RawPhoneNumber, RecipientFailure, RemoveCallback, RemoveStoplistEntry, RenderedMessages,
+    SendOptions, SendSms, SendSmsResponse, SenderId, SendersResponse, SmsCostResult, SmsEncoding,
+    SmsId, SmsResult, SmsStatusResult, StartCallAuth, StartCallAuthOptions, StartCallAuthResponse,
+    Status, StatusCode, StatusOnlyResponse, StoplistResponse, StoplistText, TemplateError,
+    TtlMinutes, UnixTimestamp, ValidationError,
+};
+pub use outbox::{
+    Outbox, OutboxEntry, OutboxError, OutboxId, OutboxLease, OutboxOutcome, OutboxResult,
+    OutboxState,
 };
+pub use report::{DeliveryReport, REPORT_COLUMNS, ReportFormat, ReportRow, ReportWriter};
@@ -0,0 +1,1093 @@
+//! Durable, file-backed outbox for `send_sms` requests.
+//!
+//! Every intended send is written to disk before the HTTP call and its outcome (SMS ids and
+//! status codes, or the last error) is recorded afterwards, so a crash between the two never
+//! silently loses a message.
+//!
+//! The outbox directory holds one JSON file per message in five sub-directories:
+//! `pending/`, `leased/`, `sent/`, `uncertain/`, and `dead/`. Workers claim a message by
+//! atomically renaming it from `pending/` to `leased/`, which makes it safe for several workers
+//! (threads or processes sharing the directory) to drain the same outbox. A lease that is not
+//! completed or failed before it expires is returned to `pending/` by the next
+//! [`Outbox::lease`] call. Records that cannot be decoded are moved to `corrupt/`.
+//!
+//! A failed send that SMS.RU may still have accepted (a transport error, a 5xx response, or an
+//! unreadable answer) is moved to `uncertain/` instead of being retried; check its delivery and
+//! call [`Outbox::requeue`] to send it again.
+//!
+//! The lease TTL is part of the delivery guarantee: once a lease expires, another worker may
+//! send the message again, and [`Outbox::complete`] / [`Outbox::fail`] on the old lease return
+//! [`OutboxError::LeaseExpired`]. Use a TTL well above the client's request timeout. A worker
+//! that crashes after SMS.RU accepted a message but before [`Outbox::complete`] leaves a lease
+//! that will be sent again, so delivery is at-least-once.
+
+use std::collections::BTreeMap;
+use std::error::Error as StdError;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::client::{SmsRuClient, SmsRuError, SmsRuErrorKind};
+use crate::domain::{
+    JsonMode, MessageText, PartnerId, RawPhoneNumber, SendOptions, SendSms, SendSmsResponse,
+    SenderId, SmsId, StatusCode, TtlMinutes, UnixTimestamp, ValidationError,
+};
+
+const PENDING_DIR: &str = "pending";
+const LEASED_DIR: &str = "leased";
+const SENT_DIR: &str = "sent";
+const UNCERTAIN_DIR: &str = "uncertain";
+const DEAD_DIR: &str = "dead";
+const CORRUPT_DIR: &str = "corrupt";
+const TMP_DIR: &str = "tmp";
+
+/// How long [`Outbox::complete`] and [`Outbox::fail`] hold a lease while they write the outcome.
+const RECORD_LEASE_TTL: Duration = Duration::from_secs(60);
+
+static NEXT_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// Identifier of an outbox message.
+///
+/// Ids are unique across processes sharing the directory and sort by enqueue time.
+pub struct OutboxId(String);
+
+impl OutboxId {
+    /// Borrow the id as a string.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    fn generate() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let sequence = NEXT_SEQUENCE.fetch_add(1, Ordering::Relaxed);
+        Self(format!("{nanos:024}-{:010}-{sequence:08}", process::id()))
+    }
+}
+
+impl fmt::Display for OutboxId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Where a message currently is in the outbox.
+pub enum OutboxState {
+    /// Waiting to be leased by a worker.
+    Pending,
+    /// Claimed by a worker until its lease expires.
+    Leased,
+    /// Accepted by SMS.RU; per-recipient results are recorded.
+    Sent,
+    /// The last attempt failed in a way SMS.RU may still have accepted; not retried.
+    Uncertain,
+    /// Failed permanently or ran out of attempts.
+    Dead,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Per-recipient result recorded for a sent message.
+pub struct OutboxResult {
+    /// Per-recipient status code.
+    pub status_code: StatusCode,
+    /// SMS id assigned by SMS.RU, if the recipient was accepted.
+    pub sms_id: Option<SmsId>,
+}
+
+#[derive(Debug, Clone)]
+/// Snapshot of a stored message, returned by [`Outbox::get`] and [`Outbox::dead_letters`].
+pub struct OutboxEntry {
+    /// Message id.
+    pub id: OutboxId,
+    /// Current state.
+    pub state: OutboxState,
+    /// The request as it was enqueued.
+    pub request: SendSms,
+    /// Number of failed send attempts so far.
+    pub attempts: u32,
+    /// Error message of the last failed attempt.
+    pub last_error: Option<String>,
+    /// Per-recipient results (only for [`OutboxState::Sent`]).
+    pub results: BTreeMap<RawPhoneNumber, OutboxResult>,
+}
+
+impl OutboxEntry {
+    /// SMS ids of accepted recipients.
+    pub fn sms_ids(&self) -> Vec<&SmsId> {
+        self.results
+            .values()
+            .filter_map(|result| result.sms_id.as_ref())
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+/// A message claimed by [`Outbox::lease`].
+///
+/// Pass it back to [`Outbox::complete`] or [`Outbox::fail`] before the lease expires.
+pub struct OutboxLease {
+    id: OutboxId,
+    path: PathBuf,
+    request: SendSms,
+    record: StoredRecord,
+}
+
+impl OutboxLease {
+    /// Id of the leased message.
+    pub fn id(&self) -> &OutboxId {
+        &self.id
+    }
+
+    /// The request to send.
+    pub fn request(&self) -> &SendSms {
+        &self.request
+    }
+
+    /// Number of failed send attempts before this lease.
+    pub fn attempts(&self) -> u32 {
+        self.record.attempts
+    }
+}
+
+#[derive(Debug)]
+/// Outcome of [`Outbox::process_next`] for one leased message.
+pub enum OutboxOutcome {
+    /// SMS.RU accepted the request; results are recorded.
+    Sent {
+        id: OutboxId,
+        response: SendSmsResponse,
+    },
+    /// The send failed with a retryable error and the message is pending again.
+    Retrying { id: OutboxId, error: SmsRuError },
+    /// SMS.RU may have accepted the send despite the error; the message was parked as
+    /// [`OutboxState::Uncertain`].
+    Uncertain { id: OutboxId, error: SmsRuError },
+    /// The send failed permanently (or ran out of attempts) and was dead-lettered.
+    DeadLettered { id: OutboxId, error: SmsRuError },
+}
+
+#[derive(Debug, thiserror::Error)]
+/// Errors produced by [`Outbox`] operations.
+pub enum OutboxError {
+    /// Reading or writing the outbox directory failed.
+    #[error("outbox I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    /// A stored message file could not be decoded.
+    #[error("corrupt outbox record {path:?}: {source}")]
+    Corrupt {
+        path: PathBuf,
+        #[source]
+        source: Box<dyn StdError + Send + Sync>,
+    },
+
+    /// The lease expired and the message was reclaimed before it was completed or failed.
+    #[error("outbox lease for {0} has expired")]
+    LeaseExpired(OutboxId),
+}
+
+#[derive(Debug, Clone)]
+/// File-backed outbox rooted at a directory.
+///
+/// `Outbox` is cheap to clone; all state lives on disk.
+pub struct Outbox {
+    root: PathBuf,
+}
+
+impl Outbox {
+    /// Open (creating if needed) an outbox in `dir`.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, OutboxError> {
+        let root = dir.as_ref().to_path_buf();
+        for sub in [
+            PENDING_DIR,
+            LEASED_DIR,
+            SENT_DIR,
+            UNCERTAIN_DIR,
+            DEAD_DIR,
+            CORRUPT_DIR,
+            TMP_DIR,
+        ] {
+            fs::create_dir_all(root.join(sub))?;
+        }
+        Ok(Self { root })
+    }
+
+    /// Persist `request` as pending and return its id.
+    pub fn enqueue(&self, request: &SendSms) -> Result<OutboxId, OutboxError> {
+        let id = OutboxId::generate();
+        let record = StoredRecord {
+            request: StoredRequest::from_request(request),
+            attempts: 0,
+            last_error: None,
+            results: BTreeMap::new(),
+        };
+        self.write_record(&id, &record, &self.pending_path(&id))?;
+        Ok(id)
+    }
+
+    /// Claim the oldest pending message for `ttl`, or return `None` if nothing is pending.
+    ///
+    /// Expired leases are returned to pending first. A record that cannot be decoded is moved to
+    /// `corrupt/` and reported once as [`OutboxError::Corrupt`].
+    pub fn lease(&self, ttl: Duration) -> Result<Option<OutboxLease>, OutboxError> {
+        self.reclaim_expired()?;
+
+        let expires_at = unix_millis(SystemTime::now() + ttl);
+        for id in self.list_ids(PENDING_DIR)? {
+            let leased = self
+                .root
+                .join(LEASED_DIR)
+                .join(format!("{id}.{expires_at}.json"));
+            match fs::rename(self.pending_path(&id), &leased) {
+                Ok(()) => {}
+                // Another worker claimed it first.
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err.into()),
+            }
+
+            let decoded = read_record(&leased).and_then(|record| {
+                let request = record
+                    .request
+                    .to_request()
+                    .map_err(|err| corrupt(&leased, err))?;
+                Ok((record, request))
+            });
+            let (record, request) = match decoded {
+                Ok(decoded) => decoded,
+                Err(OutboxError::Corrupt { source, .. }) => {
+                    let path = self.state_path(CORRUPT_DIR, &id);
+                    fs::rename(&leased, &path)?;
+                    return Err(OutboxError::Corrupt { path, source });
+                }
+                Err(err) => return Err(err),
+            };
+            return Ok(Some(OutboxLease {
+                id,
+                path: leased,
+                request,
+                record,
+            }));
+        }
+        Ok(None)
+    }
+
+    /// Record that SMS.RU accepted the leased message.
+    ///
+    /// An expired lease is still completed if no other worker has leased the message since.
+    /// Otherwise this returns [`OutboxError::LeaseExpired`] and the other worker's outcome wins.
+    pub fn complete(
+        &self,
+        lease: OutboxLease,
+        response: &SendSmsResponse,
+    ) -> Result<(), OutboxError> {
+        let claimed = self.claim(&lease)?;
+        let mut record = lease.record;
+        record.results = response
+            .sms
+            .iter()
+            .map(|(phone, result)| {
+                (
+                    phone.raw().to_owned(),
+                    StoredResult {
+                        status_code: result.status_code.as_i32(),
+                        sms_id: result.sms_id.as_ref().map(|id| id.as_str().to_owned()),
+                    },
+                )
+            })
+            .collect();
+        self.write_record(&lease.id, &record, &self.state_path(SENT_DIR, &lease.id))?;
+        remove_if_exists(&claimed)?;
+        Ok(())
+    }
+
+    /// Record a failed attempt for the leased message and return its new state.
+    ///
+    /// Errors after which SMS.RU may still have accepted the message (transport errors, 5xx
+    /// responses, unreadable answers) park it as [`OutboxState::Uncertain`] so it is not sent
+    /// twice. Permanent errors (see [`is_permanent_error`]) and the `max_attempts`-th failure
+    /// dead-letter it; other failures return it to pending.
+    pub fn fail(
+        &self,
+        lease: OutboxLease,
+        error: &SmsRuError,
+        max_attempts: u32,
+    ) -> Result<OutboxState, OutboxError> {
+        let claimed = self.claim(&lease)?;
+        let mut record = lease.record;
+        record.attempts = record.attempts.saturating_add(1);
+        record.last_error = Some(error.to_string());
+        let (state, dir) = if error.may_have_been_accepted() {
+            (OutboxState::Uncertain, UNCERTAIN_DIR)
+        } else if is_permanent_error(error) || record.attempts >= max_attempts {
+            (OutboxState::Dead, DEAD_DIR)
+        } else {
+            (OutboxState::Pending, PENDING_DIR)
+        };
+        self.write_record(&lease.id, &record, &self.state_path(dir, &lease.id))?;
+        remove_if_exists(&claimed)?;
+        Ok(state)
+    }
+
+    /// Return an uncertain message to pending once you have checked it was not delivered.
+    ///
+    /// Returns `false` if `id` is not in the uncertain state.
+    pub fn requeue(&self, id: &OutboxId) -> Result<bool, OutboxError> {
+        match fs::rename(self.state_path(UNCERTAIN_DIR, id), self.pending_path(id)) {
+            Ok(()) => Ok(true),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Re-lease `lease` under a fresh expiry so it cannot be reclaimed while its outcome is
+    /// written; if we crash before removing it, it is reclaimed like any expired lease.
+    ///
+    /// A lease that expired and was returned to pending is claimed from there; one that another
+    /// worker has leased again yields [`OutboxError::LeaseExpired`].
+    fn claim(&self, lease: &OutboxLease) -> Result<PathBuf, OutboxError> {
+        let expires_at = unix_millis(SystemTime::now() + RECORD_LEASE_TTL);
+        let claimed = self
+            .root
+            .join(LEASED_DIR)
+            .join(format!("{}.{expires_at}.json", lease.id));
+        for source in [lease.path.clone(), self.pending_path(&lease.id)] {
+            match fs::rename(&source, &claimed) {
+                Ok(()) => return Ok(claimed),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Err(OutboxError::LeaseExpired(lease.id.clone()))
+    }
+
+    /// Lease the next pending message, send it with `client`, and record the outcome.
+    ///
+    /// Returns `None` when nothing is pending. A [`SmsRuError::RecipientsFailed`] response is
+    /// recorded as sent, with the rejected recipients' status codes in the results; other
+    /// errors are recorded with [`Outbox::fail`].
+    pub async fn process_next(
+        &self,
+        client: &SmsRuClient,
+        lease_ttl: Duration,
+        max_attempts: u32,
+    ) -> Result<Option<OutboxOutcome>, OutboxError> {
+        let Some(lease) = self.blocking(move |outbox| outbox.lease(lease_ttl)).await? else {
+            return Ok(None);
+        };
+        let id = lease.id.clone();
+        let response = match client.send_sms(lease.request.clone()).await {
+            Ok(response) => response,
+            // The send happened; rejected recipients are recorded in the results.
+            Err(SmsRuError::RecipientsFailed(response)) => *response,
+            Err(error) => {
+                let (state, error) = self
+                    .blocking(move |outbox| {
+                        let state = outbox.fail(lease, &error, max_attempts)?;
+                        Ok((state, error))
+                    })
+                    .await?;
+                return Ok(Some(match state {
+                    OutboxState::Uncertain => OutboxOutcome::Uncertain { id, error },
+                    OutboxState::Dead => OutboxOutcome::DeadLettered { id, error },
+                    _ => OutboxOutcome::Retrying { id, error },
+                }));
+            }
+        };
+        let response = self
+            .blocking(move |outbox| {
+                outbox.complete(lease, &response)?;
+                Ok(response)
+            })
+            .await?;
+        Ok(Some(OutboxOutcome::Sent { id, response }))
+    }
+
+    /// Run blocking file operations off the async runtime.
+    async fn blocking<T: Send + 'static>(
+        &self,
+        operation: impl FnOnce(Outbox) -> Result<T, OutboxError> + Send + 'static,
+    ) -> Result<T, OutboxError> {
+        let outbox = self.clone();
+        match tokio::task::spawn_blocking(move || operation(outbox)).await {
+            Ok(result) => result,
+            Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+            Err(err) => Err(io::Error::other(err).into()),
+        }
+    }
+
+    /// Look up a message in any state.
+    pub fn get(&self, id: &OutboxId) -> Result<Option<OutboxEntry>, OutboxError> {
+        for (dir, state) in [
+            (SENT_DIR, OutboxState::Sent),
+            (UNCERTAIN_DIR, OutboxState::Uncertain),
+            (DEAD_DIR, OutboxState::Dead),
+            (PENDING_DIR, OutboxState::Pending),
+        ] {
+            let path = self.state_path(dir, id);
+            if path.exists() {
+                return entry(id.clone(), state, &path).map(Some);
+            }
+        }
+
+        for (leased_id, path) in self.list_leases()? {
+            if &leased_id == id {
+                return entry(leased_id, OutboxState::Leased, &path).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// All dead-lettered messages, oldest first.
+    pub fn dead_letters(&self) -> Result<Vec<OutboxEntry>, OutboxError> {
+        self.entries(DEAD_DIR, OutboxState::Dead)
+    }
+
+    /// All messages whose delivery is uncertain, oldest first.
+    pub fn uncertain(&self) -> Result<Vec<OutboxEntry>, OutboxError> {
+        self.entries(UNCERTAIN_DIR, OutboxState::Uncertain)
+    }
+
+    fn entries(&self, dir: &str, state: OutboxState) -> Result<Vec<OutboxEntry>, OutboxError> {
+        self.list_ids(dir)?
+            .into_iter()
+            .map(|id| {
+                let path = self.state_path(dir, &id);
+                entry(id, state, &path)
+            })
+            .collect()
+    }
+
+    fn reclaim_expired(&self) -> Result<(), OutboxError> {
+        let now = unix_millis(SystemTime::now());
+        for (id, path) in self.list_leases()? {
+            let expires_at = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.rsplit_once('.'))
+                .and_then(|(_, expires)| expires.parse::<u128>().ok())
+                .unwrap_or(0);
+            if expires_at > now {
+                continue;
+            }
+
+            // A lease whose outcome was already recorded is left over from a crash between
+            // writing the record and removing the lease file.
+            let recorded = [SENT_DIR, UNCERTAIN_DIR, DEAD_DIR, PENDING_DIR]
+                .into_iter()
+                .any(|dir| self.state_path(dir, &id).exists());
+            let result = if recorded {
+                fs::remove_file(&path)
+            } else {
+                fs::rename(&path, self.pending_path(&id))
+            };
+            match result {
+                Ok(()) => {}
+                // Completed, failed, or reclaimed by another worker in the meantime.
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(())
+    }
+
+    fn list_ids(&self, dir: &str) -> Result<Vec<OutboxId>, OutboxError> {
+        let mut ids = Vec::new();
+        for file in fs::read_dir(self.root.join(dir))? {
+            let name = file?.file_name();
+            if let Some(id) = name.to_str().and_then(|name| name.strip_suffix(".json")) {
+                ids.push(OutboxId(id.to_owned()));
+            }
+        }
+        ids.sort();
+        Ok(ids)
+    }
+
+    fn list_leases(&self) -> Result<Vec<(OutboxId, PathBuf)>, OutboxError> {
+        let mut leases = Vec::new();
+        for file in fs::read_dir(self.root.join(LEASED_DIR))? {
+            let path = file?.path();
+            let id = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.split_once('.'))
+                .map(|(id, _)| OutboxId(id.to_owned()));
+            if let Some(id) = id {
+                leases.push((id, path));
+            }
+        }
+        leases.sort();
+        Ok(leases)
+    }
+
+    fn write_record(
+        &self,
+        id: &OutboxId,
+        record: &StoredRecord,
+        target: &Path,
+    ) -> Result<(), OutboxError> {
+        let tmp = self.tmp_path(id);
+        let bytes = serde_json::to_vec(record).map_err(|err| corrupt(target, err))?;
+        fs::write(&tmp, bytes)?;
+        fs::File::open(&tmp)?.sync_all()?;
+        fs::rename(&tmp, target)?;
+        Ok(())
+    }
+
+    fn pending_path(&self, id: &OutboxId) -> PathBuf {
+        self.state_path(PENDING_DIR, id)
+    }
+
+    fn state_path(&self, dir: &str, id: &OutboxId) -> PathBuf {
+        self.root.join(dir).join(format!("{id}.json"))
+    }
+
+    fn tmp_path(&self, id: &OutboxId) -> PathBuf {
+        let sequence = NEXT_SEQUENCE.fetch_add(1, Ordering::Relaxed);
+        self.root
+            .join(TMP_DIR)
+            .join(format!("{id}.{}.{sequence}.tmp", process::id()))
+    }
+}
+
+/// Whether retrying `error` cannot succeed, so the message should be dead-lettered.
+///
+/// Errors whose [`SmsRuError::kind`] is retryable (transient or throttled) or unknown are
+/// retried; everything else is permanent. [`Outbox::fail`] checks first whether SMS.RU may
+/// have accepted the message and never retries those.
+pub fn is_permanent_error(error: &SmsRuError) -> bool {
+    !matches!(
+        error.kind(),
+        SmsRuErrorKind::Retryable | SmsRuErrorKind::Throttled | SmsRuErrorKind::Unknown
+    )
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredRecord {
+    request: StoredRequest,
+    attempts: u32,
+    last_error: Option<String>,
+    #[serde(default)]
+    results: BTreeMap<String, StoredResult>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "shape", rename_all = "snake_case")]
+enum StoredRequest {
+    ToMany {
+        recipients: Vec<String>,
+        msg: String,
+        options: StoredOptions,
+    },
+    PerRecipient {
+        messages: BTreeMap<String, String>,
+        options: StoredOptions,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredOptions {
+    plain: bool,
+    from: Option<String>,
+    ip: Option<IpAddr>,
+    time: Option<u64>,
+    ttl: Option<u16>,
+    daytime: bool,
+    translit: bool,
+    test: bool,
+    partner_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredResult {
+    status_code: i32,
+    sms_id: Option<String>,
+}
+
+impl StoredRequest {
+    fn from_request(request: &SendSms) -> Self {
+        match request {
+            SendSms::ToMany(to_many) => Self::ToMany {
+                recipients: to_many
+                    .recipients()
+                    .iter()
+                    .map(|phone| phone.raw().to_owned())
+                    .collect(),
+                msg: to_many.msg().as_str().to_owned(),
+                options: StoredOptions::from_options(to_many.options()),
+            },
+            SendSms::PerRecipient(per_recipient) => Self::PerRecipient {
+                messages: per_recipient
+                    .messages()
+                    .iter()
+                    .map(|(phone, msg)| (phone.raw().to_owned(), msg.as_str().to_owned()))
+                    .collect(),
+                options: StoredOptions::from_options(per_recipient.options()),
+            },
+        }
+    }
+
+    fn to_request(&self) -> Result<SendSms, ValidationError> {
+        match self {
+            Self::ToMany {
+                recipients,
+                msg,
+                options,
+            } => SendSms::to_many(
+                recipients
+                    .iter()
+                    .map(RawPhoneNumber::new)
+                    .collect::<Result<_, _>>()?,
+                MessageText::new(msg.as_str())?,
+                options.to_options()?,
+            ),
+            Self::PerRecipient { messages, options } => SendSms::per_recipient(
+                messages
+                    .iter()
+                    .map(|(phone, msg)| Ok((RawPhoneNumber::new(phone)?, MessageText::new(msg)?)))
+                    .collect::<Result<_, ValidationError>>()?,
+                options.to_options()?,
+            ),
+        }
+    }
+}
+
+impl StoredOptions {
+    fn from_options(options: &SendOptions) -> Self {
+        Self {
+            plain: options.json == JsonMode::Plain,
+            from: options.from.as_ref().map(|from| from.as_str().to_owned()),
+            ip: options.ip,
+            time: options.time.map(UnixTimestamp::value),
+            ttl: options.ttl.map(TtlMinutes::value),
+            daytime: options.daytime,
+            translit: options.translit,
+            test: options.test,
+            partner_id: options.partner_id.as_ref().map(|id| id.as_str().to_owned()),
+        }
+    }
+
+    fn to_options(&self) -> Result<SendOptions, ValidationError> {
+        Ok(SendOptions {
+            json: if self.plain {
+                JsonMode::Plain
+            } else {
+                JsonMode::Json
+            },
+            // Records written before the sender format rules must still load.
+            from: self.from.as_deref().map(SenderId::stored).transpose()?,
+            ip: self.ip,
+            time: self.time.map(UnixTimestamp::new),
+            ttl: self.ttl.map(TtlMinutes::new).transpose()?,
+            daytime: self.daytime,
+            translit: self.translit,
+            test: self.test,
+            partner_id: self.partner_id.as_deref().map(PartnerId::new).transpose()?,
+        })
+    }
+}
+
+fn entry(id: OutboxId, state: OutboxState, path: &Path) -> Result<OutboxEntry, OutboxError> {
+    let record = read_record(path)?;
+    let request = record
+        .request
+        .to_request()
+        .map_err(|err| corrupt(path, err))?;
+    let mut results = BTreeMap::new();
+    for (phone, result) in record.results {
+        let phone = RawPhoneNumber::new(phone).map_err(|err| corrupt(path, err))?;
+        let sms_id = result
+            .sms_id
+            .map(SmsId::new)
+            .transpose()
+            .map_err(|err| corrupt(path, err))?;
+        results.insert(
+            phone,
+            OutboxResult {
+                status_code: StatusCode::new(result.status_code),
+                sms_id,
+            },
+        );
+    }
+    Ok(OutboxEntry {
+        id,
+        state,
+        request,
+        attempts: record.attempts,
+        last_error: record.last_error,
+        results,
+    })
+}
+
+fn read_record(path: &Path) -> Result<StoredRecord, OutboxError> {
+    let bytes = fs::read(path)?;
+    serde_json::from_slice(&bytes).map_err(|err| corrupt(path, err))
+}
+
+fn corrupt(path: &Path, err: impl StdError + Send + Sync + 'static) -> OutboxError {
+    OutboxError::Corrupt {
+        path: path.to_path_buf(),
+        source: Box::new(err),
+    }
+}
+
+fn remove_if_exists(path: &Path) -> Result<(), OutboxError> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn unix_millis(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Auth;
+    use crate::client::test_support::{FakeTransport, make_client};
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let sequence = NEXT_SEQUENCE.fetch_add(1, Ordering::Relaxed);
+            let path = std::env::temp_dir()
+                .join(format!("smsru-outbox-test-{}-{sequence}", process::id()));
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn request(phones: &[&str]) -> SendSms {
+        let options = SendOptions {
+            from: Some(SenderId::new("Shop").unwrap()),
+            ttl: Some(TtlMinutes::new(30).unwrap()),
+            translit: true,
+            ..SendOptions::default()
+        };
+        SendSms::to_many(
+            phones
+                .iter()
+                .map(|phone| RawPhoneNumber::new(*phone).unwrap())
+                .collect(),
+            MessageText::new("hello").unwrap(),
+            options,
+        )
+        .unwrap()
+    }
+
+    fn client(transport: FakeTransport) -> SmsRuClient {
+        make_client(Auth::api_id("key").unwrap(), transport)
+    }
+
+    fn unavailable() -> SmsRuError {
+        SmsRuError::Api {
+            status_code: StatusCode::new(220),
+            status_text: None,
+        }
+    }
+
+    #[test]
+    fn request_round_trips_through_storage() {
+        let dir = TempDir::new();
+        let outbox = Outbox::open(&dir.0).unwrap();
+        let id = outbox.enqueue(&request(&["79250000001"])).unwrap();
+
+        let entry = outbox.get(&id).unwrap().unwrap();
+        assert_eq!(entry.state, OutboxState::Pending);
+        match entry.request {
+            SendSms::ToMany(to_many) => {
+                assert_eq!(to_many.recipients()[0].raw(), "79250000001");
+                assert_eq!(to_many.msg().as_str(), "hello");
+                assert_eq!(to_many.options().from.as_ref().unwrap().as_str(), "Shop");
+                assert_eq!(to_many.options().ttl.unwrap().value(), 30);
+                assert!(to_many.options().translit);
+            }
+            other => panic!("unexpected request: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn leases_are_exclusive_and_expired_leases_are_reclaimed() {
+        let dir = TempDir::new();
+        let outbox = Outbox::open(&dir.0).unwrap();
+        let other_worker = Outbox::open(&dir.0).unwrap();
+        let first = outbox.enqueue(&request(&["79250000001"])).unwrap();
+        let second = outbox.enqueue(&request(&["79250000002"])).unwrap();
+
+        let a = outbox.lease(Duration::from_secs(60)).unwrap().unwrap();
+        let b = other_worker.lease(Duration::ZERO).unwrap().unwrap();
+        assert_eq!(a.id(), &first);
+        assert_eq!(b.id(), &second);
+        assert_eq!(
+            outbox.get(&first).unwrap().unwrap().state,
+            OutboxState::Leased
+        );
+
+        // `b` expired immediately, so it is handed out again; `a` is still held.
+        let c = outbox.lease(Duration::from_secs(60)).unwrap().unwrap();
+        assert_eq!(c.id(), &second);
+        assert!(outbox.lease(Duration::from_secs(60)).unwrap().is_none());
+
+        let error = unavailable();
+        assert!(matches!(
+            other_worker.fail(b, &error, 3),
+            Err(OutboxError::LeaseExpired(id)) if id == second
+        ));
+        assert_eq!(outbox.fail(c, &error, 3).unwrap(), OutboxState::Pending);
+        assert_eq!(outbox.get(&second).unwrap().unwrap().attempts, 1);
+    }
+
+    #[test]
+    fn stale_leases_complete_only_while_nobody_else_holds_the_message() {
+        let dir = TempDir::new();
+        let outbox = Outbox::open(&dir.0).unwrap();
+        let response = SendSmsResponse {
+            status: crate::domain::Status::Ok,
+            status_code: StatusCode::new(100),
+            status_text: None,
+            balance: None,
+            sms: Default::default(),
+        };
+
+        // Reclaimed but not leased again: the late completion still counts.
+        let reclaimed = outbox.enqueue(&request(&["79250000001"])).unwrap();
+        let lease = outbox.lease(Duration::ZERO).unwrap().unwrap();
+        outbox.reclaim_expired().unwrap();
+        outbox.complete(lease, &response).unwrap();
+        assert_eq!(
+            outbox.get(&reclaimed).unwrap().unwrap().state,
+            OutboxState::Sent
+        );
+
+        // Leased again by another worker: the stale lease is refused.
+        let released = outbox.enqueue(&request(&["79250000002"])).unwrap();
+        let stale = outbox.lease(Duration::ZERO).unwrap().unwrap();
+        let current = outbox.lease(Duration::from_secs(60)).unwrap().unwrap();
+        assert_eq!(current.id(), &released);
+        assert!(matches!(
+            outbox.complete(stale, &response),
+            Err(OutboxError::LeaseExpired(id)) if id == released
+        ));
+        assert_eq!(
+            outbox.get(&released).unwrap().unwrap().state,
+            OutboxState::Leased
+        );
+        outbox.complete(current, &response).unwrap();
+    }
+
+    #[test]
+    fn corrupt_records_are_moved_aside_once() {
+        let dir = TempDir::new();
+        let outbox = Outbox::open(&dir.0).unwrap();
+        let id = OutboxId::generate();
+        fs::write(outbox.pending_path(&id), b"not json").unwrap();
+
+        match outbox.lease(Duration::ZERO) {
+            Err(OutboxError::Corrupt { path, .. }) => {
+                assert_eq!(path, outbox.state_path(CORRUPT_DIR, &id));
+                assert!(path.exists());
+            }
+            other => panic!("unexpected lease result: {other:?}"),
+        }
+        assert!(outbox.lease(Duration::ZERO).unwrap().is_none());
+    }
+
+    #[test]
+    fn senders_stored_before_the_format_rules_still_load() {
+        let dir = TempDir::new();
+        let outbox = Outbox::open(&dir.0).unwrap();
+        let id = outbox.enqueue(&request(&["79250000001"])).unwrap();
+        let path = outbox.pending_path(&id);
+        let mut record = read_record(&path).unwrap();
+        match &mut record.request {
+            StoredRequest::ToMany { options, .. } => options.from = Some("12-34".to_owned()),
+            other => panic!("unexpected request: {other:?}"),
+        }
+        outbox.write_record(&id, &record, &path).unwrap();
+
+        let lease = outbox.lease(Duration::from_secs(60)).unwrap().unwrap();
+        match lease.request() {
+            SendSms::ToMany(to_many) => {
+                assert_eq!(to_many.options().from.as_ref().unwrap().as_str(), "12-34");
+            }
+            other => panic!("unexpected request: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn leases_left_behind_after_recording_the_outcome_are_dropped() {
+        let dir = TempDir::new();
+        let outbox = Outbox::open(&dir.0).unwrap();
+        let id = outbox.enqueue(&request(&["79250000001"])).unwrap();
+
+        // Simulate a crash in `fail` after the dead record was written.
+        let lease = outbox.lease(Duration::ZERO).unwrap().unwrap();
+        fs::copy(&lease.path, outbox.state_path(DEAD_DIR, &id)).unwrap();
+
+        assert!(outbox.lease(Duration::from_secs(60)).unwrap().is_none());
+        assert_eq!(outbox.get(&id).unwrap().unwrap().state, OutboxState::Dead);
+        assert!(outbox.list_leases().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn process_next_records_sms_ids_and_status_codes() {
+        let json = r#"
+        {
+          "status": "OK",
+          "status_code": 100,
+          "sms": {
+            "79250000001": { "status": "OK", "status_code": 100, "sms_id": "000-1" },
+            "79250000002": { "status": "ERROR", "status_code": 207, "status_text": "no route" }
+          }
+        }
+        "#;
+        let dir = TempDir::new();
+        let outbox = Outbox::open(&dir.0).unwrap();
+        let id = outbox
+            .enqueue(&request(&["79250000001", "79250000002"]))
+            .unwrap();
+
+        let client = client(FakeTransport::new(200, json));
+        let outcome = outbox
+            .process_next(&client, Duration::from_secs(60), 3)
+            .await
+            .unwrap();
+        assert!(matches!(outcome, Some(OutboxOutcome::Sent { id: sent, .. }) if sent == id));
+
+        let entry = outbox.get(&id).unwrap().unwrap();
+        assert_eq!(entry.state, OutboxState::Sent);
+        assert_eq!(entry.sms_ids(), vec![&SmsId::new("000-1").unwrap()]);
+        assert_eq!(
+            entry.results[&RawPhoneNumber::new("79250000002").unwrap()].status_code,
+            StatusCode::new(207)
+        );
+        assert!(
+            outbox
+                .process_next(&client, Duration::from_secs(60), 3)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn retryable_failures_requeue_and_permanent_failures_dead_letter() {
+        let dir = TempDir::new();
+        let outbox = Outbox::open(&dir.0).unwrap();
+        let id = outbox.enqueue(&request(&["79250000001"])).unwrap();
+        let client = client(FakeTransport::sequence(vec![
+            (
+                200,
+                r#"{"status": "ERROR", "status_code": 220, "status_text": "unavailable"}"#,
+            ),
+            (
+                200,
+                r#"{"status": "ERROR", "status_code": 202, "status_text": "bad number"}"#,
+            ),
+        ]));
+
+        let outcome = outbox
+            .process_next(&client, Duration::from_secs(60), 5)
+            .await
+            .unwrap();
+        assert!(matches!(outcome, Some(OutboxOutcome::Retrying { .. })));
+        assert_eq!(
+            outbox.get(&id).unwrap().unwrap().state,
+            OutboxState::Pending
+        );
+
+        let outcome = outbox
+            .process_next(&client, Duration::from_secs(60), 5)
+            .await
+            .unwrap();
+        assert!(matches!(outcome, Some(OutboxOutcome::DeadLettered { .. })));
+
+        let dead = outbox.dead_letters().unwrap();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].id, id);
+        assert_eq!(dead[0].attempts, 2);
+        assert!(dead[0].last_error.as_deref().unwrap().contains("202"));
+    }
+
+    #[tokio::test]
+    async fn failures_that_may_have_been_accepted_are_never_resent() {
+        let dir = TempDir::new();
+        let outbox = Outbox::open(&dir.0).unwrap();
+        let first = outbox.enqueue(&request(&["79250000001"])).unwrap();
+        let second = outbox.enqueue(&request(&["79250000002"])).unwrap();
+        let transport = FakeTransport::sequence(vec![(503, "")]);
+        transport.queue_timeout();
+        let client = client(transport.clone());
+
+        for id in [&first, &second] {
+            let outcome = outbox
+                .process_next(&client, Duration::from_secs(60), 5)
+                .await
+                .unwrap();
+            assert!(
+                matches!(outcome, Some(OutboxOutcome::Uncertain { id: parked, .. }) if &parked == id)
+            );
+        }
+        assert!(
+            outbox
+                .process_next(&client, Duration::from_secs(60), 5)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(transport.requested_urls().len(), 2);
+
+        let uncertain = outbox.uncertain().unwrap();
+        assert_eq!(uncertain.len(), 2);
+        assert_eq!(uncertain[0].state, OutboxState::Uncertain);
+        assert!(outbox.dead_letters().unwrap().is_empty());
+
+        assert!(outbox.requeue(&first).unwrap());
+        assert!(!outbox.requeue(&first).unwrap());
+        assert_eq!(
+            outbox.get(&first).unwrap().unwrap().state,
+            OutboxState::Pending
+        );
+    }
+
+    #[test]
+    fn attempts_are_capped() {
+        let dir = TempDir::new();
+        let outbox = Outbox::open(&dir.0).unwrap();
+        let id = outbox.enqueue(&request(&["79250000001"])).unwrap();
+        let error = unavailable();
+        assert!(is_permanent_error(&SmsRuError::HttpStatus {
+            status: 400,
+            body: None
+        }));
+        assert!(!is_permanent_error(&error));
+
+        let lease = outbox.lease(Duration::from_secs(60)).unwrap().unwrap();
+        assert_eq!(outbox.fail(lease, &error, 2).unwrap(), OutboxState::Pending);
+        let lease = outbox.lease(Duration::from_secs(60)).unwrap().unwrap();
+        assert_eq!(lease.attempts(), 1);
+        assert_eq!(outbox.fail(lease, &error, 2).unwrap(), OutboxState::Dead);
+        assert_eq!(outbox.get(&id).unwrap().unwrap().state, OutboxState::Dead);
+    }
+}
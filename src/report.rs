@@ -0,0 +1,464 @@
+//! Delivery reports built from `sms/send` and `sms/status` responses, written as CSV or JSON
+//! Lines with a stable column schema.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::time::SystemTime;
+
+use serde::Serialize;
+
+use crate::csv;
+use crate::domain::{
+    CheckStatusResponse, RawPhoneNumber, SendSmsResponse, SmsId, StatusCode, UnixTimestamp,
+};
+
+/// Column names, in output order, shared by the CSV header and the JSON Lines keys.
+pub const REPORT_COLUMNS: [&str; 7] = [
+    "phone",
+    "sms_id",
+    "status_code",
+    "status_text",
+    "cost",
+    "sent_at",
+    "status_at",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// One message (or rejected recipient) in a [`DeliveryReport`].
+pub struct ReportRow {
+    /// Recipient number; `None` for status results of messages not sent through this report.
+    pub phone: Option<RawPhoneNumber>,
+    /// SMS id assigned by SMS.RU; `None` for rejected recipients.
+    pub sms_id: Option<SmsId>,
+    /// Latest known status code: the delivery status once checked, else the send result.
+    pub status_code: StatusCode,
+    /// Status text that came with [`ReportRow::status_code`].
+    pub status_text: Option<String>,
+    /// Message cost from `sms/status`.
+    pub cost: Option<String>,
+    /// When the send response was recorded.
+    pub sent_at: Option<UnixTimestamp>,
+    /// When the latest status response was recorded.
+    pub status_at: Option<UnixTimestamp>,
+}
+
+#[derive(Debug, Clone, Default)]
+/// Rows merged from send and status responses, matched by SMS id.
+pub struct DeliveryReport {
+    rows: Vec<ReportRow>,
+    by_sms_id: BTreeMap<SmsId, usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Output format of a [`ReportWriter`].
+pub enum ReportFormat {
+    /// RFC 4180 CSV with a header row and CRLF line endings.
+    Csv,
+    /// One JSON object per line; missing values are `null`.
+    JsonLines,
+}
+
+#[derive(Debug)]
+/// Writes [`ReportRow`]s in a [`ReportFormat`].
+pub struct ReportWriter<W: Write> {
+    out: W,
+    format: ReportFormat,
+    mask: Option<(usize, usize)>,
+    escape_formulas: bool,
+    header_written: bool,
+}
+
+impl DeliveryReport {
+    /// Create an empty report.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add one row per recipient of a send response, stamped with `sent_at`.
+    pub fn add_send(&mut self, response: &SendSmsResponse, sent_at: SystemTime) {
+        let sent_at = UnixTimestamp::from_system_time(sent_at);
+        for (phone, result) in &response.sms {
+            let row = ReportRow {
+                phone: Some(phone.clone()),
+                sms_id: result.sms_id.clone(),
+                status_code: result.status_code,
+                status_text: result.status_text.clone(),
+                cost: None,
+                sent_at: Some(sent_at),
+                status_at: None,
+            };
+            self.push(row);
+        }
+    }
+
+    /// Update rows with the results of a status response, stamped with `checked_at`.
+    ///
+    /// Ids without a matching row get a new row without a phone number.
+    pub fn add_status(&mut self, response: &CheckStatusResponse, checked_at: SystemTime) {
+        let checked_at = UnixTimestamp::from_system_time(checked_at);
+        for (sms_id, result) in &response.sms {
+            match self.by_sms_id.get(sms_id) {
+                Some(&index) => {
+                    let row = &mut self.rows[index];
+                    row.status_code = result.status_code;
+                    row.status_text = result.status_text.clone();
+                    row.cost = result.cost.clone().or(row.cost.take());
+                    row.status_at = Some(checked_at);
+                }
+                None => self.push(ReportRow {
+                    phone: None,
+                    sms_id: Some(sms_id.clone()),
+                    status_code: result.status_code,
+                    status_text: result.status_text.clone(),
+                    cost: result.cost.clone(),
+                    sent_at: None,
+                    status_at: Some(checked_at),
+                }),
+            }
+        }
+    }
+
+    /// Rows in the order they were added.
+    pub fn rows(&self) -> &[ReportRow] {
+        &self.rows
+    }
+
+    fn push(&mut self, row: ReportRow) {
+        if let Some(sms_id) = &row.sms_id {
+            self.by_sms_id.insert(sms_id.clone(), self.rows.len());
+        }
+        self.rows.push(row);
+    }
+}
+
+impl<W: Write> ReportWriter<W> {
+    /// Write to `out` in `format`.
+    pub fn new(out: W, format: ReportFormat) -> Self {
+        Self {
+            out,
+            format,
+            mask: None,
+            escape_formulas: true,
+            header_written: false,
+        }
+    }
+
+    /// Replace phone characters with `*`, keeping the first `keep_start` and the last
+    /// `keep_end`. Numbers too short to keep both parts are masked entirely.
+    pub fn mask_phones(mut self, keep_start: usize, keep_end: usize) -> Self {
+        self.mask = Some((keep_start, keep_end));
+        self
+    }
+
+    /// Prefix CSV `phone` and `status_text` values starting with `=`, `+`, `-` or `@` with `'`,
+    /// so spreadsheets do not evaluate them as formulas. On by default; JSON Lines is unaffected.
+    pub fn escape_formulas(mut self, escape: bool) -> Self {
+        self.escape_formulas = escape;
+        self
+    }
+
+    /// Write one row (and the CSV header before the first one).
+    pub fn write_row(&mut self, row: &ReportRow) -> io::Result<()> {
+        let phone = row.phone.as_ref().map(|phone| match self.mask {
+            Some((keep_start, keep_end)) => mask(phone.raw(), keep_start, keep_end),
+            None => phone.raw().to_owned(),
+        });
+        let sent_at = row.sent_at.map(format_timestamp);
+        let status_at = row.status_at.map(format_timestamp);
+
+        match self.format {
+            ReportFormat::Csv => {
+                self.write_header()?;
+                let status_code = row.status_code.as_i32().to_string();
+                let phone = csv_text(phone.as_deref(), self.escape_formulas);
+                let status_text = csv_text(row.status_text.as_deref(), self.escape_formulas);
+                csv::write_record(
+                    &mut self.out,
+                    &[
+                        &phone,
+                        row.sms_id.as_ref().map_or("", SmsId::as_str),
+                        &status_code,
+                        &status_text,
+                        row.cost.as_deref().unwrap_or(""),
+                        sent_at.as_deref().unwrap_or(""),
+                        status_at.as_deref().unwrap_or(""),
+                    ],
+                )
+            }
+            ReportFormat::JsonLines => {
+                let line = JsonRow {
+                    phone: phone.as_deref(),
+                    sms_id: row.sms_id.as_ref().map(SmsId::as_str),
+                    status_code: row.status_code.as_i32(),
+                    status_text: row.status_text.as_deref(),
+                    cost: row.cost.as_deref(),
+                    sent_at: sent_at.as_deref(),
+                    status_at: status_at.as_deref(),
+                };
+                serde_json::to_writer(&mut self.out, &line)?;
+                self.out.write_all(b"\n")
+            }
+        }
+    }
+
+    /// Write every row of `report`.
+    pub fn write_report(&mut self, report: &DeliveryReport) -> io::Result<()> {
+        report.rows().iter().try_for_each(|row| self.write_row(row))
+    }
+
+    /// Write the CSV header if no row was written, flush, and return the output.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.format == ReportFormat::Csv {
+            self.write_header()?;
+        }
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        if !self.header_written {
+            self.header_written = true;
+            csv::write_record(&mut self.out, &REPORT_COLUMNS)?;
+        }
+        Ok(())
+    }
+}
+
+// Field order and names must match `REPORT_COLUMNS`.
+#[derive(Serialize)]
+struct JsonRow<'a> {
+    phone: Option<&'a str>,
+    sms_id: Option<&'a str>,
+    status_code: i32,
+    status_text: Option<&'a str>,
+    cost: Option<&'a str>,
+    sent_at: Option<&'a str>,
+    status_at: Option<&'a str>,
+}
+
+fn mask(phone: &str, keep_start: usize, keep_end: usize) -> String {
+    let len = phone.chars().count();
+    if len <= keep_start + keep_end {
+        return "*".repeat(len);
+    }
+    phone
+        .chars()
+        .enumerate()
+        .map(|(index, ch)| {
+            if index < keep_start || index >= len - keep_end {
+                ch
+            } else {
+                '*'
+            }
+        })
+        .collect()
+}
+
+fn csv_text(value: Option<&str>, escape_formulas: bool) -> Cow<'_, str> {
+    let value = value.unwrap_or("");
+    if escape_formulas {
+        escape_formula(value)
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+fn escape_formula(value: &str) -> Cow<'_, str> {
+    if value.starts_with(['=', '+', '-', '@']) {
+        Cow::Owned(format!("'{value}"))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+/// Format as RFC 3339 in UTC, e.g. `2024-03-01T12:30:00Z`.
+fn format_timestamp(timestamp: UnixTimestamp) -> String {
+    let secs = timestamp.value();
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+
+    // Civil-from-days (proleptic Gregorian calendar), valid for all u64 Unix days in range.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{SmsResult, SmsStatusResult, Status};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn report() -> DeliveryReport {
+        let phone = |raw: &str| RawPhoneNumber::new(raw).unwrap();
+        let mut sms = BTreeMap::new();
+        sms.insert(
+            phone("79251234567"),
+            SmsResult {
+                status: Status::Ok,
+                status_code: StatusCode::new(100),
+                status_text: None,
+                sms_id: Some(SmsId::new("000-1").unwrap()),
+            },
+        );
+        sms.insert(
+            phone("79250000000"),
+            SmsResult {
+                status: Status::Error,
+                status_code: StatusCode::new(207),
+                status_text: Some("no route, sorry".to_owned()),
+                sms_id: None,
+            },
+        );
+        let send = SendSmsResponse {
+            status: Status::Ok,
+            status_code: StatusCode::new(100),
+            status_text: None,
+            balance: None,
+            sms,
+        };
+
+        let mut statuses = BTreeMap::new();
+        statuses.insert(
+            SmsId::new("000-1").unwrap(),
+            SmsStatusResult {
+                status: Status::Ok,
+                status_code: StatusCode::new(103),
+                status_text: Some("Delivered".to_owned()),
+                cost: Some("1.50".to_owned()),
+            },
+        );
+        let status = CheckStatusResponse {
+            status: Status::Ok,
+            status_code: StatusCode::new(100),
+            status_text: None,
+            balance: None,
+            sms: statuses,
+        };
+
+        let mut report = DeliveryReport::new();
+        report.add_send(&send, at(1_709_296_200));
+        report.add_status(&status, at(1_709_296_260));
+        report
+    }
+
+    #[test]
+    fn status_updates_are_merged_by_sms_id() {
+        let report = report();
+        assert_eq!(report.rows().len(), 2);
+        let delivered = report
+            .rows()
+            .iter()
+            .find(|row| row.sms_id.is_some())
+            .unwrap();
+        assert_eq!(delivered.status_code.as_i32(), 103);
+        assert_eq!(delivered.cost.as_deref(), Some("1.50"));
+        assert!(delivered.sent_at.is_some() && delivered.status_at.is_some());
+    }
+
+    #[test]
+    fn csv_has_stable_header_and_masks_phones() {
+        let mut writer = ReportWriter::new(Vec::new(), ReportFormat::Csv).mask_phones(4, 2);
+        writer.write_report(&report()).unwrap();
+        let csv = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert_eq!(
+            csv,
+            "phone,sms_id,status_code,status_text,cost,sent_at,status_at\r\n\
+             7925*****00,,207,\"no route, sorry\",,2024-03-01T12:30:00Z,\r\n\
+             7925*****67,000-1,103,Delivered,1.50,2024-03-01T12:30:00Z,2024-03-01T12:31:00Z\r\n"
+        );
+
+        let empty = ReportWriter::new(Vec::new(), ReportFormat::Csv)
+            .finish()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(empty).unwrap(),
+            "phone,sms_id,status_code,status_text,cost,sent_at,status_at\r\n"
+        );
+    }
+
+    #[test]
+    fn csv_escapes_values_that_start_a_formula() {
+        let row = ReportRow {
+            phone: Some(RawPhoneNumber::new("+79251234567").unwrap()),
+            sms_id: None,
+            status_code: StatusCode::new(207),
+            status_text: Some("=HYPERLINK(\"http://evil\")".to_owned()),
+            cost: None,
+            sent_at: None,
+            status_at: None,
+        };
+        let write = |mut writer: ReportWriter<Vec<u8>>| {
+            writer.write_row(&row).unwrap();
+            let out = String::from_utf8(writer.finish().unwrap()).unwrap();
+            out.lines().nth(1).unwrap().to_owned()
+        };
+
+        assert_eq!(
+            write(ReportWriter::new(Vec::new(), ReportFormat::Csv)),
+            "'+79251234567,,207,\"'=HYPERLINK(\"\"http://evil\"\")\",,,"
+        );
+        assert_eq!(
+            write(ReportWriter::new(Vec::new(), ReportFormat::Csv).escape_formulas(false)),
+            "+79251234567,,207,\"=HYPERLINK(\"\"http://evil\"\")\",,,"
+        );
+
+        let mut json = ReportWriter::new(Vec::new(), ReportFormat::JsonLines);
+        json.write_row(&row).unwrap();
+        let json = String::from_utf8(json.finish().unwrap()).unwrap();
+        assert!(json.starts_with(r#"{"phone":"+79251234567","#));
+        assert_eq!(escape_formula("-1"), "'-1");
+        assert_eq!(escape_formula("@SUM(A1)"), "'@SUM(A1)");
+        assert_eq!(escape_formula("Delivered"), "Delivered");
+    }
+
+    #[test]
+    fn json_lines_use_the_same_columns() {
+        let mut writer = ReportWriter::new(Vec::new(), ReportFormat::JsonLines);
+        writer.write_report(&report()).unwrap();
+        let out = String::from_utf8(writer.finish().unwrap()).unwrap();
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[1],
+            r#"{"phone":"79251234567","sms_id":"000-1","status_code":103,"status_text":"Delivered","cost":"1.50","sent_at":"2024-03-01T12:30:00Z","status_at":"2024-03-01T12:31:00Z"}"#
+        );
+        let keys = serde_json::from_str::<serde_json::Value>(lines[0])
+            .unwrap()
+            .as_object()
+            .unwrap()
+            .len();
+        assert_eq!(keys, REPORT_COLUMNS.len());
+    }
+
+    #[test]
+    fn timestamps_and_masks_handle_edges() {
+        assert_eq!(
+            format_timestamp(UnixTimestamp::new(0)),
+            "1970-01-01T00:00:00Z"
+        );
+        assert_eq!(
+            format_timestamp(UnixTimestamp::new(951_782_400)),
+            "2000-02-29T00:00:00Z"
+        );
+        assert_eq!(mask("123", 2, 2), "***");
+    }
+}
@@ -26,7 +26,7 @@ pub use callcheck_status::{
 };
 pub use check_cost::{decode_check_cost_json_response, encode_check_cost_form};
 pub use check_status::{decode_check_status_json_response, encode_check_status_form};
-pub use send_sms::{decode_send_sms_json_response, encode_send_sms_form};
+pub use send_sms::{decode_send_sms_json_response, encode_send_sms_form, send_sms_fingerprint};
 pub use stoplist::{
     decode_get_stoplist_json_response, encode_add_stoplist_form, encode_get_stoplist_form,
     encode_remove_stoplist_form,
@@ -90,6 +90,23 @@ pub fn encode_send_sms_form(request: &SendSms) -> Vec<(String, String)> {
     params
 }
 
+/// Stable fingerprint of the form `request` is sent as (FNV-1a 64 over every field, as hex).
+///
+/// Stored next to persisted requests to detect that a resumed or repeated send changed.
+pub fn send_sms_fingerprint(request: &SendSms) -> String {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    let mut hash = OFFSET;
+    for (key, value) in encode_send_sms_form(request) {
+        for byte in key.bytes().chain([0]).chain(value.bytes()).chain([0]) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(PRIME);
+        }
+    }
+    format!("{hash:016x}")
+}
+
 fn push_json(params: &mut Vec<(String, String)>, options: &SendOptions) {
     if options.json == JsonMode::Json {
         params.push(("json".to_owned(), "1".to_owned()));
@@ -219,6 +236,24 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn fingerprint_changes_with_any_field() {
+        let request = |text: &str, translit: bool| {
+            let options = SendOptions {
+                translit,
+                ..Default::default()
+            };
+            let phone = RawPhoneNumber::new("79251234567").unwrap();
+            SendSms::to_many(vec![phone], MessageText::new(text).unwrap(), options).unwrap()
+        };
+
+        let fingerprint = send_sms_fingerprint(&request("hi", false));
+        assert_eq!(fingerprint.len(), 16);
+        assert_eq!(fingerprint, send_sms_fingerprint(&request("hi", false)));
+        assert_ne!(fingerprint, send_sms_fingerprint(&request("hi!", false)));
+        assert_ne!(fingerprint, send_sms_fingerprint(&request("hi", true)));
+    }
+
     #[test]
     fn encode_to_many_form_params() {
         let p1 = RawPhoneNumber::new("+79251234567").unwrap();